@@ -210,15 +210,19 @@ func main() {
 	}
 
 	hub := grpc.NewStreamHub()
-	if err := (&controller.ArgonConfigReconciler{
+	reconciler := &controller.ArgonConfigReconciler{
 		Client:       mgr.GetClient(),
 		Scheme:       mgr.GetScheme(),
 		IngressClass: ingressClass,
 		StreamHub:    hub,
-	}).SetupWithManager(mgr); err != nil {
+		Recorder:     mgr.GetEventRecorderFor("argon-controller"),
+	}
+	if err := reconciler.SetupWithManager(mgr); err != nil {
 		setupLog.Error(err, "unable to create controller", "controller", "ArgonConfig")
 		os.Exit(1)
 	}
+	// snapshots rejected by dataplanes show up as events on the Ingress concerned
+	hub.OnNodeStatus(reconciler.ReportNodeStatus)
 
 	grpcAddr := ":18000"
 	grpcServer, err := grpc.NewServer(hub, grpcAddr, grpcServerName)
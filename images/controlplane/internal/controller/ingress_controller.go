@@ -21,14 +21,17 @@ import (
 	"crypto/sha256"
 	"encoding/hex"
 	"fmt"
+	"strings"
 	"time"
 
 	. "argon/internal/grpc"
 	. "argon/internal/model"
 
+	corev1 "k8s.io/api/core/v1"
 	discoveryv1 "k8s.io/api/discovery/v1"
 	networkingv1 "k8s.io/api/networking/v1"
 	"k8s.io/apimachinery/pkg/runtime"
+	"k8s.io/client-go/tools/record"
 	ctrl "sigs.k8s.io/controller-runtime"
 	"sigs.k8s.io/controller-runtime/pkg/builder"
 	"sigs.k8s.io/controller-runtime/pkg/client"
@@ -45,6 +48,7 @@ type ArgonConfigReconciler struct {
 	lastVersion     string
 	currentSnapshot Snapshot
 	StreamHub       *StreamHub
+	Recorder        record.EventRecorder
 }
 
 const ingressClassIndexKey = "spec.ingressClassName"
@@ -120,6 +124,7 @@ func (r *ArgonConfigReconciler) parseEndpoints(ctx context.Context, ingList *net
 		for _, rule := range ing.Spec.Rules {
 			// Build target proxy for this rule using helper
 			t := buildTargetFromRule(ctx, r.Client, ing.Namespace, rule, *targetEndpoint, bundle)
+			t.Ingress = ing.Namespace + "/" + ing.Name
 			if len(t.Path) > 0 {
 				targetProxies = append(targetProxies, t)
 			}
@@ -138,11 +143,13 @@ func (r *ArgonConfigReconciler) ToSnapshot(targets []TargetProxy) Snapshot {
 		GeneratedAtUnixSec: time.Now().Unix(),
 		ResourceVersions:   make(map[string]string),
 		TLS:                make([]TLSSecret, 0),
+		Owners:             make(map[string]string),
 	}
 
 	for _, tp := range targets {
 		if tp.SNI.Name != "" {
 			snap.TLS = append(snap.TLS, tp.SNI)
+			snap.Owners["ServerTlsBundle/"+tp.SNI.Name] = tp.Ingress
 		}
 
 		for path, te := range tp.Path {
@@ -155,22 +162,31 @@ func (r *ArgonConfigReconciler) ToSnapshot(targets []TargetProxy) Snapshot {
 				Cluster:  clusterName,
 				Priority: int(RoutePriority(path, *te.PathType)),
 			})
+			snap.Owners["Route/"+tp.Host+path] = tp.Ingress
+			snap.Owners["Cluster/"+clusterName] = tp.Ingress
 
 			cluster := Cluster{
-				Name:            clusterName,
-				LBPolicy:        te.LBAlgorithm,
-				Endpoints:       make([]Endpoint, 0, len(te.Addresses)),
-				TimeoutMs:       te.TimeoutMs,
-				Retries:         te.Retries,
-				BackendProtocol: te.BackendProtocol,
-				RewriteHeaders:  te.RewriteHeaders,
-				Auth:            te.Auth,
+				Name:             clusterName,
+				LBPolicy:         te.LBAlgorithm,
+				Endpoints:        make([]Endpoint, 0, len(te.Addresses)),
+				TimeoutMs:        te.TimeoutMs,
+				Retries:          te.Retries,
+				BackendProtocol:  te.BackendProtocol,
+				RewriteHeaders:   te.RewriteHeaders,
+				Auth:             te.Auth,
+				HashPolicy:       te.HashPolicy,
+				HealthCheck:      te.HealthCheck,
+				OutlierDetection: te.OutlierDetection,
+				CircuitBreaker:   te.CircuitBreaker,
+				RetryPolicy:      te.RetryPolicy,
+				RetryBudget:      te.RetryBudget,
 			}
 			for _, a := range te.Addresses {
 				cluster.Endpoints = append(cluster.Endpoints, Endpoint{
 					Address: a,
 					Port:    te.Port,
 					Weight:  1,
+					Zone:    te.Zones[a],
 				})
 			}
 
@@ -188,3 +204,30 @@ func (r *ArgonConfigReconciler) ToSnapshot(targets []TargetProxy) Snapshot {
 
 	return snap
 }
+
+// ReportNodeStatus turns a dataplane NACK into Warning events on the Ingresses whose
+// routes, clusters or TLS bundles were rejected.
+func (r *ArgonConfigReconciler) ReportNodeStatus(status NodeStatus, owners map[string]string) {
+	ctx := context.Background()
+	logger := log.FromContext(ctx).WithValues("node", status.NodeID, "version", status.Version)
+	if len(status.Errors) == 0 {
+		logger.V(1).Info("snapshot accepted")
+		return
+	}
+
+	for _, e := range status.Errors {
+		logger.Info("snapshot part rejected", "kind", e.GetKind(), "name", e.GetName(), "message", e.GetMessage())
+		owner, ok := owners[e.GetKind()+"/"+e.GetName()]
+		if !ok || r.Recorder == nil {
+			continue
+		}
+		ns, name, _ := strings.Cut(owner, "/")
+		var ing networkingv1.Ingress
+		if err := r.Get(ctx, client.ObjectKey{Namespace: ns, Name: name}, &ing); err != nil {
+			logger.Error(err, "failed to get ingress for rejection", "ingress", owner)
+			continue
+		}
+		r.Recorder.Eventf(&ing, corev1.EventTypeWarning, "ConfigRejected",
+			"dataplane %s rejected %s %q: %s", status.NodeID, e.GetKind(), e.GetName(), e.GetMessage())
+	}
+}
@@ -152,12 +152,30 @@ func parseAnnotations(annotations map[string]string) *TargetEndpoint {
 			lbAlgorithm = LBLeastConn
 		case LBRoundRobin:
 			lbAlgorithm = LBRoundRobin
+		case LBWeightedRoundRobin:
+			lbAlgorithm = LBWeightedRoundRobin
+		case LBP2C:
+			lbAlgorithm = LBP2C
+		case LBRingHash:
+			lbAlgorithm = LBRingHash
+		case LBMaglev:
+			lbAlgorithm = LBMaglev
 		default:
 			lbAlgorithm = LBRoundRobin
 		}
 	}
 	te.LBAlgorithm = lbAlgorithm
 
+	if rawHashBy, ok := annotations[HASH_BY_ANNOTATION]; ok {
+		te.HashPolicy = parseHashPolicy(rawHashBy)
+	}
+
+	te.HealthCheck = parseYAMLAnnotation[HealthCheck](annotations, HEALTH_CHECK_ANNOTATION)
+	te.OutlierDetection = parseYAMLAnnotation[OutlierDetection](annotations, OUTLIER_DETECTION_ANNOTATION)
+	te.CircuitBreaker = parseYAMLAnnotation[CircuitBreaker](annotations, CIRCUIT_BREAKER_ANNOTATION)
+	te.RetryPolicy = parseYAMLAnnotation[RetryPolicy](annotations, RETRY_POLICY_ANNOTATION)
+	te.RetryBudget = parseYAMLAnnotation[RetryBudget](annotations, RETRY_BUDGET_ANNOTATION)
+
 	// Auth annotations
 	var auth *AuthConfig
 	if rawURL, ok := annotations[AUTH_URL_ANNOTATION]; ok && strings.TrimSpace(rawURL) != "" {
@@ -232,6 +250,7 @@ func buildTargetFromRule(
 		}
 
 		var allAddrs []string
+		zones := make(map[string]string)
 		var chosenPort *int32
 		var proto = corev1.ProtocolTCP
 		portName, _ := resolveServicePortName(ctx, c, namespace, svcName, &be)
@@ -254,6 +273,11 @@ func buildTargetFromRule(
 					continue
 				}
 				allAddrs = appendUnique(allAddrs, ep.Addresses...)
+				if ep.Zone != nil {
+					for _, a := range ep.Addresses {
+						zones[a] = *ep.Zone
+					}
+				}
 			}
 		}
 
@@ -264,22 +288,68 @@ func buildTargetFromRule(
 		sort.Strings(allAddrs)
 
 		target.Path[p.Path] = TargetEndpoint{
-			Port:            *chosenPort,
-			Protocol:        proto,
-			Addresses:       allAddrs,
-			PathType:        p.PathType,
-			BackendProtocol: base.BackendProtocol,
-			Retries:         base.Retries,
-			TimeoutMs:       base.TimeoutMs,
-			LBAlgorithm:     base.LBAlgorithm,
-			RewriteHeaders:  base.RewriteHeaders,
-			Auth:            base.Auth,
+			Port:             *chosenPort,
+			Protocol:         proto,
+			Addresses:        allAddrs,
+			Zones:            zones,
+			PathType:         p.PathType,
+			BackendProtocol:  base.BackendProtocol,
+			Retries:          base.Retries,
+			TimeoutMs:        base.TimeoutMs,
+			LBAlgorithm:      base.LBAlgorithm,
+			RewriteHeaders:   base.RewriteHeaders,
+			Auth:             base.Auth,
+			HashPolicy:       base.HashPolicy,
+			HealthCheck:      base.HealthCheck,
+			OutlierDetection: base.OutlierDetection,
+			CircuitBreaker:   base.CircuitBreaker,
+			RetryPolicy:      base.RetryPolicy,
+			RetryBudget:      base.RetryBudget,
 		}
 	}
 
 	return target
 }
 
+// parseHashPolicy reads "header:<name>", "cookie:<name>", "query:<name>" or "source-ip";
+// anything else leaves RingHash/Maglev without a key (requests are spread round-robin).
+func parseHashPolicy(raw string) HashPolicy {
+	kind, name, _ := strings.Cut(strings.TrimSpace(raw), ":")
+	name = strings.TrimSpace(name)
+	switch strings.ToLower(strings.TrimSpace(kind)) {
+	case "header":
+		if name != "" {
+			return HashPolicy{Source: "Header", Name: name}
+		}
+	case "cookie":
+		if name != "" {
+			return HashPolicy{Source: "Cookie", Name: name}
+		}
+	case "query":
+		if name != "" {
+			return HashPolicy{Source: "QueryParameter", Name: name}
+		}
+	case "source-ip":
+		return HashPolicy{Source: "SourceIP"}
+	}
+	return HashPolicy{}
+}
+
+// parseYAMLAnnotation decodes a YAML (or JSON) annotation into T; a missing or
+// invalid value yields the zero T, which leaves the setting off.
+func parseYAMLAnnotation[T any](annotations map[string]string, key string) T {
+	var out T
+	raw, ok := annotations[key]
+	if !ok || strings.TrimSpace(raw) == "" {
+		return out
+	}
+	if err := yaml.Unmarshal([]byte(raw), &out); err != nil {
+		var zero T
+		return zero
+	}
+	return out
+}
+
 // parseCSVList splits a comma-separated list, trims whitespace, and removes empties/duplicates.
 func parseCSVList(s string) []string {
 	parts := strings.Split(s, ",")
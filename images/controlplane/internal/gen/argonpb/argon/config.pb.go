@@ -23,7 +23,7 @@ const (
 
 type WatchRequest struct {
 	state         protoimpl.MessageState `protogen:"open.v1"`
-	NodeId        string                 `protobuf:"bytes,1,opt,name=node_id,json=nodeId,proto3" json:"node_id,omitempty"` //
+	NodeId        string                 `protobuf:"bytes,1,opt,name=node_id,json=nodeId,proto3" json:"node_id,omitempty"`
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
@@ -65,6 +65,136 @@ func (x *WatchRequest) GetNodeId() string {
 	return ""
 }
 
+// Sent by the dataplane on the Stream RPC: once when it connects, then once per snapshot or
+// delta received.
+type DiscoveryRequest struct {
+	state           protoimpl.MessageState `protogen:"open.v1"`
+	NodeId          string                 `protobuf:"bytes,1,opt,name=node_id,json=nodeId,proto3" json:"node_id,omitempty"`
+	VersionInfo     string                 `protobuf:"bytes,2,opt,name=version_info,json=versionInfo,proto3" json:"version_info,omitempty"`             // version of the snapshot in use; empty before the first
+	ResponseVersion string                 `protobuf:"bytes,3,opt,name=response_version,json=responseVersion,proto3" json:"response_version,omitempty"` // snapshot acknowledged by this message; empty on connect
+	Errors          []*ConfigError         `protobuf:"bytes,4,rep,name=errors,proto3" json:"errors,omitempty"`                                          // NACK when set: parts of response_version that were rejected
+	unknownFields   protoimpl.UnknownFields
+	sizeCache       protoimpl.SizeCache
+}
+
+func (x *DiscoveryRequest) Reset() {
+	*x = DiscoveryRequest{}
+	mi := &file_argon_config_proto_msgTypes[1]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *DiscoveryRequest) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*DiscoveryRequest) ProtoMessage() {}
+
+func (x *DiscoveryRequest) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[1]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use DiscoveryRequest.ProtoReflect.Descriptor instead.
+func (*DiscoveryRequest) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{1}
+}
+
+func (x *DiscoveryRequest) GetNodeId() string {
+	if x != nil {
+		return x.NodeId
+	}
+	return ""
+}
+
+func (x *DiscoveryRequest) GetVersionInfo() string {
+	if x != nil {
+		return x.VersionInfo
+	}
+	return ""
+}
+
+func (x *DiscoveryRequest) GetResponseVersion() string {
+	if x != nil {
+		return x.ResponseVersion
+	}
+	return ""
+}
+
+func (x *DiscoveryRequest) GetErrors() []*ConfigError {
+	if x != nil {
+		return x.Errors
+	}
+	return nil
+}
+
+type ConfigError struct {
+	state         protoimpl.MessageState `protogen:"open.v1"`
+	Kind          string                 `protobuf:"bytes,1,opt,name=kind,proto3" json:"kind,omitempty"` // "Route","Cluster","ServerTlsBundle","Snapshot","SnapshotDelta"
+	Name          string                 `protobuf:"bytes,2,opt,name=name,proto3" json:"name,omitempty"` // cluster name, "<host><path>" of a route, TLS bundle name
+	Message       string                 `protobuf:"bytes,3,opt,name=message,proto3" json:"message,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *ConfigError) Reset() {
+	*x = ConfigError{}
+	mi := &file_argon_config_proto_msgTypes[2]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *ConfigError) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*ConfigError) ProtoMessage() {}
+
+func (x *ConfigError) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[2]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use ConfigError.ProtoReflect.Descriptor instead.
+func (*ConfigError) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{2}
+}
+
+func (x *ConfigError) GetKind() string {
+	if x != nil {
+		return x.Kind
+	}
+	return ""
+}
+
+func (x *ConfigError) GetName() string {
+	if x != nil {
+		return x.Name
+	}
+	return ""
+}
+
+func (x *ConfigError) GetMessage() string {
+	if x != nil {
+		return x.Message
+	}
+	return ""
+}
+
 type Endpoint struct {
 	state         protoimpl.MessageState `protogen:"open.v1"`
 	Address       string                 `protobuf:"bytes,1,opt,name=address,proto3" json:"address,omitempty"`
@@ -78,7 +208,7 @@ type Endpoint struct {
 
 func (x *Endpoint) Reset() {
 	*x = Endpoint{}
-	mi := &file_argon_config_proto_msgTypes[1]
+	mi := &file_argon_config_proto_msgTypes[3]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -90,7 +220,7 @@ func (x *Endpoint) String() string {
 func (*Endpoint) ProtoMessage() {}
 
 func (x *Endpoint) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[1]
+	mi := &file_argon_config_proto_msgTypes[3]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -103,7 +233,7 @@ func (x *Endpoint) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use Endpoint.ProtoReflect.Descriptor instead.
 func (*Endpoint) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{1}
+	return file_argon_config_proto_rawDescGZIP(), []int{3}
 }
 
 func (x *Endpoint) GetAddress() string {
@@ -113,58 +243,580 @@ func (x *Endpoint) GetAddress() string {
 	return ""
 }
 
-func (x *Endpoint) GetPort() int32 {
+func (x *Endpoint) GetPort() int32 {
+	if x != nil {
+		return x.Port
+	}
+	return 0
+}
+
+func (x *Endpoint) GetWeight() int32 {
+	if x != nil {
+		return x.Weight
+	}
+	return 0
+}
+
+func (x *Endpoint) GetZone() string {
+	if x != nil {
+		return x.Zone
+	}
+	return ""
+}
+
+func (x *Endpoint) GetRegion() string {
+	if x != nil {
+		return x.Region
+	}
+	return ""
+}
+
+type HeaderRewrite struct {
+	state         protoimpl.MessageState `protogen:"open.v1"`
+	Name          string                 `protobuf:"bytes,1,opt,name=name,proto3" json:"name,omitempty"`
+	Mode          string                 `protobuf:"bytes,2,opt,name=mode,proto3" json:"mode,omitempty"`
+	Value         string                 `protobuf:"bytes,3,opt,name=value,proto3" json:"value,omitempty"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *HeaderRewrite) Reset() {
+	*x = HeaderRewrite{}
+	mi := &file_argon_config_proto_msgTypes[4]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *HeaderRewrite) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*HeaderRewrite) ProtoMessage() {}
+
+func (x *HeaderRewrite) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[4]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use HeaderRewrite.ProtoReflect.Descriptor instead.
+func (*HeaderRewrite) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{4}
+}
+
+func (x *HeaderRewrite) GetName() string {
+	if x != nil {
+		return x.Name
+	}
+	return ""
+}
+
+func (x *HeaderRewrite) GetMode() string {
+	if x != nil {
+		return x.Mode
+	}
+	return ""
+}
+
+func (x *HeaderRewrite) GetValue() string {
+	if x != nil {
+		return x.Value
+	}
+	return ""
+}
+
+type HashPolicy struct {
+	state         protoimpl.MessageState `protogen:"open.v1"`
+	Source        string                 `protobuf:"bytes,1,opt,name=source,proto3" json:"source,omitempty"` // "Header","Cookie","QueryParameter","SourceIP"
+	Name          string                 `protobuf:"bytes,2,opt,name=name,proto3" json:"name,omitempty"`     // header/cookie/query parameter name
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *HashPolicy) Reset() {
+	*x = HashPolicy{}
+	mi := &file_argon_config_proto_msgTypes[5]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *HashPolicy) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*HashPolicy) ProtoMessage() {}
+
+func (x *HashPolicy) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[5]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use HashPolicy.ProtoReflect.Descriptor instead.
+func (*HashPolicy) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{5}
+}
+
+func (x *HashPolicy) GetSource() string {
+	if x != nil {
+		return x.Source
+	}
+	return ""
+}
+
+func (x *HashPolicy) GetName() string {
+	if x != nil {
+		return x.Name
+	}
+	return ""
+}
+
+type SessionAffinity struct {
+	state         protoimpl.MessageState `protogen:"open.v1"`
+	CookieName    string                 `protobuf:"bytes,1,opt,name=cookie_name,json=cookieName,proto3" json:"cookie_name,omitempty"` // enables cookie affinity when set
+	CookiePath    string                 `protobuf:"bytes,2,opt,name=cookie_path,json=cookiePath,proto3" json:"cookie_path,omitempty"` // default "/"
+	MaxAgeSec     int64                  `protobuf:"varint,3,opt,name=max_age_sec,json=maxAgeSec,proto3" json:"max_age_sec,omitempty"` // 0 = session cookie
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *SessionAffinity) Reset() {
+	*x = SessionAffinity{}
+	mi := &file_argon_config_proto_msgTypes[6]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *SessionAffinity) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*SessionAffinity) ProtoMessage() {}
+
+func (x *SessionAffinity) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[6]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use SessionAffinity.ProtoReflect.Descriptor instead.
+func (*SessionAffinity) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{6}
+}
+
+func (x *SessionAffinity) GetCookieName() string {
+	if x != nil {
+		return x.CookieName
+	}
+	return ""
+}
+
+func (x *SessionAffinity) GetCookiePath() string {
+	if x != nil {
+		return x.CookiePath
+	}
+	return ""
+}
+
+func (x *SessionAffinity) GetMaxAgeSec() int64 {
+	if x != nil {
+		return x.MaxAgeSec
+	}
+	return 0
+}
+
+type HealthCheck struct {
+	state              protoimpl.MessageState `protogen:"open.v1"`
+	Path               string                 `protobuf:"bytes,1,opt,name=path,proto3" json:"path,omitempty"`                                                         // enables active HTTP health checks when set
+	IntervalMs         int32                  `protobuf:"varint,2,opt,name=interval_ms,json=intervalMs,proto3" json:"interval_ms,omitempty"`                          // default 5000
+	TimeoutMs          int32                  `protobuf:"varint,3,opt,name=timeout_ms,json=timeoutMs,proto3" json:"timeout_ms,omitempty"`                             // default 1000
+	HealthyThreshold   int32                  `protobuf:"varint,4,opt,name=healthy_threshold,json=healthyThreshold,proto3" json:"healthy_threshold,omitempty"`        // default 2
+	UnhealthyThreshold int32                  `protobuf:"varint,5,opt,name=unhealthy_threshold,json=unhealthyThreshold,proto3" json:"unhealthy_threshold,omitempty"`  // default 3
+	ExpectedStatuses   []int32                `protobuf:"varint,6,rep,packed,name=expected_statuses,json=expectedStatuses,proto3" json:"expected_statuses,omitempty"` // default 200-399
+	unknownFields      protoimpl.UnknownFields
+	sizeCache          protoimpl.SizeCache
+}
+
+func (x *HealthCheck) Reset() {
+	*x = HealthCheck{}
+	mi := &file_argon_config_proto_msgTypes[7]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *HealthCheck) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*HealthCheck) ProtoMessage() {}
+
+func (x *HealthCheck) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[7]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use HealthCheck.ProtoReflect.Descriptor instead.
+func (*HealthCheck) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{7}
+}
+
+func (x *HealthCheck) GetPath() string {
+	if x != nil {
+		return x.Path
+	}
+	return ""
+}
+
+func (x *HealthCheck) GetIntervalMs() int32 {
+	if x != nil {
+		return x.IntervalMs
+	}
+	return 0
+}
+
+func (x *HealthCheck) GetTimeoutMs() int32 {
+	if x != nil {
+		return x.TimeoutMs
+	}
+	return 0
+}
+
+func (x *HealthCheck) GetHealthyThreshold() int32 {
+	if x != nil {
+		return x.HealthyThreshold
+	}
+	return 0
+}
+
+func (x *HealthCheck) GetUnhealthyThreshold() int32 {
+	if x != nil {
+		return x.UnhealthyThreshold
+	}
+	return 0
+}
+
+func (x *HealthCheck) GetExpectedStatuses() []int32 {
+	if x != nil {
+		return x.ExpectedStatuses
+	}
+	return nil
+}
+
+type OutlierDetection struct {
+	state              protoimpl.MessageState `protogen:"open.v1"`
+	ConsecutiveErrors  int32                  `protobuf:"varint,1,opt,name=consecutive_errors,json=consecutiveErrors,proto3" json:"consecutive_errors,omitempty"`      // 5xx/connect errors in a row to eject; enables detection when > 0
+	BaseEjectionMs     int32                  `protobuf:"varint,2,opt,name=base_ejection_ms,json=baseEjectionMs,proto3" json:"base_ejection_ms,omitempty"`             // default 30000, multiplied by the number of ejections
+	MaxEjectionMs      int32                  `protobuf:"varint,3,opt,name=max_ejection_ms,json=maxEjectionMs,proto3" json:"max_ejection_ms,omitempty"`                // default 300000
+	MaxEjectionPercent int32                  `protobuf:"varint,4,opt,name=max_ejection_percent,json=maxEjectionPercent,proto3" json:"max_ejection_percent,omitempty"` // default 10; at least one endpoint may always be ejected
+	unknownFields      protoimpl.UnknownFields
+	sizeCache          protoimpl.SizeCache
+}
+
+func (x *OutlierDetection) Reset() {
+	*x = OutlierDetection{}
+	mi := &file_argon_config_proto_msgTypes[8]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *OutlierDetection) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*OutlierDetection) ProtoMessage() {}
+
+func (x *OutlierDetection) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[8]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use OutlierDetection.ProtoReflect.Descriptor instead.
+func (*OutlierDetection) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{8}
+}
+
+func (x *OutlierDetection) GetConsecutiveErrors() int32 {
+	if x != nil {
+		return x.ConsecutiveErrors
+	}
+	return 0
+}
+
+func (x *OutlierDetection) GetBaseEjectionMs() int32 {
+	if x != nil {
+		return x.BaseEjectionMs
+	}
+	return 0
+}
+
+func (x *OutlierDetection) GetMaxEjectionMs() int32 {
+	if x != nil {
+		return x.MaxEjectionMs
+	}
+	return 0
+}
+
+func (x *OutlierDetection) GetMaxEjectionPercent() int32 {
+	if x != nil {
+		return x.MaxEjectionPercent
+	}
+	return 0
+}
+
+// Upstream connections are not limited separately: the connection pool is shared by all
+// clusters, and a request in flight holds at most one connection, so max_requests bounds them.
+type CircuitBreaker struct {
+	state              protoimpl.MessageState `protogen:"open.v1"`
+	MaxRequests        int32                  `protobuf:"varint,1,opt,name=max_requests,json=maxRequests,proto3" json:"max_requests,omitempty"`                        // requests in flight to the cluster; 0 = unlimited
+	MaxPendingRequests int32                  `protobuf:"varint,2,opt,name=max_pending_requests,json=maxPendingRequests,proto3" json:"max_pending_requests,omitempty"` // requests queued when max_requests is reached
+	MaxRetries         int32                  `protobuf:"varint,3,opt,name=max_retries,json=maxRetries,proto3" json:"max_retries,omitempty"`                           // retries in flight to the cluster; 0 = unlimited
+	PendingTimeoutMs   int32                  `protobuf:"varint,4,opt,name=pending_timeout_ms,json=pendingTimeoutMs,proto3" json:"pending_timeout_ms,omitempty"`       // default 1000; how long a queued request waits for a slot
+	unknownFields      protoimpl.UnknownFields
+	sizeCache          protoimpl.SizeCache
+}
+
+func (x *CircuitBreaker) Reset() {
+	*x = CircuitBreaker{}
+	mi := &file_argon_config_proto_msgTypes[9]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *CircuitBreaker) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*CircuitBreaker) ProtoMessage() {}
+
+func (x *CircuitBreaker) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[9]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use CircuitBreaker.ProtoReflect.Descriptor instead.
+func (*CircuitBreaker) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{9}
+}
+
+func (x *CircuitBreaker) GetMaxRequests() int32 {
+	if x != nil {
+		return x.MaxRequests
+	}
+	return 0
+}
+
+func (x *CircuitBreaker) GetMaxPendingRequests() int32 {
+	if x != nil {
+		return x.MaxPendingRequests
+	}
+	return 0
+}
+
+func (x *CircuitBreaker) GetMaxRetries() int32 {
+	if x != nil {
+		return x.MaxRetries
+	}
+	return 0
+}
+
+func (x *CircuitBreaker) GetPendingTimeoutMs() int32 {
+	if x != nil {
+		return x.PendingTimeoutMs
+	}
+	return 0
+}
+
+type RetryPolicy struct {
+	state           protoimpl.MessageState `protogen:"open.v1"`
+	RetryOn         []string               `protobuf:"bytes,1,rep,name=retry_on,json=retryOn,proto3" json:"retry_on,omitempty"`                              // "502","503","504","gateway-error","reset","connect-failure","timeout"; default connect-failure
+	PerTryTimeoutMs int32                  `protobuf:"varint,2,opt,name=per_try_timeout_ms,json=perTryTimeoutMs,proto3" json:"per_try_timeout_ms,omitempty"` // default timeout_ms
+	BaseBackoffMs   int32                  `protobuf:"varint,3,opt,name=base_backoff_ms,json=baseBackoffMs,proto3" json:"base_backoff_ms,omitempty"`         // default 25, doubled per retry, full jitter
+	MaxBackoffMs    int32                  `protobuf:"varint,4,opt,name=max_backoff_ms,json=maxBackoffMs,proto3" json:"max_backoff_ms,omitempty"`            // default 10 x base_backoff_ms
+	MaxBodyBytes    int32                  `protobuf:"varint,5,opt,name=max_body_bytes,json=maxBodyBytes,proto3" json:"max_body_bytes,omitempty"`            // buffer request bodies up to this size to replay them on retries; 0 = off
+	unknownFields   protoimpl.UnknownFields
+	sizeCache       protoimpl.SizeCache
+}
+
+func (x *RetryPolicy) Reset() {
+	*x = RetryPolicy{}
+	mi := &file_argon_config_proto_msgTypes[10]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *RetryPolicy) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*RetryPolicy) ProtoMessage() {}
+
+func (x *RetryPolicy) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[10]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use RetryPolicy.ProtoReflect.Descriptor instead.
+func (*RetryPolicy) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{10}
+}
+
+func (x *RetryPolicy) GetRetryOn() []string {
+	if x != nil {
+		return x.RetryOn
+	}
+	return nil
+}
+
+func (x *RetryPolicy) GetPerTryTimeoutMs() int32 {
+	if x != nil {
+		return x.PerTryTimeoutMs
+	}
+	return 0
+}
+
+func (x *RetryPolicy) GetBaseBackoffMs() int32 {
+	if x != nil {
+		return x.BaseBackoffMs
+	}
+	return 0
+}
+
+func (x *RetryPolicy) GetMaxBackoffMs() int32 {
+	if x != nil {
+		return x.MaxBackoffMs
+	}
+	return 0
+}
+
+func (x *RetryPolicy) GetMaxBodyBytes() int32 {
+	if x != nil {
+		return x.MaxBodyBytes
+	}
+	return 0
+}
+
+type RetryBudget struct {
+	state               protoimpl.MessageState `protogen:"open.v1"`
+	BudgetPercent       int32                  `protobuf:"varint,1,opt,name=budget_percent,json=budgetPercent,proto3" json:"budget_percent,omitempty"`                     // retries in flight as a percentage of active requests; default 20
+	MinRetryConcurrency int32                  `protobuf:"varint,2,opt,name=min_retry_concurrency,json=minRetryConcurrency,proto3" json:"min_retry_concurrency,omitempty"` // retries always allowed in flight; default 3
+	unknownFields       protoimpl.UnknownFields
+	sizeCache           protoimpl.SizeCache
+}
+
+func (x *RetryBudget) Reset() {
+	*x = RetryBudget{}
+	mi := &file_argon_config_proto_msgTypes[11]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *RetryBudget) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*RetryBudget) ProtoMessage() {}
+
+func (x *RetryBudget) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[11]
 	if x != nil {
-		return x.Port
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
 	}
-	return 0
+	return mi.MessageOf(x)
 }
 
-func (x *Endpoint) GetWeight() int32 {
-	if x != nil {
-		return x.Weight
-	}
-	return 0
+// Deprecated: Use RetryBudget.ProtoReflect.Descriptor instead.
+func (*RetryBudget) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{11}
 }
 
-func (x *Endpoint) GetZone() string {
+func (x *RetryBudget) GetBudgetPercent() int32 {
 	if x != nil {
-		return x.Zone
+		return x.BudgetPercent
 	}
-	return ""
+	return 0
 }
 
-func (x *Endpoint) GetRegion() string {
+func (x *RetryBudget) GetMinRetryConcurrency() int32 {
 	if x != nil {
-		return x.Region
+		return x.MinRetryConcurrency
 	}
-	return ""
+	return 0
 }
 
-type HeaderRewrite struct {
+// Hedges count against the cluster retry budget; without one, the RetryBudget defaults apply.
+type HedgePolicy struct {
 	state         protoimpl.MessageState `protogen:"open.v1"`
-	Name          string                 `protobuf:"bytes,1,opt,name=name,proto3" json:"name,omitempty"`
-	Mode          string                 `protobuf:"bytes,2,opt,name=mode,proto3" json:"mode,omitempty"`
-	Value         string                 `protobuf:"bytes,3,opt,name=value,proto3" json:"value,omitempty"`
+	DelayMs       int32                  `protobuf:"varint,1,opt,name=delay_ms,json=delayMs,proto3" json:"delay_ms,omitempty"` // race a second attempt after this long without response headers; enables hedging when > 0
+	Methods       []string               `protobuf:"bytes,2,rep,name=methods,proto3" json:"methods,omitempty"`                 // idempotent methods to hedge; default GET, HEAD
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
 
-func (x *HeaderRewrite) Reset() {
-	*x = HeaderRewrite{}
-	mi := &file_argon_config_proto_msgTypes[2]
+func (x *HedgePolicy) Reset() {
+	*x = HedgePolicy{}
+	mi := &file_argon_config_proto_msgTypes[12]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
 
-func (x *HeaderRewrite) String() string {
+func (x *HedgePolicy) String() string {
 	return protoimpl.X.MessageStringOf(x)
 }
 
-func (*HeaderRewrite) ProtoMessage() {}
+func (*HedgePolicy) ProtoMessage() {}
 
-func (x *HeaderRewrite) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[2]
+func (x *HedgePolicy) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[12]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -175,30 +827,23 @@ func (x *HeaderRewrite) ProtoReflect() protoreflect.Message {
 	return mi.MessageOf(x)
 }
 
-// Deprecated: Use HeaderRewrite.ProtoReflect.Descriptor instead.
-func (*HeaderRewrite) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{2}
-}
-
-func (x *HeaderRewrite) GetName() string {
-	if x != nil {
-		return x.Name
-	}
-	return ""
+// Deprecated: Use HedgePolicy.ProtoReflect.Descriptor instead.
+func (*HedgePolicy) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{12}
 }
 
-func (x *HeaderRewrite) GetMode() string {
+func (x *HedgePolicy) GetDelayMs() int32 {
 	if x != nil {
-		return x.Mode
+		return x.DelayMs
 	}
-	return ""
+	return 0
 }
 
-func (x *HeaderRewrite) GetValue() string {
+func (x *HedgePolicy) GetMethods() []string {
 	if x != nil {
-		return x.Value
+		return x.Methods
 	}
-	return ""
+	return nil
 }
 
 type Cluster struct {
@@ -212,15 +857,23 @@ type Cluster struct {
 	RequestHeaders  []*HeaderRewrite       `protobuf:"bytes,7,rep,name=request_headers,json=requestHeaders,proto3" json:"request_headers,omitempty"`
 	// If true, the dataplane will not verify the backend's TLS certificate
 	// (insecure: accepts self-signed/expired certificates). Defaults to false.
-	BackendTlsInsecureSkipVerify bool        `protobuf:"varint,8,opt,name=backend_tls_insecure_skip_verify,json=backendTlsInsecureSkipVerify,proto3" json:"backend_tls_insecure_skip_verify,omitempty"`
-	Auth                         *AuthConfig `protobuf:"bytes,9,opt,name=auth,proto3" json:"auth,omitempty"` // External auth (e.g., oauth2-proxy)
+	BackendTlsInsecureSkipVerify bool              `protobuf:"varint,8,opt,name=backend_tls_insecure_skip_verify,json=backendTlsInsecureSkipVerify,proto3" json:"backend_tls_insecure_skip_verify,omitempty"`
+	Auth                         *AuthConfig       `protobuf:"bytes,9,opt,name=auth,proto3" json:"auth,omitempty"`                                // External auth (e.g., oauth2-proxy)
+	HashPolicy                   *HashPolicy       `protobuf:"bytes,10,opt,name=hash_policy,json=hashPolicy,proto3" json:"hash_policy,omitempty"` // request key for "RingHash"/"Maglev"
+	SessionAffinity              *SessionAffinity  `protobuf:"bytes,11,opt,name=session_affinity,json=sessionAffinity,proto3" json:"session_affinity,omitempty"`
+	HealthCheck                  *HealthCheck      `protobuf:"bytes,12,opt,name=health_check,json=healthCheck,proto3" json:"health_check,omitempty"`
+	OutlierDetection             *OutlierDetection `protobuf:"bytes,13,opt,name=outlier_detection,json=outlierDetection,proto3" json:"outlier_detection,omitempty"`
+	CircuitBreaker               *CircuitBreaker   `protobuf:"bytes,14,opt,name=circuit_breaker,json=circuitBreaker,proto3" json:"circuit_breaker,omitempty"`
+	RetryPolicy                  *RetryPolicy      `protobuf:"bytes,15,opt,name=retry_policy,json=retryPolicy,proto3" json:"retry_policy,omitempty"` // retries stays the number of attempts
+	RetryBudget                  *RetryBudget      `protobuf:"bytes,16,opt,name=retry_budget,json=retryBudget,proto3" json:"retry_budget,omitempty"` // caps retries relative to the cluster's load when set
+	HedgePolicy                  *HedgePolicy      `protobuf:"bytes,17,opt,name=hedge_policy,json=hedgePolicy,proto3" json:"hedge_policy,omitempty"`
 	unknownFields                protoimpl.UnknownFields
 	sizeCache                    protoimpl.SizeCache
 }
 
 func (x *Cluster) Reset() {
 	*x = Cluster{}
-	mi := &file_argon_config_proto_msgTypes[3]
+	mi := &file_argon_config_proto_msgTypes[13]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -232,7 +885,7 @@ func (x *Cluster) String() string {
 func (*Cluster) ProtoMessage() {}
 
 func (x *Cluster) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[3]
+	mi := &file_argon_config_proto_msgTypes[13]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -245,7 +898,7 @@ func (x *Cluster) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use Cluster.ProtoReflect.Descriptor instead.
 func (*Cluster) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{3}
+	return file_argon_config_proto_rawDescGZIP(), []int{13}
 }
 
 func (x *Cluster) GetName() string {
@@ -311,20 +964,78 @@ func (x *Cluster) GetAuth() *AuthConfig {
 	return nil
 }
 
+func (x *Cluster) GetHashPolicy() *HashPolicy {
+	if x != nil {
+		return x.HashPolicy
+	}
+	return nil
+}
+
+func (x *Cluster) GetSessionAffinity() *SessionAffinity {
+	if x != nil {
+		return x.SessionAffinity
+	}
+	return nil
+}
+
+func (x *Cluster) GetHealthCheck() *HealthCheck {
+	if x != nil {
+		return x.HealthCheck
+	}
+	return nil
+}
+
+func (x *Cluster) GetOutlierDetection() *OutlierDetection {
+	if x != nil {
+		return x.OutlierDetection
+	}
+	return nil
+}
+
+func (x *Cluster) GetCircuitBreaker() *CircuitBreaker {
+	if x != nil {
+		return x.CircuitBreaker
+	}
+	return nil
+}
+
+func (x *Cluster) GetRetryPolicy() *RetryPolicy {
+	if x != nil {
+		return x.RetryPolicy
+	}
+	return nil
+}
+
+func (x *Cluster) GetRetryBudget() *RetryBudget {
+	if x != nil {
+		return x.RetryBudget
+	}
+	return nil
+}
+
+func (x *Cluster) GetHedgePolicy() *HedgePolicy {
+	if x != nil {
+		return x.HedgePolicy
+	}
+	return nil
+}
+
 type Route struct {
-	state         protoimpl.MessageState `protogen:"open.v1"`
-	Host          string                 `protobuf:"bytes,1,opt,name=host,proto3" json:"host,omitempty"`
-	Path          string                 `protobuf:"bytes,2,opt,name=path,proto3" json:"path,omitempty"`
-	PathType      string                 `protobuf:"bytes,3,opt,name=path_type,json=pathType,proto3" json:"path_type,omitempty"` // "Prefix","Exact"
-	Cluster       string                 `protobuf:"bytes,4,opt,name=cluster,proto3" json:"cluster,omitempty"`
-	Priority      int32                  `protobuf:"varint,5,opt,name=priority,proto3" json:"priority,omitempty"`
-	unknownFields protoimpl.UnknownFields
-	sizeCache     protoimpl.SizeCache
+	state                  protoimpl.MessageState `protogen:"open.v1"`
+	Host                   string                 `protobuf:"bytes,1,opt,name=host,proto3" json:"host,omitempty"`
+	Path                   string                 `protobuf:"bytes,2,opt,name=path,proto3" json:"path,omitempty"`
+	PathType               string                 `protobuf:"bytes,3,opt,name=path_type,json=pathType,proto3" json:"path_type,omitempty"` // "Prefix","Exact"
+	Cluster                string                 `protobuf:"bytes,4,opt,name=cluster,proto3" json:"cluster,omitempty"`
+	Priority               int32                  `protobuf:"varint,5,opt,name=priority,proto3" json:"priority,omitempty"`
+	AccessLogDisabled      bool                   `protobuf:"varint,6,opt,name=access_log_disabled,json=accessLogDisabled,proto3" json:"access_log_disabled,omitempty"`
+	AccessLogSamplePercent int32                  `protobuf:"varint,7,opt,name=access_log_sample_percent,json=accessLogSamplePercent,proto3" json:"access_log_sample_percent,omitempty"` // 0 = dataplane default
+	unknownFields          protoimpl.UnknownFields
+	sizeCache              protoimpl.SizeCache
 }
 
 func (x *Route) Reset() {
 	*x = Route{}
-	mi := &file_argon_config_proto_msgTypes[4]
+	mi := &file_argon_config_proto_msgTypes[14]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -336,7 +1047,7 @@ func (x *Route) String() string {
 func (*Route) ProtoMessage() {}
 
 func (x *Route) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[4]
+	mi := &file_argon_config_proto_msgTypes[14]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -349,7 +1060,7 @@ func (x *Route) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use Route.ProtoReflect.Descriptor instead.
 func (*Route) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{4}
+	return file_argon_config_proto_rawDescGZIP(), []int{14}
 }
 
 func (x *Route) GetHost() string {
@@ -387,21 +1098,35 @@ func (x *Route) GetPriority() int32 {
 	return 0
 }
 
+func (x *Route) GetAccessLogDisabled() bool {
+	if x != nil {
+		return x.AccessLogDisabled
+	}
+	return false
+}
+
+func (x *Route) GetAccessLogSamplePercent() int32 {
+	if x != nil {
+		return x.AccessLogSamplePercent
+	}
+	return 0
+}
+
 type ServerTlsBundle struct {
 	state         protoimpl.MessageState `protogen:"open.v1"`
-	Name          string                 `protobuf:"bytes,1,opt,name=name,proto3" json:"name,omitempty"`                                        // uniq name bundle "<ns>/<secret>")
-	Sni           []string               `protobuf:"bytes,2,rep,name=sni,proto3" json:"sni,omitempty"`                                          // SNI-hosts (from ingress.spec.tls.hosts)
-	CertPem       []byte                 `protobuf:"bytes,3,opt,name=cert_pem,json=certPem,proto3" json:"cert_pem,omitempty"`                   // chain (PEM)
-	KeyPem        []byte                 `protobuf:"bytes,4,opt,name=key_pem,json=keyPem,proto3" json:"key_pem,omitempty"`                      // PKCS#8 PEM
-	NotAfterUnix  int64                  `protobuf:"varint,5,opt,name=not_after_unix,json=notAfterUnix,proto3" json:"not_after_unix,omitempty"` //
-	Version       string                 `protobuf:"bytes,6,opt,name=version,proto3" json:"version,omitempty"`                                  // sha256(cert||key)
+	Name          string                 `protobuf:"bytes,1,opt,name=name,proto3" json:"name,omitempty"`                      // uniq name bundle "<ns>/<secret>")
+	Sni           []string               `protobuf:"bytes,2,rep,name=sni,proto3" json:"sni,omitempty"`                        // SNI-hosts (from ingress.spec.tls.hosts)
+	CertPem       []byte                 `protobuf:"bytes,3,opt,name=cert_pem,json=certPem,proto3" json:"cert_pem,omitempty"` // chain (PEM)
+	KeyPem        []byte                 `protobuf:"bytes,4,opt,name=key_pem,json=keyPem,proto3" json:"key_pem,omitempty"`    // PKCS#8 PEM
+	NotAfterUnix  int64                  `protobuf:"varint,5,opt,name=not_after_unix,json=notAfterUnix,proto3" json:"not_after_unix,omitempty"`
+	Version       string                 `protobuf:"bytes,6,opt,name=version,proto3" json:"version,omitempty"` // sha256(cert||key)
 	unknownFields protoimpl.UnknownFields
 	sizeCache     protoimpl.SizeCache
 }
 
 func (x *ServerTlsBundle) Reset() {
 	*x = ServerTlsBundle{}
-	mi := &file_argon_config_proto_msgTypes[5]
+	mi := &file_argon_config_proto_msgTypes[15]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -413,7 +1138,7 @@ func (x *ServerTlsBundle) String() string {
 func (*ServerTlsBundle) ProtoMessage() {}
 
 func (x *ServerTlsBundle) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[5]
+	mi := &file_argon_config_proto_msgTypes[15]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -426,7 +1151,7 @@ func (x *ServerTlsBundle) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use ServerTlsBundle.ProtoReflect.Descriptor instead.
 func (*ServerTlsBundle) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{5}
+	return file_argon_config_proto_rawDescGZIP(), []int{15}
 }
 
 func (x *ServerTlsBundle) GetName() string {
@@ -487,7 +1212,7 @@ type Snapshot struct {
 
 func (x *Snapshot) Reset() {
 	*x = Snapshot{}
-	mi := &file_argon_config_proto_msgTypes[6]
+	mi := &file_argon_config_proto_msgTypes[16]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -499,7 +1224,7 @@ func (x *Snapshot) String() string {
 func (*Snapshot) ProtoMessage() {}
 
 func (x *Snapshot) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[6]
+	mi := &file_argon_config_proto_msgTypes[16]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -512,7 +1237,7 @@ func (x *Snapshot) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use Snapshot.ProtoReflect.Descriptor instead.
 func (*Snapshot) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{6}
+	return file_argon_config_proto_rawDescGZIP(), []int{16}
 }
 
 func (x *Snapshot) GetVersion() string {
@@ -571,6 +1296,214 @@ func (x *Snapshot) GetServerTls() []*ServerTlsBundle {
 	return nil
 }
 
+// Changes to the snapshot `base_version`, applied in place so unchanged clusters keep their
+// runtime state. Routes are keyed by host, path and path_type; clusters and TLS bundles by name.
+type SnapshotDelta struct {
+	state                   protoimpl.MessageState `protogen:"open.v1"`
+	Version                 string                 `protobuf:"bytes,1,opt,name=version,proto3" json:"version,omitempty"`
+	BaseVersion             string                 `protobuf:"bytes,2,opt,name=base_version,json=baseVersion,proto3" json:"base_version,omitempty"` // the dataplane NACKs a delta on any other version
+	Clusters                []*Cluster             `protobuf:"bytes,3,rep,name=clusters,proto3" json:"clusters,omitempty"`                          // added or changed
+	RemovedClusters         []string               `protobuf:"bytes,4,rep,name=removed_clusters,json=removedClusters,proto3" json:"removed_clusters,omitempty"`
+	Routes                  []*Route               `protobuf:"bytes,5,rep,name=routes,proto3" json:"routes,omitempty"`                                    // added or changed
+	RemovedRoutes           []*Route               `protobuf:"bytes,6,rep,name=removed_routes,json=removedRoutes,proto3" json:"removed_routes,omitempty"` // only host, path and path_type are read
+	ServerTls               []*ServerTlsBundle     `protobuf:"bytes,7,rep,name=server_tls,json=serverTls,proto3" json:"server_tls,omitempty"`             // added or changed
+	RemovedServerTls        []string               `protobuf:"bytes,8,rep,name=removed_server_tls,json=removedServerTls,proto3" json:"removed_server_tls,omitempty"`
+	ResourceVersions        map[string]string      `protobuf:"bytes,9,rep,name=resource_versions,json=resourceVersions,proto3" json:"resource_versions,omitempty" protobuf_key:"bytes,1,opt,name=key" protobuf_val:"bytes,2,opt,name=value"` // added or changed
+	RemovedResourceVersions []string               `protobuf:"bytes,10,rep,name=removed_resource_versions,json=removedResourceVersions,proto3" json:"removed_resource_versions,omitempty"`
+	GeneratedAtUnixSec      int64                  `protobuf:"varint,11,opt,name=generated_at_unix_sec,json=generatedAtUnixSec,proto3" json:"generated_at_unix_sec,omitempty"`
+	unknownFields           protoimpl.UnknownFields
+	sizeCache               protoimpl.SizeCache
+}
+
+func (x *SnapshotDelta) Reset() {
+	*x = SnapshotDelta{}
+	mi := &file_argon_config_proto_msgTypes[17]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *SnapshotDelta) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*SnapshotDelta) ProtoMessage() {}
+
+func (x *SnapshotDelta) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[17]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use SnapshotDelta.ProtoReflect.Descriptor instead.
+func (*SnapshotDelta) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{17}
+}
+
+func (x *SnapshotDelta) GetVersion() string {
+	if x != nil {
+		return x.Version
+	}
+	return ""
+}
+
+func (x *SnapshotDelta) GetBaseVersion() string {
+	if x != nil {
+		return x.BaseVersion
+	}
+	return ""
+}
+
+func (x *SnapshotDelta) GetClusters() []*Cluster {
+	if x != nil {
+		return x.Clusters
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetRemovedClusters() []string {
+	if x != nil {
+		return x.RemovedClusters
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetRoutes() []*Route {
+	if x != nil {
+		return x.Routes
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetRemovedRoutes() []*Route {
+	if x != nil {
+		return x.RemovedRoutes
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetServerTls() []*ServerTlsBundle {
+	if x != nil {
+		return x.ServerTls
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetRemovedServerTls() []string {
+	if x != nil {
+		return x.RemovedServerTls
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetResourceVersions() map[string]string {
+	if x != nil {
+		return x.ResourceVersions
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetRemovedResourceVersions() []string {
+	if x != nil {
+		return x.RemovedResourceVersions
+	}
+	return nil
+}
+
+func (x *SnapshotDelta) GetGeneratedAtUnixSec() int64 {
+	if x != nil {
+		return x.GeneratedAtUnixSec
+	}
+	return 0
+}
+
+type DiscoveryResponse struct {
+	state protoimpl.MessageState `protogen:"open.v1"`
+	// Types that are valid to be assigned to Kind:
+	//
+	//	*DiscoveryResponse_Snapshot
+	//	*DiscoveryResponse_Delta
+	Kind          isDiscoveryResponse_Kind `protobuf_oneof:"kind"`
+	unknownFields protoimpl.UnknownFields
+	sizeCache     protoimpl.SizeCache
+}
+
+func (x *DiscoveryResponse) Reset() {
+	*x = DiscoveryResponse{}
+	mi := &file_argon_config_proto_msgTypes[18]
+	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+	ms.StoreMessageInfo(mi)
+}
+
+func (x *DiscoveryResponse) String() string {
+	return protoimpl.X.MessageStringOf(x)
+}
+
+func (*DiscoveryResponse) ProtoMessage() {}
+
+func (x *DiscoveryResponse) ProtoReflect() protoreflect.Message {
+	mi := &file_argon_config_proto_msgTypes[18]
+	if x != nil {
+		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
+		if ms.LoadMessageInfo() == nil {
+			ms.StoreMessageInfo(mi)
+		}
+		return ms
+	}
+	return mi.MessageOf(x)
+}
+
+// Deprecated: Use DiscoveryResponse.ProtoReflect.Descriptor instead.
+func (*DiscoveryResponse) Descriptor() ([]byte, []int) {
+	return file_argon_config_proto_rawDescGZIP(), []int{18}
+}
+
+func (x *DiscoveryResponse) GetKind() isDiscoveryResponse_Kind {
+	if x != nil {
+		return x.Kind
+	}
+	return nil
+}
+
+func (x *DiscoveryResponse) GetSnapshot() *Snapshot {
+	if x != nil {
+		if x, ok := x.Kind.(*DiscoveryResponse_Snapshot); ok {
+			return x.Snapshot
+		}
+	}
+	return nil
+}
+
+func (x *DiscoveryResponse) GetDelta() *SnapshotDelta {
+	if x != nil {
+		if x, ok := x.Kind.(*DiscoveryResponse_Delta); ok {
+			return x.Delta
+		}
+	}
+	return nil
+}
+
+type isDiscoveryResponse_Kind interface {
+	isDiscoveryResponse_Kind()
+}
+
+type DiscoveryResponse_Snapshot struct {
+	Snapshot *Snapshot `protobuf:"bytes,1,opt,name=snapshot,proto3,oneof"` // replaces everything
+}
+
+type DiscoveryResponse_Delta struct {
+	Delta *SnapshotDelta `protobuf:"bytes,2,opt,name=delta,proto3,oneof"`
+}
+
+func (*DiscoveryResponse_Snapshot) isDiscoveryResponse_Kind() {}
+
+func (*DiscoveryResponse_Delta) isDiscoveryResponse_Kind() {}
+
 type AuthConfig struct {
 	state           protoimpl.MessageState `protogen:"open.v1"`
 	Url             string                 `protobuf:"bytes,1,opt,name=url,proto3" json:"url,omitempty"`
@@ -584,7 +1517,7 @@ type AuthConfig struct {
 
 func (x *AuthConfig) Reset() {
 	*x = AuthConfig{}
-	mi := &file_argon_config_proto_msgTypes[7]
+	mi := &file_argon_config_proto_msgTypes[19]
 	ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 	ms.StoreMessageInfo(mi)
 }
@@ -596,7 +1529,7 @@ func (x *AuthConfig) String() string {
 func (*AuthConfig) ProtoMessage() {}
 
 func (x *AuthConfig) ProtoReflect() protoreflect.Message {
-	mi := &file_argon_config_proto_msgTypes[7]
+	mi := &file_argon_config_proto_msgTypes[19]
 	if x != nil {
 		ms := protoimpl.X.MessageStateOf(protoimpl.Pointer(x))
 		if ms.LoadMessageInfo() == nil {
@@ -609,7 +1542,7 @@ func (x *AuthConfig) ProtoReflect() protoreflect.Message {
 
 // Deprecated: Use AuthConfig.ProtoReflect.Descriptor instead.
 func (*AuthConfig) Descriptor() ([]byte, []int) {
-	return file_argon_config_proto_rawDescGZIP(), []int{7}
+	return file_argon_config_proto_rawDescGZIP(), []int{19}
 }
 
 func (x *AuthConfig) GetUrl() string {
@@ -653,7 +1586,16 @@ const file_argon_config_proto_rawDesc = "" +
 	"\n" +
 	"\x12argon/config.proto\x12\fargon.config\"'\n" +
 	"\fWatchRequest\x12\x17\n" +
-	"\anode_id\x18\x01 \x01(\tR\x06nodeId\"|\n" +
+	"\anode_id\x18\x01 \x01(\tR\x06nodeId\"\xac\x01\n" +
+	"\x10DiscoveryRequest\x12\x17\n" +
+	"\anode_id\x18\x01 \x01(\tR\x06nodeId\x12!\n" +
+	"\fversion_info\x18\x02 \x01(\tR\vversionInfo\x12)\n" +
+	"\x10response_version\x18\x03 \x01(\tR\x0fresponseVersion\x121\n" +
+	"\x06errors\x18\x04 \x03(\v2\x19.argon.config.ConfigErrorR\x06errors\"O\n" +
+	"\vConfigError\x12\x12\n" +
+	"\x04kind\x18\x01 \x01(\tR\x04kind\x12\x12\n" +
+	"\x04name\x18\x02 \x01(\tR\x04name\x12\x18\n" +
+	"\amessage\x18\x03 \x01(\tR\amessage\"|\n" +
 	"\bEndpoint\x12\x18\n" +
 	"\aaddress\x18\x01 \x01(\tR\aaddress\x12\x12\n" +
 	"\x04port\x18\x02 \x01(\x05R\x04port\x12\x16\n" +
@@ -663,7 +1605,49 @@ const file_argon_config_proto_rawDesc = "" +
 	"\rHeaderRewrite\x12\x12\n" +
 	"\x04name\x18\x01 \x01(\tR\x04name\x12\x12\n" +
 	"\x04mode\x18\x02 \x01(\tR\x04mode\x12\x14\n" +
-	"\x05value\x18\x03 \x01(\tR\x05value\"\x90\x03\n" +
+	"\x05value\x18\x03 \x01(\tR\x05value\"8\n" +
+	"\n" +
+	"HashPolicy\x12\x16\n" +
+	"\x06source\x18\x01 \x01(\tR\x06source\x12\x12\n" +
+	"\x04name\x18\x02 \x01(\tR\x04name\"s\n" +
+	"\x0fSessionAffinity\x12\x1f\n" +
+	"\vcookie_name\x18\x01 \x01(\tR\n" +
+	"cookieName\x12\x1f\n" +
+	"\vcookie_path\x18\x02 \x01(\tR\n" +
+	"cookiePath\x12\x1e\n" +
+	"\vmax_age_sec\x18\x03 \x01(\x03R\tmaxAgeSec\"\xec\x01\n" +
+	"\vHealthCheck\x12\x12\n" +
+	"\x04path\x18\x01 \x01(\tR\x04path\x12\x1f\n" +
+	"\vinterval_ms\x18\x02 \x01(\x05R\n" +
+	"intervalMs\x12\x1d\n" +
+	"\n" +
+	"timeout_ms\x18\x03 \x01(\x05R\ttimeoutMs\x12+\n" +
+	"\x11healthy_threshold\x18\x04 \x01(\x05R\x10healthyThreshold\x12/\n" +
+	"\x13unhealthy_threshold\x18\x05 \x01(\x05R\x12unhealthyThreshold\x12+\n" +
+	"\x11expected_statuses\x18\x06 \x03(\x05R\x10expectedStatuses\"\xc5\x01\n" +
+	"\x10OutlierDetection\x12-\n" +
+	"\x12consecutive_errors\x18\x01 \x01(\x05R\x11consecutiveErrors\x12(\n" +
+	"\x10base_ejection_ms\x18\x02 \x01(\x05R\x0ebaseEjectionMs\x12&\n" +
+	"\x0fmax_ejection_ms\x18\x03 \x01(\x05R\rmaxEjectionMs\x120\n" +
+	"\x14max_ejection_percent\x18\x04 \x01(\x05R\x12maxEjectionPercent\"\xb4\x01\n" +
+	"\x0eCircuitBreaker\x12!\n" +
+	"\fmax_requests\x18\x01 \x01(\x05R\vmaxRequests\x120\n" +
+	"\x14max_pending_requests\x18\x02 \x01(\x05R\x12maxPendingRequests\x12\x1f\n" +
+	"\vmax_retries\x18\x03 \x01(\x05R\n" +
+	"maxRetries\x12,\n" +
+	"\x12pending_timeout_ms\x18\x04 \x01(\x05R\x10pendingTimeoutMs\"\xc9\x01\n" +
+	"\vRetryPolicy\x12\x19\n" +
+	"\bretry_on\x18\x01 \x03(\tR\aretryOn\x12+\n" +
+	"\x12per_try_timeout_ms\x18\x02 \x01(\x05R\x0fperTryTimeoutMs\x12&\n" +
+	"\x0fbase_backoff_ms\x18\x03 \x01(\x05R\rbaseBackoffMs\x12$\n" +
+	"\x0emax_backoff_ms\x18\x04 \x01(\x05R\fmaxBackoffMs\x12$\n" +
+	"\x0emax_body_bytes\x18\x05 \x01(\x05R\fmaxBodyBytes\"h\n" +
+	"\vRetryBudget\x12%\n" +
+	"\x0ebudget_percent\x18\x01 \x01(\x05R\rbudgetPercent\x122\n" +
+	"\x15min_retry_concurrency\x18\x02 \x01(\x05R\x13minRetryConcurrency\"B\n" +
+	"\vHedgePolicy\x12\x19\n" +
+	"\bdelay_ms\x18\x01 \x01(\x05R\adelayMs\x12\x18\n" +
+	"\amethods\x18\x02 \x03(\tR\amethods\"\xa1\a\n" +
 	"\aCluster\x12\x12\n" +
 	"\x04name\x18\x01 \x01(\tR\x04name\x12\x1b\n" +
 	"\tlb_policy\x18\x02 \x01(\tR\blbPolicy\x124\n" +
@@ -674,13 +1658,25 @@ const file_argon_config_proto_rawDesc = "" +
 	"\x10backend_protocol\x18\x06 \x01(\tR\x0fbackendProtocol\x12D\n" +
 	"\x0frequest_headers\x18\a \x03(\v2\x1b.argon.config.HeaderRewriteR\x0erequestHeaders\x12F\n" +
 	" backend_tls_insecure_skip_verify\x18\b \x01(\bR\x1cbackendTlsInsecureSkipVerify\x12,\n" +
-	"\x04auth\x18\t \x01(\v2\x18.argon.config.AuthConfigR\x04auth\"\x82\x01\n" +
+	"\x04auth\x18\t \x01(\v2\x18.argon.config.AuthConfigR\x04auth\x129\n" +
+	"\vhash_policy\x18\n" +
+	" \x01(\v2\x18.argon.config.HashPolicyR\n" +
+	"hashPolicy\x12H\n" +
+	"\x10session_affinity\x18\v \x01(\v2\x1d.argon.config.SessionAffinityR\x0fsessionAffinity\x12<\n" +
+	"\fhealth_check\x18\f \x01(\v2\x19.argon.config.HealthCheckR\vhealthCheck\x12K\n" +
+	"\x11outlier_detection\x18\r \x01(\v2\x1e.argon.config.OutlierDetectionR\x10outlierDetection\x12E\n" +
+	"\x0fcircuit_breaker\x18\x0e \x01(\v2\x1c.argon.config.CircuitBreakerR\x0ecircuitBreaker\x12<\n" +
+	"\fretry_policy\x18\x0f \x01(\v2\x19.argon.config.RetryPolicyR\vretryPolicy\x12<\n" +
+	"\fretry_budget\x18\x10 \x01(\v2\x19.argon.config.RetryBudgetR\vretryBudget\x12<\n" +
+	"\fhedge_policy\x18\x11 \x01(\v2\x19.argon.config.HedgePolicyR\vhedgePolicy\"\xed\x01\n" +
 	"\x05Route\x12\x12\n" +
 	"\x04host\x18\x01 \x01(\tR\x04host\x12\x12\n" +
 	"\x04path\x18\x02 \x01(\tR\x04path\x12\x1b\n" +
 	"\tpath_type\x18\x03 \x01(\tR\bpathType\x12\x18\n" +
 	"\acluster\x18\x04 \x01(\tR\acluster\x12\x1a\n" +
-	"\bpriority\x18\x05 \x01(\x05R\bpriority\"\xab\x01\n" +
+	"\bpriority\x18\x05 \x01(\x05R\bpriority\x12.\n" +
+	"\x13access_log_disabled\x18\x06 \x01(\bR\x11accessLogDisabled\x129\n" +
+	"\x19access_log_sample_percent\x18\a \x01(\x05R\x16accessLogSamplePercent\"\xab\x01\n" +
 	"\x0fServerTlsBundle\x12\x12\n" +
 	"\x04name\x18\x01 \x01(\tR\x04name\x12\x10\n" +
 	"\x03sni\x18\x02 \x03(\tR\x03sni\x12\x19\n" +
@@ -701,7 +1697,28 @@ const file_argon_config_proto_rawDesc = "" +
 	"server_tls\x18\x1e \x03(\v2\x1d.argon.config.ServerTlsBundleR\tserverTls\x1aC\n" +
 	"\x15ResourceVersionsEntry\x12\x10\n" +
 	"\x03key\x18\x01 \x01(\tR\x03key\x12\x14\n" +
-	"\x05value\x18\x02 \x01(\tR\x05value:\x028\x01\"\xa1\x01\n" +
+	"\x05value\x18\x02 \x01(\tR\x05value:\x028\x01\"\x93\x05\n" +
+	"\rSnapshotDelta\x12\x18\n" +
+	"\aversion\x18\x01 \x01(\tR\aversion\x12!\n" +
+	"\fbase_version\x18\x02 \x01(\tR\vbaseVersion\x121\n" +
+	"\bclusters\x18\x03 \x03(\v2\x15.argon.config.ClusterR\bclusters\x12)\n" +
+	"\x10removed_clusters\x18\x04 \x03(\tR\x0fremovedClusters\x12+\n" +
+	"\x06routes\x18\x05 \x03(\v2\x13.argon.config.RouteR\x06routes\x12:\n" +
+	"\x0eremoved_routes\x18\x06 \x03(\v2\x13.argon.config.RouteR\rremovedRoutes\x12<\n" +
+	"\n" +
+	"server_tls\x18\a \x03(\v2\x1d.argon.config.ServerTlsBundleR\tserverTls\x12,\n" +
+	"\x12removed_server_tls\x18\b \x03(\tR\x10removedServerTls\x12^\n" +
+	"\x11resource_versions\x18\t \x03(\v21.argon.config.SnapshotDelta.ResourceVersionsEntryR\x10resourceVersions\x12:\n" +
+	"\x19removed_resource_versions\x18\n" +
+	" \x03(\tR\x17removedResourceVersions\x121\n" +
+	"\x15generated_at_unix_sec\x18\v \x01(\x03R\x12generatedAtUnixSec\x1aC\n" +
+	"\x15ResourceVersionsEntry\x12\x10\n" +
+	"\x03key\x18\x01 \x01(\tR\x03key\x12\x14\n" +
+	"\x05value\x18\x02 \x01(\tR\x05value:\x028\x01\"\x86\x01\n" +
+	"\x11DiscoveryResponse\x124\n" +
+	"\bsnapshot\x18\x01 \x01(\v2\x16.argon.config.SnapshotH\x00R\bsnapshot\x123\n" +
+	"\x05delta\x18\x02 \x01(\v2\x1b.argon.config.SnapshotDeltaH\x00R\x05deltaB\x06\n" +
+	"\x04kind\"\xa1\x01\n" +
 	"\n" +
 	"AuthConfig\x12\x10\n" +
 	"\x03url\x18\x01 \x01(\tR\x03url\x12\x16\n" +
@@ -710,9 +1727,10 @@ const file_argon_config_proto_rawDesc = "" +
 	"\n" +
 	"skip_paths\x18\x04 \x03(\tR\tskipPaths\x12\x1f\n" +
 	"\vcookie_name\x18\x05 \x01(\tR\n" +
-	"cookieName2P\n" +
+	"cookieName2\x9f\x01\n" +
 	"\x0fConfigDiscovery\x12=\n" +
-	"\x05Watch\x12\x1a.argon.config.WatchRequest\x1a\x16.argon.config.Snapshot0\x01B6Z4argon.github.io/ingress/internal/gen/argonpb;argonpbb\x06proto3"
+	"\x05Watch\x12\x1a.argon.config.WatchRequest\x1a\x16.argon.config.Snapshot0\x01\x12M\n" +
+	"\x06Stream\x12\x1e.argon.config.DiscoveryRequest\x1a\x1f.argon.config.DiscoveryResponse(\x010\x01B6Z4argon.github.io/ingress/internal/gen/argonpb;argonpbb\x06proto3"
 
 var (
 	file_argon_config_proto_rawDescOnce sync.Once
@@ -726,33 +1744,64 @@ func file_argon_config_proto_rawDescGZIP() []byte {
 	return file_argon_config_proto_rawDescData
 }
 
-var file_argon_config_proto_msgTypes = make([]protoimpl.MessageInfo, 9)
+var file_argon_config_proto_msgTypes = make([]protoimpl.MessageInfo, 22)
 var file_argon_config_proto_goTypes = []any{
-	(*WatchRequest)(nil),    // 0: argon.config.WatchRequest
-	(*Endpoint)(nil),        // 1: argon.config.Endpoint
-	(*HeaderRewrite)(nil),   // 2: argon.config.HeaderRewrite
-	(*Cluster)(nil),         // 3: argon.config.Cluster
-	(*Route)(nil),           // 4: argon.config.Route
-	(*ServerTlsBundle)(nil), // 5: argon.config.ServerTlsBundle
-	(*Snapshot)(nil),        // 6: argon.config.Snapshot
-	(*AuthConfig)(nil),      // 7: argon.config.AuthConfig
-	nil,                     // 8: argon.config.Snapshot.ResourceVersionsEntry
+	(*WatchRequest)(nil),      // 0: argon.config.WatchRequest
+	(*DiscoveryRequest)(nil),  // 1: argon.config.DiscoveryRequest
+	(*ConfigError)(nil),       // 2: argon.config.ConfigError
+	(*Endpoint)(nil),          // 3: argon.config.Endpoint
+	(*HeaderRewrite)(nil),     // 4: argon.config.HeaderRewrite
+	(*HashPolicy)(nil),        // 5: argon.config.HashPolicy
+	(*SessionAffinity)(nil),   // 6: argon.config.SessionAffinity
+	(*HealthCheck)(nil),       // 7: argon.config.HealthCheck
+	(*OutlierDetection)(nil),  // 8: argon.config.OutlierDetection
+	(*CircuitBreaker)(nil),    // 9: argon.config.CircuitBreaker
+	(*RetryPolicy)(nil),       // 10: argon.config.RetryPolicy
+	(*RetryBudget)(nil),       // 11: argon.config.RetryBudget
+	(*HedgePolicy)(nil),       // 12: argon.config.HedgePolicy
+	(*Cluster)(nil),           // 13: argon.config.Cluster
+	(*Route)(nil),             // 14: argon.config.Route
+	(*ServerTlsBundle)(nil),   // 15: argon.config.ServerTlsBundle
+	(*Snapshot)(nil),          // 16: argon.config.Snapshot
+	(*SnapshotDelta)(nil),     // 17: argon.config.SnapshotDelta
+	(*DiscoveryResponse)(nil), // 18: argon.config.DiscoveryResponse
+	(*AuthConfig)(nil),        // 19: argon.config.AuthConfig
+	nil,                       // 20: argon.config.Snapshot.ResourceVersionsEntry
+	nil,                       // 21: argon.config.SnapshotDelta.ResourceVersionsEntry
 }
 var file_argon_config_proto_depIdxs = []int32{
-	1, // 0: argon.config.Cluster.endpoints:type_name -> argon.config.Endpoint
-	2, // 1: argon.config.Cluster.request_headers:type_name -> argon.config.HeaderRewrite
-	7, // 2: argon.config.Cluster.auth:type_name -> argon.config.AuthConfig
-	8, // 3: argon.config.Snapshot.resource_versions:type_name -> argon.config.Snapshot.ResourceVersionsEntry
-	4, // 4: argon.config.Snapshot.routes:type_name -> argon.config.Route
-	3, // 5: argon.config.Snapshot.clusters:type_name -> argon.config.Cluster
-	5, // 6: argon.config.Snapshot.server_tls:type_name -> argon.config.ServerTlsBundle
-	0, // 7: argon.config.ConfigDiscovery.Watch:input_type -> argon.config.WatchRequest
-	6, // 8: argon.config.ConfigDiscovery.Watch:output_type -> argon.config.Snapshot
-	8, // [8:9] is the sub-list for method output_type
-	7, // [7:8] is the sub-list for method input_type
-	7, // [7:7] is the sub-list for extension type_name
-	7, // [7:7] is the sub-list for extension extendee
-	0, // [0:7] is the sub-list for field type_name
+	2,  // 0: argon.config.DiscoveryRequest.errors:type_name -> argon.config.ConfigError
+	3,  // 1: argon.config.Cluster.endpoints:type_name -> argon.config.Endpoint
+	4,  // 2: argon.config.Cluster.request_headers:type_name -> argon.config.HeaderRewrite
+	19, // 3: argon.config.Cluster.auth:type_name -> argon.config.AuthConfig
+	5,  // 4: argon.config.Cluster.hash_policy:type_name -> argon.config.HashPolicy
+	6,  // 5: argon.config.Cluster.session_affinity:type_name -> argon.config.SessionAffinity
+	7,  // 6: argon.config.Cluster.health_check:type_name -> argon.config.HealthCheck
+	8,  // 7: argon.config.Cluster.outlier_detection:type_name -> argon.config.OutlierDetection
+	9,  // 8: argon.config.Cluster.circuit_breaker:type_name -> argon.config.CircuitBreaker
+	10, // 9: argon.config.Cluster.retry_policy:type_name -> argon.config.RetryPolicy
+	11, // 10: argon.config.Cluster.retry_budget:type_name -> argon.config.RetryBudget
+	12, // 11: argon.config.Cluster.hedge_policy:type_name -> argon.config.HedgePolicy
+	20, // 12: argon.config.Snapshot.resource_versions:type_name -> argon.config.Snapshot.ResourceVersionsEntry
+	14, // 13: argon.config.Snapshot.routes:type_name -> argon.config.Route
+	13, // 14: argon.config.Snapshot.clusters:type_name -> argon.config.Cluster
+	15, // 15: argon.config.Snapshot.server_tls:type_name -> argon.config.ServerTlsBundle
+	13, // 16: argon.config.SnapshotDelta.clusters:type_name -> argon.config.Cluster
+	14, // 17: argon.config.SnapshotDelta.routes:type_name -> argon.config.Route
+	14, // 18: argon.config.SnapshotDelta.removed_routes:type_name -> argon.config.Route
+	15, // 19: argon.config.SnapshotDelta.server_tls:type_name -> argon.config.ServerTlsBundle
+	21, // 20: argon.config.SnapshotDelta.resource_versions:type_name -> argon.config.SnapshotDelta.ResourceVersionsEntry
+	16, // 21: argon.config.DiscoveryResponse.snapshot:type_name -> argon.config.Snapshot
+	17, // 22: argon.config.DiscoveryResponse.delta:type_name -> argon.config.SnapshotDelta
+	0,  // 23: argon.config.ConfigDiscovery.Watch:input_type -> argon.config.WatchRequest
+	1,  // 24: argon.config.ConfigDiscovery.Stream:input_type -> argon.config.DiscoveryRequest
+	16, // 25: argon.config.ConfigDiscovery.Watch:output_type -> argon.config.Snapshot
+	18, // 26: argon.config.ConfigDiscovery.Stream:output_type -> argon.config.DiscoveryResponse
+	25, // [25:27] is the sub-list for method output_type
+	23, // [23:25] is the sub-list for method input_type
+	23, // [23:23] is the sub-list for extension type_name
+	23, // [23:23] is the sub-list for extension extendee
+	0,  // [0:23] is the sub-list for field type_name
 }
 
 func init() { file_argon_config_proto_init() }
@@ -760,13 +1809,17 @@ func file_argon_config_proto_init() {
 	if File_argon_config_proto != nil {
 		return
 	}
+	file_argon_config_proto_msgTypes[18].OneofWrappers = []any{
+		(*DiscoveryResponse_Snapshot)(nil),
+		(*DiscoveryResponse_Delta)(nil),
+	}
 	type x struct{}
 	out := protoimpl.TypeBuilder{
 		File: protoimpl.DescBuilder{
 			GoPackagePath: reflect.TypeOf(x{}).PkgPath(),
 			RawDescriptor: unsafe.Slice(unsafe.StringData(file_argon_config_proto_rawDesc), len(file_argon_config_proto_rawDesc)),
 			NumEnums:      0,
-			NumMessages:   9,
+			NumMessages:   22,
 			NumExtensions: 0,
 			NumServices:   1,
 		},
@@ -19,7 +19,8 @@ import (
 const _ = grpc.SupportPackageIsVersion9
 
 const (
-	ConfigDiscovery_Watch_FullMethodName = "/argon.config.ConfigDiscovery/Watch"
+	ConfigDiscovery_Watch_FullMethodName  = "/argon.config.ConfigDiscovery/Watch"
+	ConfigDiscovery_Stream_FullMethodName = "/argon.config.ConfigDiscovery/Stream"
 )
 
 // ConfigDiscoveryClient is the client API for ConfigDiscovery service.
@@ -27,6 +28,8 @@ const (
 // For semantics around ctx use and closing/ending streaming RPCs, please refer to https://pkg.go.dev/google.golang.org/grpc/?tab=doc#ClientConn.NewStream.
 type ConfigDiscoveryClient interface {
 	Watch(ctx context.Context, in *WatchRequest, opts ...grpc.CallOption) (grpc.ServerStreamingClient[Snapshot], error)
+	// Watch with an ACK/NACK of every snapshot; the dataplane falls back to Watch when unimplemented
+	Stream(ctx context.Context, opts ...grpc.CallOption) (grpc.BidiStreamingClient[DiscoveryRequest, DiscoveryResponse], error)
 }
 
 type configDiscoveryClient struct {
@@ -56,11 +59,26 @@ func (c *configDiscoveryClient) Watch(ctx context.Context, in *WatchRequest, opt
 // This type alias is provided for backwards compatibility with existing code that references the prior non-generic stream type by name.
 type ConfigDiscovery_WatchClient = grpc.ServerStreamingClient[Snapshot]
 
+func (c *configDiscoveryClient) Stream(ctx context.Context, opts ...grpc.CallOption) (grpc.BidiStreamingClient[DiscoveryRequest, DiscoveryResponse], error) {
+	cOpts := append([]grpc.CallOption{grpc.StaticMethod()}, opts...)
+	stream, err := c.cc.NewStream(ctx, &ConfigDiscovery_ServiceDesc.Streams[1], ConfigDiscovery_Stream_FullMethodName, cOpts...)
+	if err != nil {
+		return nil, err
+	}
+	x := &grpc.GenericClientStream[DiscoveryRequest, DiscoveryResponse]{ClientStream: stream}
+	return x, nil
+}
+
+// This type alias is provided for backwards compatibility with existing code that references the prior non-generic stream type by name.
+type ConfigDiscovery_StreamClient = grpc.BidiStreamingClient[DiscoveryRequest, DiscoveryResponse]
+
 // ConfigDiscoveryServer is the server API for ConfigDiscovery service.
 // All implementations must embed UnimplementedConfigDiscoveryServer
 // for forward compatibility.
 type ConfigDiscoveryServer interface {
 	Watch(*WatchRequest, grpc.ServerStreamingServer[Snapshot]) error
+	// Watch with an ACK/NACK of every snapshot; the dataplane falls back to Watch when unimplemented
+	Stream(grpc.BidiStreamingServer[DiscoveryRequest, DiscoveryResponse]) error
 	mustEmbedUnimplementedConfigDiscoveryServer()
 }
 
@@ -74,6 +92,9 @@ type UnimplementedConfigDiscoveryServer struct{}
 func (UnimplementedConfigDiscoveryServer) Watch(*WatchRequest, grpc.ServerStreamingServer[Snapshot]) error {
 	return status.Errorf(codes.Unimplemented, "method Watch not implemented")
 }
+func (UnimplementedConfigDiscoveryServer) Stream(grpc.BidiStreamingServer[DiscoveryRequest, DiscoveryResponse]) error {
+	return status.Errorf(codes.Unimplemented, "method Stream not implemented")
+}
 func (UnimplementedConfigDiscoveryServer) mustEmbedUnimplementedConfigDiscoveryServer() {}
 func (UnimplementedConfigDiscoveryServer) testEmbeddedByValue()                         {}
 
@@ -106,6 +127,13 @@ func _ConfigDiscovery_Watch_Handler(srv interface{}, stream grpc.ServerStream) e
 // This type alias is provided for backwards compatibility with existing code that references the prior non-generic stream type by name.
 type ConfigDiscovery_WatchServer = grpc.ServerStreamingServer[Snapshot]
 
+func _ConfigDiscovery_Stream_Handler(srv interface{}, stream grpc.ServerStream) error {
+	return srv.(ConfigDiscoveryServer).Stream(&grpc.GenericServerStream[DiscoveryRequest, DiscoveryResponse]{ServerStream: stream})
+}
+
+// This type alias is provided for backwards compatibility with existing code that references the prior non-generic stream type by name.
+type ConfigDiscovery_StreamServer = grpc.BidiStreamingServer[DiscoveryRequest, DiscoveryResponse]
+
 // ConfigDiscovery_ServiceDesc is the grpc.ServiceDesc for ConfigDiscovery service.
 // It's only intended for direct use with grpc.RegisterService,
 // and not to be introspected or modified (even as a copy)
@@ -119,6 +147,12 @@ var ConfigDiscovery_ServiceDesc = grpc.ServiceDesc{
 			Handler:       _ConfigDiscovery_Watch_Handler,
 			ServerStreams: true,
 		},
+		{
+			StreamName:    "Stream",
+			Handler:       _ConfigDiscovery_Stream_Handler,
+			ServerStreams: true,
+			ClientStreams: true,
+		},
 	},
 	Metadata: "argon/config.proto",
 }
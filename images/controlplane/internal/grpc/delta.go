@@ -0,0 +1,94 @@
+package grpc
+
+import (
+	"sort"
+
+	argonpb "argon/internal/gen/argonpb/argon"
+
+	"google.golang.org/protobuf/proto"
+)
+
+// snapshotDelta returns the changes that turn base into next, keyed the way the dataplane
+// applies them: clusters and TLS bundles by name, routes by host, path and path type.
+// It returns nil when next must be sent in full.
+func snapshotDelta(base, next *argonpb.Snapshot) *argonpb.SnapshotDelta {
+	if base == nil ||
+		base.GetControllerId() != next.GetControllerId() ||
+		base.GetIngressClassName() != next.GetIngressClassName() {
+		return nil
+	}
+
+	delta := &argonpb.SnapshotDelta{
+		Version:            next.GetVersion(),
+		BaseVersion:        base.GetVersion(),
+		GeneratedAtUnixSec: next.GetGeneratedAtUnixSec(),
+	}
+
+	clusters := make(map[string]*argonpb.Cluster, len(base.GetClusters()))
+	for _, c := range base.GetClusters() {
+		clusters[c.GetName()] = c
+	}
+	for _, c := range next.GetClusters() {
+		if old, ok := clusters[c.GetName()]; !ok || !proto.Equal(old, c) {
+			delta.Clusters = append(delta.Clusters, c)
+		}
+		delete(clusters, c.GetName())
+	}
+	for name := range clusters {
+		delta.RemovedClusters = append(delta.RemovedClusters, name)
+	}
+	sort.Strings(delta.RemovedClusters)
+
+	routeKey := func(r *argonpb.Route) string {
+		return r.GetHost() + "\x00" + r.GetPath() + "\x00" + r.GetPathType()
+	}
+	routes := make(map[string]*argonpb.Route, len(base.GetRoutes()))
+	for _, r := range base.GetRoutes() {
+		routes[routeKey(r)] = r
+	}
+	for _, r := range next.GetRoutes() {
+		if old, ok := routes[routeKey(r)]; !ok || !proto.Equal(old, r) {
+			delta.Routes = append(delta.Routes, r)
+		}
+		delete(routes, routeKey(r))
+	}
+	for _, r := range base.GetRoutes() {
+		if _, removed := routes[routeKey(r)]; removed {
+			delta.RemovedRoutes = append(delta.RemovedRoutes, &argonpb.Route{
+				Host: r.GetHost(), Path: r.GetPath(), PathType: r.GetPathType(),
+			})
+		}
+	}
+
+	bundles := make(map[string]*argonpb.ServerTlsBundle, len(base.GetServerTls()))
+	for _, b := range base.GetServerTls() {
+		bundles[b.GetName()] = b
+	}
+	for _, b := range next.GetServerTls() {
+		if old, ok := bundles[b.GetName()]; !ok || !proto.Equal(old, b) {
+			delta.ServerTls = append(delta.ServerTls, b)
+		}
+		delete(bundles, b.GetName())
+	}
+	for name := range bundles {
+		delta.RemovedServerTls = append(delta.RemovedServerTls, name)
+	}
+	sort.Strings(delta.RemovedServerTls)
+
+	for key, version := range next.GetResourceVersions() {
+		if old, ok := base.GetResourceVersions()[key]; !ok || old != version {
+			if delta.ResourceVersions == nil {
+				delta.ResourceVersions = make(map[string]string)
+			}
+			delta.ResourceVersions[key] = version
+		}
+	}
+	for key := range base.GetResourceVersions() {
+		if _, ok := next.GetResourceVersions()[key]; !ok {
+			delta.RemovedResourceVersions = append(delta.RemovedResourceVersions, key)
+		}
+	}
+	sort.Strings(delta.RemovedResourceVersions)
+
+	return delta
+}
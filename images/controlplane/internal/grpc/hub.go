@@ -3,18 +3,35 @@ package grpc
 import (
 	"sync"
 
+	argonpb "argon/internal/gen/argonpb/argon"
 	"argon/internal/model"
 )
 
+// NodeStatus is the last ACK/NACK a dataplane sent on the Stream RPC.
+type NodeStatus struct {
+	NodeID  string
+	Version string                 // snapshot acknowledged
+	Errors  []*argonpb.ConfigError // rejected parts of it; empty for an ACK
+}
+
+// NodeStatusFunc is told of every new ACK/NACK, with the owners of the acknowledged
+// snapshot (nil when it is no longer the latest one).
+type NodeStatusFunc func(status NodeStatus, owners map[string]string)
+
 type StreamHub struct {
-	mu   sync.RWMutex
-	next int64
-	cons map[int64]chan model.Snapshot
-	last model.Snapshot
+	mu       sync.RWMutex
+	next     int64
+	cons     map[int64]chan model.Snapshot
+	last     model.Snapshot
+	nodes    map[string]NodeStatus
+	onStatus NodeStatusFunc
 }
 
 func NewStreamHub() *StreamHub {
-	return &StreamHub{cons: make(map[int64]chan model.Snapshot)}
+	return &StreamHub{
+		cons:  make(map[int64]chan model.Snapshot),
+		nodes: make(map[string]NodeStatus),
+	}
 }
 
 func (h *StreamHub) Add() (int64, <-chan model.Snapshot, model.Snapshot) {
@@ -49,3 +66,44 @@ func (h *StreamHub) Broadcast(s model.Snapshot) {
 		}
 	}
 }
+
+// OnNodeStatus sets the function told of ACKs/NACKs; set it before serving.
+func (h *StreamHub) OnNodeStatus(f NodeStatusFunc) {
+	h.mu.Lock()
+	defer h.mu.Unlock()
+	h.onStatus = f
+}
+
+// Ack records what a dataplane answered to a snapshot it was sent.
+func (h *StreamHub) Ack(status NodeStatus) {
+	h.mu.Lock()
+	h.nodes[status.NodeID] = status
+	var owners map[string]string
+	if status.Version == h.last.Version {
+		owners = h.last.Owners
+	}
+	onStatus := h.onStatus
+	h.mu.Unlock()
+
+	if onStatus != nil {
+		onStatus(status, owners)
+	}
+}
+
+// ForgetNode drops the status of a dataplane that disconnected.
+func (h *StreamHub) ForgetNode(nodeID string) {
+	h.mu.Lock()
+	defer h.mu.Unlock()
+	delete(h.nodes, nodeID)
+}
+
+// NodeStatuses returns the last ACK/NACK of every connected dataplane.
+func (h *StreamHub) NodeStatuses() []NodeStatus {
+	h.mu.RLock()
+	defer h.mu.RUnlock()
+	out := make([]NodeStatus, 0, len(h.nodes))
+	for _, s := range h.nodes {
+		out = append(out, s)
+	}
+	return out
+}
@@ -4,10 +4,13 @@ import (
 	"context"
 	"crypto/tls"
 	"crypto/x509"
+	"errors"
 	"fmt"
+	"io"
 	"net"
 	"sort"
 	"strings"
+	"sync/atomic"
 
 	argonpb "argon/internal/gen/argonpb/argon"
 	"argon/internal/model"
@@ -15,6 +18,7 @@ import (
 	"google.golang.org/grpc"
 	"google.golang.org/grpc/credentials"
 	"google.golang.org/grpc/keepalive"
+	"google.golang.org/grpc/metadata"
 	"sigs.k8s.io/controller-runtime/pkg/log"
 )
 
@@ -115,6 +119,94 @@ func (s *Server) Watch(req *argonpb.WatchRequest, stream argonpb.ConfigDiscovery
 	}
 }
 
+// Stream is Watch with an ACK/NACK from the dataplane for every snapshot it is sent;
+// the answers are recorded per node in the hub. Once the dataplane has the last snapshot
+// sent in use, the next one goes out as a delta on it.
+func (s *Server) Stream(stream argonpb.ConfigDiscovery_StreamServer) error {
+	logger := log.FromContext(context.Background())
+
+	// the dataplane waits for the response headers before it reads snapshots
+	if err := stream.SendHeader(metadata.MD{}); err != nil {
+		return err
+	}
+	first, err := stream.Recv()
+	if err != nil {
+		return err
+	}
+	node := first.GetNodeId()
+	logger.Info("dataplane connected", "node", node, "version", first.GetVersionInfo())
+
+	id, ch, last := s.Hub.Add()
+	defer s.Hub.Remove(id)
+	defer s.Hub.ForgetNode(node)
+
+	// version the dataplane has in use, as of its latest request
+	var inUse atomic.Value
+	inUse.Store(first.GetVersionInfo())
+
+	recvErr := make(chan error, 1)
+	go func() {
+		req := first
+		for {
+			inUse.Store(req.GetVersionInfo())
+			if req.GetResponseVersion() != "" {
+				s.Hub.Ack(NodeStatus{
+					NodeID:  node,
+					Version: req.GetResponseVersion(),
+					Errors:  req.GetErrors(),
+				})
+			}
+			var err error
+			if req, err = stream.Recv(); err != nil {
+				recvErr <- err
+				return
+			}
+		}
+	}()
+
+	var sent *argonpb.Snapshot
+	send := func(snap model.Snapshot) error {
+		next := toPbSnapshot(snap)
+		resp := &argonpb.DiscoveryResponse{
+			Kind: &argonpb.DiscoveryResponse_Snapshot{Snapshot: next},
+		}
+		// a snapshot the dataplane did not take (NACKed, or not ACKed yet) is no base
+		if sent != nil && inUse.Load().(string) == sent.GetVersion() {
+			if delta := snapshotDelta(sent, next); delta != nil {
+				resp.Kind = &argonpb.DiscoveryResponse_Delta{Delta: delta}
+			}
+		}
+		if err := stream.Send(resp); err != nil {
+			return err
+		}
+		sent = next
+		return nil
+	}
+
+	if last.Version != "" {
+		if err := send(last); err != nil {
+			return err
+		}
+	}
+
+	ctx := stream.Context()
+	for {
+		select {
+		case snap := <-ch:
+			if err := send(snap); err != nil {
+				return err
+			}
+		case err := <-recvErr:
+			if errors.Is(err, io.EOF) {
+				return nil
+			}
+			return err
+		case <-ctx.Done():
+			return ctx.Err()
+		}
+	}
+}
+
 func (s *Server) RunGRPC(ctx context.Context) error {
 
 	lis, err := net.Listen("tcp", s.addr)
@@ -188,6 +280,54 @@ func toPbSnapshot(in model.Snapshot) *argonpb.Snapshot {
 				CookieName:      c.Auth.CookieName,
 			}
 		}
+		if c.HashPolicy.Source != "" {
+			pc.HashPolicy = &argonpb.HashPolicy{
+				Source: c.HashPolicy.Source,
+				Name:   c.HashPolicy.Name,
+			}
+		}
+		if c.HealthCheck.Path != "" {
+			pc.HealthCheck = &argonpb.HealthCheck{
+				Path:               c.HealthCheck.Path,
+				IntervalMs:         c.HealthCheck.IntervalMs,
+				TimeoutMs:          c.HealthCheck.TimeoutMs,
+				HealthyThreshold:   c.HealthCheck.HealthyThreshold,
+				UnhealthyThreshold: c.HealthCheck.UnhealthyThreshold,
+				ExpectedStatuses:   c.HealthCheck.ExpectedStatuses,
+			}
+		}
+		if c.OutlierDetection.ConsecutiveErrors > 0 {
+			pc.OutlierDetection = &argonpb.OutlierDetection{
+				ConsecutiveErrors:  c.OutlierDetection.ConsecutiveErrors,
+				BaseEjectionMs:     c.OutlierDetection.BaseEjectionMs,
+				MaxEjectionMs:      c.OutlierDetection.MaxEjectionMs,
+				MaxEjectionPercent: c.OutlierDetection.MaxEjectionPercent,
+			}
+		}
+		if c.CircuitBreaker != (model.CircuitBreaker{}) {
+			pc.CircuitBreaker = &argonpb.CircuitBreaker{
+				MaxRequests:        c.CircuitBreaker.MaxRequests,
+				MaxPendingRequests: c.CircuitBreaker.MaxPendingRequests,
+				MaxRetries:         c.CircuitBreaker.MaxRetries,
+				PendingTimeoutMs:   c.CircuitBreaker.PendingTimeoutMs,
+			}
+		}
+		if rp := c.RetryPolicy; len(rp.RetryOn) > 0 || rp.PerTryTimeoutMs != 0 || rp.BaseBackoffMs != 0 ||
+			rp.MaxBackoffMs != 0 || rp.MaxBodyBytes != 0 {
+			pc.RetryPolicy = &argonpb.RetryPolicy{
+				RetryOn:         rp.RetryOn,
+				PerTryTimeoutMs: rp.PerTryTimeoutMs,
+				BaseBackoffMs:   rp.BaseBackoffMs,
+				MaxBackoffMs:    rp.MaxBackoffMs,
+				MaxBodyBytes:    rp.MaxBodyBytes,
+			}
+		}
+		if c.RetryBudget != (model.RetryBudget{}) {
+			pc.RetryBudget = &argonpb.RetryBudget{
+				BudgetPercent:       c.RetryBudget.BudgetPercent,
+				MinRetryConcurrency: c.RetryBudget.MinRetryConcurrency,
+			}
+		}
 		pb.Clusters = append(pb.Clusters, pc)
 	}
 
@@ -26,6 +26,10 @@ type Snapshot struct {
 	TLS      []TLSSecret   `json:"tls,omitempty"`
 	GeoIP    *GeoIPRuntime `json:"geoip,omitempty"`
 	Policies *Policies     `json:"policies,omitempty"`
+
+	// "<kind>/<name>" of a route, cluster or TLS bundle -> "<ns>/<name>" of its Ingress,
+	// to report what a dataplane rejected; not sent to dataplanes
+	Owners map[string]string `json:"-"`
 }
 
 func (snap *Snapshot) Sort() {
@@ -91,12 +95,18 @@ type Cluster struct {
     LBPolicy  LBPolicy   `json:"lbPolicy,omitempty"` // RR
     Endpoints []Endpoint `json:"endpoints"`
     // retries/timeouts/health-check
-    TimeoutMs       int32            `json:"timeoutMs,omitempty"`
-    Retries         int32            `json:"retries,omitempty"`
-    BackendProtocol string           `json:"backendProtocol,omitempty"`
-    LBAlgorithm     LBPolicy         `json:"lbAlgotihm,omitempty"`
-    RewriteHeaders  []RewriteHeaders `json:"rwHeaders"`
-    Auth            *AuthConfig      `json:"auth,omitempty"`
+    TimeoutMs        int32            `json:"timeoutMs,omitempty"`
+    Retries          int32            `json:"retries,omitempty"`
+    BackendProtocol  string           `json:"backendProtocol,omitempty"`
+    LBAlgorithm      LBPolicy         `json:"lbAlgotihm,omitempty"`
+    RewriteHeaders   []RewriteHeaders `json:"rwHeaders"`
+    Auth             *AuthConfig      `json:"auth,omitempty"`
+    HashPolicy       HashPolicy       `json:"hashPolicy,omitempty"` // request key for RingHash/Maglev
+    HealthCheck      HealthCheck      `json:"healthCheck,omitempty"`
+    OutlierDetection OutlierDetection `json:"outlierDetection,omitempty"`
+    CircuitBreaker   CircuitBreaker   `json:"circuitBreaker,omitempty"`
+    RetryPolicy      RetryPolicy      `json:"retryPolicy,omitempty"`
+    RetryBudget      RetryBudget      `json:"retryBudget,omitempty"`
 }
 
 type LBPolicy string
@@ -104,12 +114,16 @@ type LBPolicy string
 type RewriteHeaderMode string
 
 const (
-	LBRoundRobin   LBPolicy          = "RoundRobin"
-	LBRandom       LBPolicy          = "Random"
-	LBLeastConn    LBPolicy          = "LeastConn"
-	RWHeaderAppend RewriteHeaderMode = "Append"
-	RWHeaderSet    RewriteHeaderMode = "Set"
-	RWHeaderRemove RewriteHeaderMode = "Remove"
+	LBRoundRobin         LBPolicy          = "RoundRobin"
+	LBWeightedRoundRobin LBPolicy          = "WeightedRoundRobin"
+	LBRandom             LBPolicy          = "Random"
+	LBLeastConn          LBPolicy          = "LeastConn"
+	LBP2C                LBPolicy          = "P2C"
+	LBRingHash           LBPolicy          = "RingHash"
+	LBMaglev             LBPolicy          = "Maglev"
+	RWHeaderAppend       RewriteHeaderMode = "Append"
+	RWHeaderSet          RewriteHeaderMode = "Set"
+	RWHeaderRemove       RewriteHeaderMode = "Remove"
 )
 
 // Endpoint — реальный upstream адрес
@@ -147,9 +161,10 @@ type CountryRoute struct {
 }
 
 type TargetProxy struct {
-	Host string
-	Path map[string]TargetEndpoint
-	SNI  TLSSecret
+	Host    string
+	Path    map[string]TargetEndpoint
+	SNI     TLSSecret
+	Ingress string // "<ns>/<name>"
 }
 
 type RewriteHeaders struct {
@@ -159,16 +174,72 @@ type RewriteHeaders struct {
 }
 
 type TargetEndpoint struct {
-    Port            int32
-    Protocol        corev1.Protocol
-    BackendProtocol string
-    Addresses       []string
-    PathType        *v1networking.PathType
-    Retries         int32
-    TimeoutMs       int32
-    LBAlgorithm     LBPolicy
-    RewriteHeaders  []RewriteHeaders
-    Auth            *AuthConfig
+    Port             int32
+    Protocol         corev1.Protocol
+    BackendProtocol  string
+    Addresses        []string
+    Zones            map[string]string // address -> topology zone from EndpointSlice
+    PathType         *v1networking.PathType
+    Retries          int32
+    TimeoutMs        int32
+    LBAlgorithm      LBPolicy
+    RewriteHeaders   []RewriteHeaders
+    Auth             *AuthConfig
+    HashPolicy       HashPolicy
+    HealthCheck      HealthCheck
+    OutlierDetection OutlierDetection
+    CircuitBreaker   CircuitBreaker
+    RetryPolicy      RetryPolicy
+    RetryBudget      RetryBudget
+}
+
+// HashPolicy is the request key RingHash/Maglev hash on; empty Source means none.
+type HashPolicy struct {
+    Source string `json:"source,omitempty"` // Header|Cookie|QueryParameter|SourceIP
+    Name   string `json:"name,omitempty"`   // header/cookie/query parameter name
+}
+
+// HealthCheck configures active HTTP health checks; empty Path means none.
+// Zero fields take the dataplane defaults.
+type HealthCheck struct {
+    Path               string  `json:"path,omitempty"`
+    IntervalMs         int32   `json:"interval_ms,omitempty"`
+    TimeoutMs          int32   `json:"timeout_ms,omitempty"`
+    HealthyThreshold   int32   `json:"healthy_threshold,omitempty"`
+    UnhealthyThreshold int32   `json:"unhealthy_threshold,omitempty"`
+    ExpectedStatuses   []int32 `json:"expected_statuses,omitempty"`
+}
+
+// OutlierDetection ejects endpoints after consecutive errors; zero ConsecutiveErrors means none.
+type OutlierDetection struct {
+    ConsecutiveErrors  int32 `json:"consecutive_errors,omitempty"`
+    BaseEjectionMs     int32 `json:"base_ejection_ms,omitempty"`
+    MaxEjectionMs      int32 `json:"max_ejection_ms,omitempty"`
+    MaxEjectionPercent int32 `json:"max_ejection_percent,omitempty"`
+}
+
+// CircuitBreaker caps requests and retries in flight to a cluster; zero limits mean unlimited.
+type CircuitBreaker struct {
+    MaxRequests        int32 `json:"max_requests,omitempty"`
+    MaxPendingRequests int32 `json:"max_pending_requests,omitempty"`
+    MaxRetries         int32 `json:"max_retries,omitempty"`
+    PendingTimeoutMs   int32 `json:"pending_timeout_ms,omitempty"`
+}
+
+// RetryPolicy says which failures Retries covers and how attempts are spaced;
+// the zero value keeps the dataplane defaults (connect failures only).
+type RetryPolicy struct {
+    RetryOn         []string `json:"retry_on,omitempty"`
+    PerTryTimeoutMs int32    `json:"per_try_timeout_ms,omitempty"`
+    BaseBackoffMs   int32    `json:"base_backoff_ms,omitempty"`
+    MaxBackoffMs    int32    `json:"max_backoff_ms,omitempty"`
+    MaxBodyBytes    int32    `json:"max_body_bytes,omitempty"`
+}
+
+// RetryBudget bounds retries in flight relative to active requests; the zero value means none.
+type RetryBudget struct {
+    BudgetPercent       int32 `json:"budget_percent,omitempty"`
+    MinRetryConcurrency int32 `json:"min_retry_concurrency,omitempty"`
 }
 
 // AuthConfig describes external authorization parameters (e.g., oauth2-proxy).
@@ -11,4 +11,10 @@ const (
     AUTH_RESPONSE_HEADERS_ANNOTATION = "argon.github.io/auth-response-headers"
     AUTH_SKIP_PATHS_ANNOTATION      = "argon.github.io/auth-skip-paths"
     AUTH_COOKIE_NAME_ANNOTATION     = "argon.github.io/auth-cookie-name"
+    HASH_BY_ANNOTATION              = "argon.github.io/hash-by"
+    HEALTH_CHECK_ANNOTATION         = "argon.github.io/health-check"
+    OUTLIER_DETECTION_ANNOTATION    = "argon.github.io/outlier-detection"
+    CIRCUIT_BREAKER_ANNOTATION      = "argon.github.io/circuit-breaker"
+    RETRY_POLICY_ANNOTATION         = "argon.github.io/retry-policy"
+    RETRY_BUDGET_ANNOTATION         = "argon.github.io/retry-budget"
 )
@@ -2,6 +2,28 @@ fn main() {
     let proto_file = "proto/argon/config.proto";
     tonic_build::configure()
         .build_server(true)
+        // admin /config_dump and STATIC_CONFIG files
+        .message_attribute(
+            ".argon.config",
+            "#[derive(serde::Serialize, serde::Deserialize)] #[serde(default)]",
+        )
+        .enum_attribute(
+            ".argon.config",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        // an omitted weight is 1 in STATIC_CONFIG files, not 0 (drained)
+        .field_attribute(
+            ".argon.config.Endpoint.weight",
+            "#[serde(default = \"crate::static_config::default_weight\")]",
+        )
+        .field_attribute(
+            ".argon.config.ServerTlsBundle.cert_pem",
+            "#[serde(with = \"crate::utils::pem\")]",
+        )
+        .field_attribute(
+            ".argon.config.ServerTlsBundle.key_pem",
+            "#[serde(with = \"crate::utils::pem\")]",
+        )
         .out_dir("./src")
         .compile_protos(&[proto_file], &["."])
         .unwrap_or_else(|e| panic!("protobuf compile error: {}", e));
@@ -0,0 +1,509 @@
+use crate::proxy::{ClientAddr, RequestId, TlsServerName};
+use crate::snapshot::RouteAccessLog;
+use crate::utils::splitmix64;
+use bytes::Bytes;
+use http::{Request, header};
+use http_body_util::combinators::BoxBody;
+use hyper::body::{Body, Frame, SizeHint};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+// lines waiting for the writer; when full, new lines are dropped instead of blocking requests
+const QUEUE_SIZE: usize = 8192;
+
+/// One completed request.
+#[derive(Clone, Debug, Default)]
+pub struct AccessLogEntry {
+    pub start: Option<SystemTime>,
+    pub client: Option<SocketAddr>,
+    pub method: String,
+    pub host: String,
+    pub path: String,
+    pub protocol: String,
+    pub status: u16,
+    pub route: String,
+    pub cluster: String,
+    pub upstream: Option<String>,
+    pub retries: u32,
+    pub sni: Option<String>,
+    pub request_id: String,
+    pub bytes_sent: u64,
+    pub duration: Duration,
+}
+
+impl AccessLogEntry {
+    // request side of the entry, taken before the request is routed
+    pub fn capture<B>(req: &Request<B>) -> Self {
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .or_else(|| req.uri().host())
+            .unwrap_or_default();
+        Self {
+            start: Some(SystemTime::now()),
+            client: req.extensions().get::<ClientAddr>().map(|c| c.0),
+            method: req.method().to_string(),
+            host: host.to_string(),
+            path: req
+                .uri()
+                .path_and_query()
+                .map_or("/", |pq| pq.as_str())
+                .to_string(),
+            protocol: format!("{:?}", req.version()),
+            sni: req.extensions().get::<TlsServerName>().map(|s| s.0.clone()),
+            request_id: req
+                .extensions()
+                .get::<RequestId>()
+                .map(|id| id.0.clone())
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Text(String),
+    Var(Var),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Var {
+    Time,
+    Client,
+    Method,
+    Host,
+    Path,
+    Protocol,
+    Status,
+    Route,
+    Cluster,
+    Upstream,
+    Retries,
+    Sni,
+    RequestId,
+    Bytes,
+    DurationMs,
+}
+
+impl Var {
+    const ALL: [(&'static str, Var); 15] = [
+        ("time", Var::Time),
+        ("client", Var::Client),
+        ("method", Var::Method),
+        ("host", Var::Host),
+        ("path", Var::Path),
+        ("protocol", Var::Protocol),
+        ("status", Var::Status),
+        ("route", Var::Route),
+        ("cluster", Var::Cluster),
+        ("upstream", Var::Upstream),
+        ("retries", Var::Retries),
+        ("sni", Var::Sni),
+        ("request_id", Var::RequestId),
+        ("bytes", Var::Bytes),
+        ("duration_ms", Var::DurationMs),
+    ];
+
+    fn parse(name: &str) -> Option<Var> {
+        Self::ALL.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+    }
+
+    fn value(&self, entry: &AccessLogEntry) -> String {
+        match self {
+            Var::Time => entry.start.map(format_rfc3339).unwrap_or_default(),
+            Var::Client => entry.client.map(|c| c.ip().to_string()).unwrap_or_default(),
+            Var::Method => entry.method.clone(),
+            Var::Host => entry.host.clone(),
+            Var::Path => entry.path.clone(),
+            Var::Protocol => entry.protocol.clone(),
+            Var::Status => entry.status.to_string(),
+            Var::Route => entry.route.clone(),
+            Var::Cluster => entry.cluster.clone(),
+            Var::Upstream => entry.upstream.clone().unwrap_or_default(),
+            Var::Retries => entry.retries.to_string(),
+            Var::Sni => entry.sni.clone().unwrap_or_default(),
+            Var::RequestId => entry.request_id.clone(),
+            Var::Bytes => entry.bytes_sent.to_string(),
+            Var::DurationMs => format!("{:.3}", entry.duration.as_secs_f64() * 1000.0),
+        }
+    }
+
+    // numbers are not quoted in the JSON format
+    fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Var::Status | Var::Retries | Var::Bytes | Var::DurationMs
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Format {
+    Json,
+    Template(Vec<Token>),
+}
+
+impl Format {
+    // "json" or a template with $variables, e.g. `$client "$method $path" $status $duration_ms`
+    fn parse(raw: &str) -> Format {
+        if raw.is_empty() || raw.eq_ignore_ascii_case("json") {
+            return Format::Json;
+        }
+
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+        let mut rest = raw;
+        while let Some(pos) = rest.find('$') {
+            text.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            match Var::parse(&after[..len]) {
+                Some(var) => {
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut text)));
+                    }
+                    tokens.push(Token::Var(var));
+                }
+                None => {
+                    text.push('$');
+                    text.push_str(&after[..len]);
+                }
+            }
+            rest = &after[len..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        Format::Template(tokens)
+    }
+
+    fn render(&self, entry: &AccessLogEntry) -> String {
+        let mut line = String::with_capacity(256);
+        match self {
+            Format::Json => {
+                line.push('{');
+                for (i, (name, var)) in Var::ALL.iter().enumerate() {
+                    if i > 0 {
+                        line.push(',');
+                    }
+                    let _ = write!(line, "\"{name}\":");
+                    let value = var.value(entry);
+                    if var.is_number() {
+                        line.push_str(&value);
+                    } else {
+                        line.push_str(&serde_json::Value::String(value).to_string());
+                    }
+                }
+                line.push('}');
+            }
+            Format::Template(tokens) => {
+                for token in tokens {
+                    match token {
+                        Token::Text(text) => line.push_str(text),
+                        Token::Var(var) => {
+                            let value = var.value(entry);
+                            line.push_str(if value.is_empty() { "-" } else { &value });
+                        }
+                    }
+                }
+            }
+        }
+        line.push('\n');
+        line
+    }
+}
+
+/// Access log: lines are formatted on the request path and written by a background task.
+pub struct AccessLog {
+    tx: mpsc::Sender<String>,
+    format: Format,
+    sample_percent: u32,
+    seed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl AccessLog {
+    // ACCESS_LOG: "stdout" or a file path (unset or "off" disables the log)
+    // ACCESS_LOG_FORMAT: "json" (default) or a $variable template
+    // ACCESS_LOG_SAMPLE_PERCENT: share of requests logged, default 100
+    pub async fn from_env() -> anyhow::Result<Option<Arc<AccessLog>>> {
+        let target = std::env::var("ACCESS_LOG").unwrap_or_default();
+        let target = target.trim();
+        if target.is_empty() || target.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        let out: Box<dyn AsyncWrite + Send + Unpin> = if target == "stdout" {
+            Box::new(tokio::io::stdout())
+        } else {
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(target)
+                .await?;
+            Box::new(file)
+        };
+
+        let format = Format::parse(
+            std::env::var("ACCESS_LOG_FORMAT")
+                .unwrap_or_default()
+                .trim(),
+        );
+        let sample_percent = std::env::var("ACCESS_LOG_SAMPLE_PERCENT")
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .unwrap_or(100)
+            .min(100);
+
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(write_loop(rx, out));
+        tracing::info!(%target, sample_percent, "access log enabled");
+
+        Ok(Some(Arc::new(AccessLog {
+            tx,
+            format,
+            sample_percent,
+            seed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        })))
+    }
+
+    // whether to log a request of a route with the given setting
+    pub fn sampled(&self, route: RouteAccessLog) -> bool {
+        let percent = match route {
+            RouteAccessLog::Off => return false,
+            RouteAccessLog::Default => self.sample_percent,
+            RouteAccessLog::Sampled(percent) => percent,
+        };
+        if percent >= 100 {
+            return true;
+        }
+        splitmix64(&self.seed) % 100 < u64::from(percent)
+    }
+
+    pub fn log(&self, entry: &AccessLogEntry) {
+        let line = self.format.render(entry);
+        if self.tx.try_send(line).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % 1000 == 1 {
+                tracing::warn!(dropped, "access log queue full, dropping lines");
+            }
+        }
+    }
+}
+
+async fn write_loop(mut rx: mpsc::Receiver<String>, mut out: Box<dyn AsyncWrite + Send + Unpin>) {
+    while let Some(line) = rx.recv().await {
+        let mut failed = out.write_all(line.as_bytes()).await.is_err();
+        // batch whatever queued up meanwhile into one flush
+        while let Ok(line) = rx.try_recv() {
+            failed |= out.write_all(line.as_bytes()).await.is_err();
+        }
+        if failed || out.flush().await.is_err() {
+            tracing::warn!("failed to write access log");
+        }
+    }
+}
+
+/// Response body that writes the access log line once the body is done (or dropped),
+/// so bytes and duration cover the whole response.
+pub struct LoggedBody {
+    inner: BoxBody<Bytes, hyper::Error>,
+    log: Arc<AccessLog>,
+    entry: AccessLogEntry,
+    started: Instant,
+}
+
+impl LoggedBody {
+    pub fn new(
+        inner: BoxBody<Bytes, hyper::Error>,
+        log: Arc<AccessLog>,
+        entry: AccessLogEntry,
+        started: Instant,
+    ) -> Self {
+        Self {
+            inner,
+            log,
+            entry,
+            started,
+        }
+    }
+}
+
+impl Body for LoggedBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, hyper::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.entry.bytes_sent += data.len() as u64;
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        self.entry.duration = self.started.elapsed();
+        self.log.log(&self.entry);
+    }
+}
+
+// UTC timestamp with milliseconds, e.g. 2024-05-01T12:30:00.123Z
+fn format_rfc3339(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            start: Some(UNIX_EPOCH + Duration::from_millis(1_714_566_600_123)),
+            client: Some("10.1.2.3:50000".parse().unwrap()),
+            method: "GET".into(),
+            host: "app.example".into(),
+            path: "/search?q=1".into(),
+            protocol: "HTTP/1.1".into(),
+            status: 200,
+            route: "/".into(),
+            cluster: "app".into(),
+            upstream: Some("10.0.0.1:8080".into()),
+            retries: 1,
+            request_id: "req-1".into(),
+            bytes_sent: 512,
+            duration: Duration::from_micros(1500),
+            ..Default::default()
+        }
+    }
+
+    fn access_log(sample_percent: u32) -> AccessLog {
+        AccessLog {
+            tx: mpsc::channel(1).0,
+            format: Format::Json,
+            sample_percent,
+            seed: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn parses_templates() {
+        assert_eq!(Format::parse(""), Format::Json);
+        assert_eq!(Format::parse("JSON"), Format::Json);
+        assert_eq!(
+            Format::parse("$client \"$method $path\" $unknown $$status"),
+            Format::Template(vec![
+                Token::Var(Var::Client),
+                Token::Text(" \"".into()),
+                Token::Var(Var::Method),
+                Token::Text(" ".into()),
+                Token::Var(Var::Path),
+                Token::Text("\" $unknown $".into()),
+                Token::Var(Var::Status),
+            ])
+        );
+    }
+
+    #[test]
+    fn renders_templates_with_dashes_for_missing_values() {
+        let format =
+            Format::parse("$time $client \"$method $host$path\" $status $sni $duration_ms");
+        assert_eq!(
+            format.render(&entry()),
+            "2024-05-01T12:30:00.123Z 10.1.2.3 \"GET app.example/search?q=1\" 200 - 1.500\n"
+        );
+    }
+
+    #[test]
+    fn renders_json_with_numbers_unquoted() {
+        let line = Format::Json.render(&entry());
+        assert!(line.ends_with("}\n"));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["time"], "2024-05-01T12:30:00.123Z");
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["retries"], 1);
+        assert_eq!(json["bytes"], 512);
+        assert_eq!(json["duration_ms"], 1.5);
+        assert_eq!(json["upstream"], "10.0.0.1:8080");
+        assert_eq!(json["sni"], "");
+        let keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(keys.len(), Var::ALL.len());
+    }
+
+    #[test]
+    fn json_escapes_quotes_and_control_characters() {
+        let mut entry = entry();
+        entry.path = "/a\"b\\c\nd\re\tf\u{1}g\u{7f}é".into();
+        let line = Format::Json.render(&entry);
+        assert!(line.contains(r#""path":"/a\"b\\c\nd\re\tf\u0001g"#));
+        assert_eq!(line.matches('\n').count(), 1, "one line per entry");
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["path"], entry.path);
+    }
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH + Duration::from_millis(1_709_251_199_999)),
+            "2024-02-29T23:59:59.999Z"
+        );
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH + Duration::from_secs(4_102_444_800)),
+            "2100-01-01T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn samples_none_at_zero_and_all_at_one_hundred_percent() {
+        let log = access_log(100);
+        assert!((0..1000).all(|_| log.sampled(RouteAccessLog::Default)));
+        assert!((0..1000).all(|_| log.sampled(RouteAccessLog::Sampled(100))));
+        assert!((0..1000).all(|_| !log.sampled(RouteAccessLog::Sampled(0))));
+        assert!(!log.sampled(RouteAccessLog::Off));
+
+        let log = access_log(0);
+        assert!((0..1000).all(|_| !log.sampled(RouteAccessLog::Default)));
+        assert!(log.sampled(RouteAccessLog::Sampled(100)));
+
+        let log = access_log(25);
+        let logged = (0..10_000)
+            .filter(|_| log.sampled(RouteAccessLog::Default))
+            .count();
+        assert!((2000..3000).contains(&logged), "{logged} of 10000 at 25%");
+    }
+}
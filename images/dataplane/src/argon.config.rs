@@ -1,10 +1,44 @@
 // This file is @generated by prost-build.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct WatchRequest {
-    ///
     #[prost(string, tag = "1")]
     pub node_id: ::prost::alloc::string::String,
 }
+/// Sent by the dataplane on the Stream RPC: once when it connects, then once per snapshot or
+/// delta received.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DiscoveryRequest {
+    #[prost(string, tag = "1")]
+    pub node_id: ::prost::alloc::string::String,
+    /// version of the snapshot in use; empty before the first
+    #[prost(string, tag = "2")]
+    pub version_info: ::prost::alloc::string::String,
+    /// snapshot acknowledged by this message; empty on connect
+    #[prost(string, tag = "3")]
+    pub response_version: ::prost::alloc::string::String,
+    /// NACK when set: parts of response_version that were rejected
+    #[prost(message, repeated, tag = "4")]
+    pub errors: ::prost::alloc::vec::Vec<ConfigError>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConfigError {
+    /// "Route","Cluster","ServerTlsBundle","Snapshot","SnapshotDelta"
+    #[prost(string, tag = "1")]
+    pub kind: ::prost::alloc::string::String,
+    /// cluster name, "<host><path>" of a route, TLS bundle name
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub message: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Endpoint {
     #[prost(string, tag = "1")]
@@ -12,12 +46,15 @@ pub struct Endpoint {
     #[prost(int32, tag = "2")]
     pub port: i32,
     #[prost(int32, tag = "3")]
+    #[serde(default = "crate::static_config::default_weight")]
     pub weight: i32,
     #[prost(string, tag = "4")]
     pub zone: ::prost::alloc::string::String,
     #[prost(string, tag = "5")]
     pub region: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct HeaderRewrite {
     #[prost(string, tag = "1")]
@@ -27,6 +64,135 @@ pub struct HeaderRewrite {
     #[prost(string, tag = "3")]
     pub value: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HashPolicy {
+    /// "Header","Cookie","QueryParameter","SourceIP"
+    #[prost(string, tag = "1")]
+    pub source: ::prost::alloc::string::String,
+    /// header/cookie/query parameter name
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SessionAffinity {
+    /// enables cookie affinity when set
+    #[prost(string, tag = "1")]
+    pub cookie_name: ::prost::alloc::string::String,
+    /// default "/"
+    #[prost(string, tag = "2")]
+    pub cookie_path: ::prost::alloc::string::String,
+    /// 0 = session cookie
+    #[prost(int64, tag = "3")]
+    pub max_age_sec: i64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HealthCheck {
+    /// enables active HTTP health checks when set
+    #[prost(string, tag = "1")]
+    pub path: ::prost::alloc::string::String,
+    /// default 5000
+    #[prost(int32, tag = "2")]
+    pub interval_ms: i32,
+    /// default 1000
+    #[prost(int32, tag = "3")]
+    pub timeout_ms: i32,
+    /// default 2
+    #[prost(int32, tag = "4")]
+    pub healthy_threshold: i32,
+    /// default 3
+    #[prost(int32, tag = "5")]
+    pub unhealthy_threshold: i32,
+    /// default 200-399
+    #[prost(int32, repeated, tag = "6")]
+    pub expected_statuses: ::prost::alloc::vec::Vec<i32>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct OutlierDetection {
+    /// 5xx/connect errors in a row to eject; enables detection when > 0
+    #[prost(int32, tag = "1")]
+    pub consecutive_errors: i32,
+    /// default 30000, multiplied by the number of ejections
+    #[prost(int32, tag = "2")]
+    pub base_ejection_ms: i32,
+    /// default 300000
+    #[prost(int32, tag = "3")]
+    pub max_ejection_ms: i32,
+    /// default 10; at least one endpoint may always be ejected
+    #[prost(int32, tag = "4")]
+    pub max_ejection_percent: i32,
+}
+/// Upstream connections are not limited separately: the connection pool is shared by all
+/// clusters, and a request in flight holds at most one connection, so max_requests bounds them.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct CircuitBreaker {
+    /// requests in flight to the cluster; 0 = unlimited
+    #[prost(int32, tag = "1")]
+    pub max_requests: i32,
+    /// requests queued when max_requests is reached
+    #[prost(int32, tag = "2")]
+    pub max_pending_requests: i32,
+    /// retries in flight to the cluster; 0 = unlimited
+    #[prost(int32, tag = "3")]
+    pub max_retries: i32,
+    /// default 1000; how long a queued request waits for a slot
+    #[prost(int32, tag = "4")]
+    pub pending_timeout_ms: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RetryPolicy {
+    /// "502","503","504","gateway-error","reset","connect-failure","timeout"; default connect-failure
+    #[prost(string, repeated, tag = "1")]
+    pub retry_on: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// default timeout_ms
+    #[prost(int32, tag = "2")]
+    pub per_try_timeout_ms: i32,
+    /// default 25, doubled per retry, full jitter
+    #[prost(int32, tag = "3")]
+    pub base_backoff_ms: i32,
+    /// default 10 x base_backoff_ms
+    #[prost(int32, tag = "4")]
+    pub max_backoff_ms: i32,
+    /// buffer request bodies up to this size to replay them on retries; 0 = off
+    #[prost(int32, tag = "5")]
+    pub max_body_bytes: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct RetryBudget {
+    /// retries in flight as a percentage of active requests; default 20
+    #[prost(int32, tag = "1")]
+    pub budget_percent: i32,
+    /// retries always allowed in flight; default 3
+    #[prost(int32, tag = "2")]
+    pub min_retry_concurrency: i32,
+}
+/// Hedges count against the cluster retry budget; without one, the RetryBudget defaults apply.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HedgePolicy {
+    /// race a second attempt after this long without response headers; enables hedging when > 0
+    #[prost(int32, tag = "1")]
+    pub delay_ms: i32,
+    /// idempotent methods to hedge; default GET, HEAD
+    #[prost(string, repeated, tag = "2")]
+    pub methods: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Cluster {
     #[prost(string, tag = "1")]
@@ -51,7 +217,28 @@ pub struct Cluster {
     /// External auth (e.g., oauth2-proxy)
     #[prost(message, optional, tag = "9")]
     pub auth: ::core::option::Option<AuthConfig>,
+    /// request key for "RingHash"/"Maglev"
+    #[prost(message, optional, tag = "10")]
+    pub hash_policy: ::core::option::Option<HashPolicy>,
+    #[prost(message, optional, tag = "11")]
+    pub session_affinity: ::core::option::Option<SessionAffinity>,
+    #[prost(message, optional, tag = "12")]
+    pub health_check: ::core::option::Option<HealthCheck>,
+    #[prost(message, optional, tag = "13")]
+    pub outlier_detection: ::core::option::Option<OutlierDetection>,
+    #[prost(message, optional, tag = "14")]
+    pub circuit_breaker: ::core::option::Option<CircuitBreaker>,
+    /// retries stays the number of attempts
+    #[prost(message, optional, tag = "15")]
+    pub retry_policy: ::core::option::Option<RetryPolicy>,
+    /// caps retries relative to the cluster's load when set
+    #[prost(message, optional, tag = "16")]
+    pub retry_budget: ::core::option::Option<RetryBudget>,
+    #[prost(message, optional, tag = "17")]
+    pub hedge_policy: ::core::option::Option<HedgePolicy>,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Route {
     #[prost(string, tag = "1")]
@@ -65,7 +252,14 @@ pub struct Route {
     pub cluster: ::prost::alloc::string::String,
     #[prost(int32, tag = "5")]
     pub priority: i32,
+    #[prost(bool, tag = "6")]
+    pub access_log_disabled: bool,
+    /// 0 = dataplane default
+    #[prost(int32, tag = "7")]
+    pub access_log_sample_percent: i32,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ServerTlsBundle {
     /// uniq name bundle "<ns>/<secret>")
@@ -76,17 +270,20 @@ pub struct ServerTlsBundle {
     pub sni: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
     /// chain (PEM)
     #[prost(bytes = "vec", tag = "3")]
+    #[serde(with = "crate::utils::pem")]
     pub cert_pem: ::prost::alloc::vec::Vec<u8>,
     /// PKCS#8 PEM
     #[prost(bytes = "vec", tag = "4")]
+    #[serde(with = "crate::utils::pem")]
     pub key_pem: ::prost::alloc::vec::Vec<u8>,
-    ///
     #[prost(int64, tag = "5")]
     pub not_after_unix: i64,
     /// sha256(cert||key)
     #[prost(string, tag = "6")]
     pub version: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Snapshot {
     #[prost(string, tag = "1")]
@@ -109,6 +306,67 @@ pub struct Snapshot {
     #[prost(message, repeated, tag = "30")]
     pub server_tls: ::prost::alloc::vec::Vec<ServerTlsBundle>,
 }
+/// Changes to the snapshot `base_version`, applied in place so unchanged clusters keep their
+/// runtime state. Routes are keyed by host, path and path_type; clusters and TLS bundles by name.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SnapshotDelta {
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    /// the dataplane NACKs a delta on any other version
+    #[prost(string, tag = "2")]
+    pub base_version: ::prost::alloc::string::String,
+    /// added or changed
+    #[prost(message, repeated, tag = "3")]
+    pub clusters: ::prost::alloc::vec::Vec<Cluster>,
+    #[prost(string, repeated, tag = "4")]
+    pub removed_clusters: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// added or changed
+    #[prost(message, repeated, tag = "5")]
+    pub routes: ::prost::alloc::vec::Vec<Route>,
+    /// only host, path and path_type are read
+    #[prost(message, repeated, tag = "6")]
+    pub removed_routes: ::prost::alloc::vec::Vec<Route>,
+    /// added or changed
+    #[prost(message, repeated, tag = "7")]
+    pub server_tls: ::prost::alloc::vec::Vec<ServerTlsBundle>,
+    #[prost(string, repeated, tag = "8")]
+    pub removed_server_tls: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// added or changed
+    #[prost(map = "string, string", tag = "9")]
+    pub resource_versions: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    #[prost(string, repeated, tag = "10")]
+    pub removed_resource_versions: ::prost::alloc::vec::Vec<
+        ::prost::alloc::string::String,
+    >,
+    #[prost(int64, tag = "11")]
+    pub generated_at_unix_sec: i64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DiscoveryResponse {
+    #[prost(oneof = "discovery_response::Kind", tags = "1, 2")]
+    pub kind: ::core::option::Option<discovery_response::Kind>,
+}
+/// Nested message and enum types in `DiscoveryResponse`.
+pub mod discovery_response {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Kind {
+        /// replaces everything
+        #[prost(message, tag = "1")]
+        Snapshot(super::Snapshot),
+        #[prost(message, tag = "2")]
+        Delta(super::SnapshotDelta),
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AuthConfig {
     #[prost(string, tag = "1")]
@@ -237,6 +495,31 @@ pub mod config_discovery_client {
                 .insert(GrpcMethod::new("argon.config.ConfigDiscovery", "Watch"));
             self.inner.server_streaming(req, path, codec).await
         }
+        /// Watch with an ACK/NACK of every snapshot; the dataplane falls back to Watch when unimplemented
+        pub async fn stream(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::DiscoveryRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::DiscoveryResponse>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/argon.config.ConfigDiscovery/Stream",
+            );
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("argon.config.ConfigDiscovery", "Stream"));
+            self.inner.streaming(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -262,6 +545,17 @@ pub mod config_discovery_server {
             &self,
             request: tonic::Request<super::WatchRequest>,
         ) -> std::result::Result<tonic::Response<Self::WatchStream>, tonic::Status>;
+        /// Server streaming response type for the Stream method.
+        type StreamStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::DiscoveryResponse, tonic::Status>,
+            >
+            + std::marker::Send
+            + 'static;
+        /// Watch with an ACK/NACK of every snapshot; the dataplane falls back to Watch when unimplemented
+        async fn stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::DiscoveryRequest>>,
+        ) -> std::result::Result<tonic::Response<Self::StreamStream>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct ConfigDiscoveryServer<T> {
@@ -385,6 +679,54 @@ pub mod config_discovery_server {
                     };
                     Box::pin(fut)
                 }
+                "/argon.config.ConfigDiscovery/Stream" => {
+                    #[allow(non_camel_case_types)]
+                    struct StreamSvc<T: ConfigDiscovery>(pub Arc<T>);
+                    impl<
+                        T: ConfigDiscovery,
+                    > tonic::server::StreamingService<super::DiscoveryRequest>
+                    for StreamSvc<T> {
+                        type Response = super::DiscoveryResponse;
+                        type ResponseStream = T::StreamStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                tonic::Streaming<super::DiscoveryRequest>,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ConfigDiscovery>::stream(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = StreamSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(empty_body());
@@ -1,6 +1,8 @@
-use crate::argon_config::Snapshot;
+use crate::argon_config::{ServerTlsBundle, Snapshot};
+use crate::snapshot::Rejection;
 use arc_swap::ArcSwap;
 use rcgen::generate_simple_self_signed;
+use rustls::InconsistentKeys;
 use rustls::crypto::aws_lc_rs::sign::any_supported_type;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use rustls::server::{ClientHello, ResolvesServerCert};
@@ -25,75 +27,126 @@ impl DynResolver {
     }
 }
 
-pub fn certificates_from_snap(snapshot: &Snapshot) -> HashMap<String, Arc<CertifiedKey>> {
+/// Bundles parsed for the previous snapshot by name, so an update only parses the
+/// certificates and keys that changed.
+#[derive(Default)]
+pub struct ParsedBundles(HashMap<String, ParsedBundle>);
+
+struct ParsedBundle {
+    cert_pem: Vec<u8>,
+    key_pem: Vec<u8>,
+    key: Arc<CertifiedKey>,
+}
+
+// certificates per SNI host, and the bundles that could not be loaded; `parsed` is
+// replaced with the bundles of `snapshot`
+pub fn certificates_from_snap(
+    snapshot: &Snapshot,
+    parsed: &mut ParsedBundles,
+) -> (HashMap<String, Arc<CertifiedKey>>, Vec<Rejection>) {
     let mut map = HashMap::new();
+    let mut owners: HashMap<&str, &str> = HashMap::new();
+    let mut rejected = Vec::new();
+    let mut previous = std::mem::take(&mut parsed.0);
+    let mut reused = 0;
 
     for sni in &snapshot.server_tls {
-        let mut cert_reader = Cursor::new(&sni.cert_pem[..]);
-        let mut chain_der: Vec<CertificateDer<'static>> = Vec::new();
-
-        for item in read_all(&mut cert_reader) {
-            match item {
-                Ok(Item::X509Certificate(cert)) => {
-                    chain_der.push(cert.into());
-                }
-                Ok(_other) => {
-                    tracing::warn!("ignoring non-certificate PEM block in cert_pem");
-                    continue;
-                }
-                Err(e) => {
-                    tracing::error!("failed to decode cert from PEM: {}", e);
+        let unchanged = previous
+            .remove(&sni.name)
+            .filter(|p| p.cert_pem == sni.cert_pem && p.key_pem == sni.key_pem);
+        let ck = match unchanged {
+            Some(p) => {
+                reused += 1;
+                p.key
+            }
+            None => match certified_key_from_bundle(sni) {
+                Ok(ck) => Arc::new(ck),
+                Err(message) => {
+                    tracing::error!(bundle = %sni.name, "{}", message);
+                    rejected.push(Rejection::new("ServerTlsBundle", &sni.name, message));
                     continue;
                 }
+            },
+        };
+        parsed.0.insert(
+            sni.name.clone(),
+            ParsedBundle {
+                cert_pem: sni.cert_pem.clone(),
+                key_pem: sni.key_pem.clone(),
+                key: ck.clone(),
+            },
+        );
+
+        for host in sni.sni.iter() {
+            if let Some(owner) = owners.insert(host, &sni.name)
+                && owner != sni.name
+            {
+                rejected.push(Rejection::new(
+                    "ServerTlsBundle",
+                    &sni.name,
+                    format!("duplicate SNI {host}, also served by {owner}"),
+                ));
             }
+            map.insert(host.clone(), ck.clone());
         }
+    }
 
-        if chain_der.is_empty() {
-            tracing::error!("no X.509 certificates found in cert_pem");
-            continue;
-        }
+    tracing::info!(
+        total = snapshot.server_tls.len(),
+        reused,
+        unique_hosts = map.len(),
+        "parsed certificates from snapshot"
+    );
+    (map, rejected)
+}
 
-        let mut key_reader = Cursor::new(&sni.key_pem[..]);
+fn certified_key_from_bundle(sni: &ServerTlsBundle) -> Result<CertifiedKey, String> {
+    let mut cert_reader = Cursor::new(&sni.cert_pem[..]);
+    let mut chain_der: Vec<CertificateDer<'static>> = Vec::new();
 
-        let key_der: PrivateKeyDer<'static> = match rustls_pemfile::read_one(&mut key_reader) {
-            Ok(Some(Item::Pkcs8Key(der))) => PrivateKeyDer::from(der),
-            Ok(Some(Item::Pkcs1Key(der))) => PrivateKeyDer::from(der), // RSA
-            Ok(Some(Item::Sec1Key(der))) => PrivateKeyDer::from(der),  // EC
-            Ok(Some(_other)) => {
-                tracing::error!("unsupported private key type in key_pem");
-                continue;
+    for item in read_all(&mut cert_reader) {
+        match item {
+            Ok(Item::X509Certificate(cert)) => {
+                chain_der.push(cert);
             }
-            Ok(None) => {
-                tracing::error!("no private key found in key_pem");
+            Ok(_other) => {
+                tracing::warn!("ignoring non-certificate PEM block in cert_pem");
                 continue;
             }
             Err(e) => {
-                tracing::error!(error = %e, "failed to parse key_pem");
+                tracing::error!("failed to decode cert from PEM: {}", e);
                 continue;
             }
-        };
+        }
+    }
 
-        let signing_key = match any_supported_type(&key_der) {
-            Ok(k) => k,
-            Err(e) => {
-                tracing::error!(error = %e, "any_supported_type failed for key_pem");
-                continue;
-            }
-        };
+    if chain_der.is_empty() {
+        return Err("no X.509 certificates found in cert_pem".to_string());
+    }
 
-        let ck = Arc::new(CertifiedKey::new(chain_der, signing_key));
+    let mut key_reader = Cursor::new(&sni.key_pem[..]);
 
-        for host in sni.sni.iter() {
-            map.insert(host.clone(), ck.clone());
+    let key_der: PrivateKeyDer<'static> = match rustls_pemfile::read_one(&mut key_reader) {
+        Ok(Some(Item::Pkcs8Key(der))) => PrivateKeyDer::from(der),
+        Ok(Some(Item::Pkcs1Key(der))) => PrivateKeyDer::from(der), // RSA
+        Ok(Some(Item::Sec1Key(der))) => PrivateKeyDer::from(der),  // EC
+        Ok(Some(_other)) => return Err("unsupported private key type in key_pem".to_string()),
+        Ok(None) => return Err("no private key found in key_pem".to_string()),
+        Err(e) => return Err(format!("failed to parse key_pem: {}", e)),
+    };
+
+    let signing_key =
+        any_supported_type(&key_der).map_err(|e| format!("unsupported key_pem: {}", e))?;
+
+    // a mismatched pair would only fail in the TLS handshakes of the hosts it serves
+    let key = CertifiedKey::new(chain_der, signing_key);
+    match key.keys_match() {
+        Ok(()) | Err(rustls::Error::InconsistentKeys(InconsistentKeys::Unknown)) => Ok(key),
+        Err(rustls::Error::InconsistentKeys(InconsistentKeys::KeyMismatch)) => {
+            Err("key_pem does not match the certificate in cert_pem".to_string())
         }
+        Err(e) => Err(format!("invalid certificate in cert_pem: {}", e)),
     }
-
-    tracing::info!(
-        total = snapshot.server_tls.len(),
-        unique_hosts = map.len(),
-        "parsed certificates from snapshot"
-    );
-    map
 }
 
 impl Debug for DynResolver {
@@ -143,3 +196,79 @@ pub fn make_dummy_cert() -> anyhow::Result<Arc<CertifiedKey>> {
     let ck = CertifiedKey::new(vec![cert_der], signing_key);
     Ok(Arc::new(ck))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // self-signed certificate and its PKCS#8 key, both PEM
+    fn self_signed(host: &str) -> (Vec<u8>, Vec<u8>) {
+        let cert = generate_simple_self_signed(vec![host.to_string()]).unwrap();
+        (
+            cert.cert.pem().into_bytes(),
+            cert.signing_key.serialize_pem().into_bytes(),
+        )
+    }
+
+    fn bundle(name: &str, host: &str, cert_pem: &[u8], key_pem: &[u8]) -> ServerTlsBundle {
+        ServerTlsBundle {
+            name: name.into(),
+            sni: vec![host.into()],
+            cert_pem: cert_pem.to_vec(),
+            key_pem: key_pem.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bundle_with_the_key_of_another_certificate_is_rejected() {
+        let (a_cert, a_key) = self_signed("a.example");
+        let (b_cert, _) = self_signed("b.example");
+        let snapshot = Snapshot {
+            server_tls: vec![
+                bundle("default/a-tls", "a.example", &a_cert, &a_key),
+                bundle("default/b-tls", "b.example", &b_cert, &a_key),
+            ],
+            ..Default::default()
+        };
+
+        let (map, rejected) = certificates_from_snap(&snapshot, &mut ParsedBundles::default());
+        assert!(map.contains_key("a.example"));
+        assert!(!map.contains_key("b.example"));
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].kind, "ServerTlsBundle");
+        assert_eq!(rejected[0].name, "default/b-tls");
+        assert_eq!(
+            rejected[0].message,
+            "key_pem does not match the certificate in cert_pem"
+        );
+    }
+
+    #[test]
+    fn unchanged_bundles_are_reused_and_fixed_ones_accepted() {
+        let (cert, key) = self_signed("a.example");
+        let (_, other_key) = self_signed("a.example");
+        let mut parsed = ParsedBundles::default();
+
+        let snapshot = Snapshot {
+            server_tls: vec![bundle("default/a-tls", "a.example", &cert, &key)],
+            ..Default::default()
+        };
+        let (first, rejected) = certificates_from_snap(&snapshot, &mut parsed);
+        assert!(rejected.is_empty());
+        let (second, _) = certificates_from_snap(&snapshot, &mut parsed);
+        assert!(Arc::ptr_eq(&first["a.example"], &second["a.example"]));
+
+        let broken = Snapshot {
+            server_tls: vec![bundle("default/a-tls", "a.example", &cert, &other_key)],
+            ..Default::default()
+        };
+        let (map, rejected) = certificates_from_snap(&broken, &mut parsed);
+        assert!(map.is_empty());
+        assert_eq!(rejected.len(), 1);
+
+        let (map, rejected) = certificates_from_snap(&snapshot, &mut parsed);
+        assert!(rejected.is_empty());
+        assert!(map.contains_key("a.example"));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::snapshot::CircuitBreakerRule;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Which threshold rejected the request; sent back in the overload header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tripped {
+    MaxRequests,
+    MaxPending,
+}
+
+impl Tripped {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tripped::MaxRequests => "max-requests",
+            Tripped::MaxPending => "max-pending-requests",
+        }
+    }
+}
+
+/// Per-cluster concurrency limits. A zero threshold means unlimited. Upstream connections
+/// are not limited separately; a request in flight holds at most one of them.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    rule: CircuitBreakerRule,
+    requests: Option<Arc<Semaphore>>,
+    pending: Arc<AtomicUsize>,
+    retries: Arc<AtomicUsize>,
+}
+
+/// Slot of an admitted request; released on drop.
+#[derive(Debug)]
+pub struct RequestPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// Slot of a retry in flight; released on drop.
+#[derive(Debug)]
+pub struct RetryPermit {
+    retries: Arc<AtomicUsize>,
+}
+
+impl Drop for RetryPermit {
+    fn drop(&mut self) {
+        self.retries.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(rule: CircuitBreakerRule) -> Self {
+        let requests = (rule.max_requests > 0).then(|| Arc::new(Semaphore::new(rule.max_requests)));
+        Self {
+            rule,
+            requests,
+            pending: Arc::new(AtomicUsize::new(0)),
+            retries: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // take a request slot; when all are busy wait up to pending_timeout as one of max_pending requests
+    pub async fn admit(&self) -> Result<RequestPermit, Tripped> {
+        let Some(requests) = &self.requests else {
+            return Ok(RequestPermit { _permit: None });
+        };
+
+        if let Ok(permit) = requests.clone().try_acquire_owned() {
+            return Ok(RequestPermit {
+                _permit: Some(permit),
+            });
+        }
+        if self.rule.max_pending == 0 {
+            return Err(Tripped::MaxRequests);
+        }
+
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed) + 1;
+        let _pending = PendingGuard(self.pending.clone());
+        if pending > self.rule.max_pending {
+            return Err(Tripped::MaxPending);
+        }
+
+        match tokio::time::timeout(self.rule.pending_timeout, requests.clone().acquire_owned())
+            .await
+        {
+            Ok(Ok(permit)) => Ok(RequestPermit {
+                _permit: Some(permit),
+            }),
+            _ => Err(Tripped::MaxPending),
+        }
+    }
+
+    // None when max_retries retries are already in flight for the cluster
+    pub fn try_retry(&self) -> Option<RetryPermit> {
+        let retries = self.retries.fetch_add(1, Ordering::Relaxed) + 1;
+        let permit = RetryPermit {
+            retries: self.retries.clone(),
+        };
+        if self.rule.max_retries > 0 && retries > self.rule.max_retries {
+            return None;
+        }
+        Some(permit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn breaker(max_requests: usize, max_pending: usize, max_retries: usize) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerRule {
+            max_requests,
+            max_pending,
+            max_retries,
+            pending_timeout: Duration::from_millis(50),
+        })
+    }
+
+    #[tokio::test]
+    async fn admits_everything_without_a_request_limit() {
+        let breaker = breaker(0, 0, 1);
+        let permits: Vec<_> = (0..100).map(|_| breaker.admit()).collect();
+        for permit in permits {
+            assert!(permit.await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_at_max_requests_without_pending() {
+        let breaker = breaker(2, 0, 0);
+        let _a = breaker.admit().await.unwrap();
+        let _b = breaker.admit().await.unwrap();
+        assert_eq!(breaker.admit().await.unwrap_err(), Tripped::MaxRequests);
+    }
+
+    #[tokio::test]
+    async fn pending_request_gets_the_released_slot() {
+        let breaker = Arc::new(breaker(1, 1, 0));
+        let first = breaker.admit().await.unwrap();
+        let waiting = tokio::spawn({
+            let breaker = breaker.clone();
+            async move { breaker.admit().await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(first);
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn pending_request_times_out() {
+        let breaker = breaker(1, 1, 0);
+        let _first = breaker.admit().await.unwrap();
+        assert_eq!(breaker.admit().await.unwrap_err(), Tripped::MaxPending);
+        assert_eq!(breaker.pending.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_over_max_pending() {
+        let breaker = Arc::new(breaker(1, 1, 0));
+        let _first = breaker.admit().await.unwrap();
+        let waiting = tokio::spawn({
+            let breaker = breaker.clone();
+            async move { breaker.admit().await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(breaker.admit().await.unwrap_err(), Tripped::MaxPending);
+        assert_eq!(waiting.await.unwrap(), Err(Tripped::MaxPending));
+    }
+
+    #[test]
+    fn retries_are_capped_while_in_flight() {
+        let breaker = breaker(0, 0, 2);
+        let a = breaker.try_retry().unwrap();
+        let _b = breaker.try_retry().unwrap();
+        assert!(breaker.try_retry().is_none());
+        drop(a);
+        assert!(breaker.try_retry().is_some());
+    }
+
+    #[test]
+    fn retries_are_unlimited_at_zero() {
+        let breaker = breaker(1, 0, 0);
+        let permits: Vec<_> = (0..100).filter_map(|_| breaker.try_retry()).collect();
+        assert_eq!(permits.len(), 100);
+    }
+}
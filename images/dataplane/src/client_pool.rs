@@ -74,11 +74,10 @@ impl ClientPool {
                 }
             }
 
-            let mut cfg = ClientConfig::builder()
+            ClientConfig::builder()
                 .dangerous()
                 .with_custom_certificate_verifier(Arc::new(NoCertVerifier))
-                .with_no_client_auth();
-            cfg
+                .with_no_client_auth()
         };
 
         let https_insecure = HttpsConnectorBuilder::new()
@@ -0,0 +1,489 @@
+use crate::argon_config::{Route, Snapshot, SnapshotDelta};
+use crate::certs::{self, ParsedBundles};
+use crate::health::HealthChecker;
+use crate::snapshot::{Locality, Rejection, RouteTable, ValidationMode};
+use arc_swap::ArcSwap;
+use prost::Message;
+use rustls::sign::CertifiedKey;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Validates snapshots and swaps them into the snapshot, route table and SNI map the
+/// proxy serves from. Accepted snapshots are cached on disk when a cache path is set, so a
+/// restart can serve the last known good config before the controller is reachable.
+/// Clusters and TLS bundles that did not change keep their runtime state and parsed keys.
+#[derive(Clone)]
+pub struct ConfigStore {
+    snapshot: Arc<RwLock<Snapshot>>,
+    route_table: Arc<RwLock<Arc<RouteTable>>>,
+    sni: Arc<ArcSwap<HashMap<String, Arc<CertifiedKey>>>>,
+    locality: Locality,
+    health: Arc<HealthChecker>,
+    validation: ValidationMode,
+    cache_path: Option<PathBuf>,
+    parsed_bundles: Arc<Mutex<ParsedBundles>>,
+}
+
+impl ConfigStore {
+    pub fn new(
+        snapshot: Arc<RwLock<Snapshot>>,
+        route_table: Arc<RwLock<Arc<RouteTable>>>,
+        sni: Arc<ArcSwap<HashMap<String, Arc<CertifiedKey>>>>,
+        locality: Locality,
+        health: Arc<HealthChecker>,
+        validation: ValidationMode,
+        cache_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            snapshot,
+            route_table,
+            sni,
+            locality,
+            health,
+            validation,
+            cache_path,
+            parsed_bundles: Arc::default(),
+        }
+    }
+
+    // SNAPSHOT_CACHE_PATH: file the last accepted snapshot is written to; unset disables caching
+    pub fn cache_path_from_env() -> Option<PathBuf> {
+        std::env::var("SNAPSHOT_CACHE_PATH")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    }
+
+    // apply the cached snapshot at boot; true when it is now in use
+    pub async fn load_cached(&self) -> bool {
+        let Some(path) = &self.cache_path else {
+            return false;
+        };
+        let bytes = match tokio::fs::read(path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                info!(path = %path.display(), "no cached snapshot");
+                return false;
+            }
+            Err(err) => {
+                warn!(path = %path.display(), %err, "failed to read cached snapshot");
+                return false;
+            }
+        };
+        let snap = match Snapshot::decode(bytes.as_slice()) {
+            Ok(snap) => snap,
+            Err(err) => {
+                warn!(path = %path.display(), %err, "failed to decode cached snapshot");
+                return false;
+            }
+        };
+
+        let version = snap.version.clone();
+        if self.swap(snap).await.is_err() {
+            return false;
+        }
+        info!(path = %path.display(), %version, "serving cached snapshot until the controller is reachable");
+        true
+    }
+
+    // Ok with the parts left out when `snap` is now in use; Err when strict validation
+    // refused it and the last known good snapshot stays in use
+    pub async fn apply(&self, snap: Snapshot) -> Result<Vec<Rejection>, Vec<Rejection>> {
+        let encoded = self.cache_path.as_ref().map(|_| snap.encode_to_vec());
+        let rejected = self.swap(snap).await?;
+        if let (Some(path), Some(encoded)) = (&self.cache_path, encoded)
+            && let Err(err) = write_atomic(path, &encoded).await
+        {
+            warn!(path = %path.display(), %err, "failed to cache snapshot");
+        }
+        Ok(rejected)
+    }
+
+    // merge `delta` into the snapshot in use and apply the result; a delta built on another
+    // version is refused as a whole
+    pub async fn apply_delta(
+        &self,
+        delta: SnapshotDelta,
+    ) -> Result<Vec<Rejection>, Vec<Rejection>> {
+        let mut snap = self.snapshot().await;
+        if delta.base_version != snap.version {
+            let rejected = Rejection::new(
+                "SnapshotDelta",
+                &delta.version,
+                format!(
+                    "base version {:?} does not match {:?} in use",
+                    delta.base_version, snap.version
+                ),
+            );
+            warn!(version = %delta.version, "rejected config: {}", rejected.message);
+            return Err(vec![rejected]);
+        }
+        merge_delta(&mut snap, delta);
+        self.apply(snap).await
+    }
+
+    async fn swap(&self, snap: Snapshot) -> Result<Vec<Rejection>, Vec<Rejection>> {
+        let previous = self.route_table.read().await.clone();
+        let route_table = RouteTable::new(&snap, &self.locality, self.health.registry(), &previous);
+        drop(previous);
+        // a refused snapshot leaves the cache holding its bundles; the next one just reparses
+        // the bundles it shares with the snapshot in use
+        let (certs, rejected_certs) =
+            certs::certificates_from_snap(&snap, &mut self.parsed_bundles.lock().unwrap());
+        let mut rejected = route_table.rejected().to_vec();
+        rejected.extend(rejected_certs);
+        for r in &rejected {
+            warn!(version = %snap.version, kind = r.kind, name = %r.name, "rejected config: {}", r.message);
+        }
+
+        if self.validation == ValidationMode::Strict && !rejected.is_empty() {
+            let in_use = self.version().await;
+            warn!(
+                version = %snap.version,
+                %in_use,
+                errors = rejected.len(),
+                "snapshot failed validation, keeping the last known good one"
+            );
+            return Err(rejected);
+        }
+
+        self.health.sync(&route_table);
+        *self.route_table.write().await = Arc::new(route_table);
+        self.sni.store(Arc::new(certs));
+        *self.snapshot.write().await = snap;
+        Ok(rejected)
+    }
+
+    pub async fn snapshot(&self) -> Snapshot {
+        self.snapshot.read().await.clone()
+    }
+
+    // generated_at_unix_sec of the snapshot in use; 0 before the first
+    pub async fn generated_at(&self) -> i64 {
+        self.snapshot.read().await.generated_at_unix_sec
+    }
+
+    // version of the snapshot in use; empty before the first
+    pub async fn version(&self) -> String {
+        self.route_table.read().await.version().to_string()
+    }
+}
+
+fn merge_delta(snap: &mut Snapshot, delta: SnapshotDelta) {
+    snap.clusters
+        .retain(|c| !delta.removed_clusters.contains(&c.name));
+    for cluster in delta.clusters {
+        match snap.clusters.iter_mut().find(|c| c.name == cluster.name) {
+            Some(existing) => *existing = cluster,
+            None => snap.clusters.push(cluster),
+        }
+    }
+
+    // routes have no name; host, path and path type identify them
+    let same_route =
+        |a: &Route, b: &Route| a.host == b.host && a.path == b.path && a.path_type == b.path_type;
+    snap.routes.retain(|r| {
+        !delta
+            .removed_routes
+            .iter()
+            .any(|removed| same_route(r, removed))
+    });
+    for route in delta.routes {
+        match snap.routes.iter_mut().find(|r| same_route(r, &route)) {
+            Some(existing) => *existing = route,
+            None => snap.routes.push(route),
+        }
+    }
+
+    snap.server_tls
+        .retain(|b| !delta.removed_server_tls.contains(&b.name));
+    for bundle in delta.server_tls {
+        match snap.server_tls.iter_mut().find(|b| b.name == bundle.name) {
+            Some(existing) => *existing = bundle,
+            None => snap.server_tls.push(bundle),
+        }
+    }
+
+    for key in &delta.removed_resource_versions {
+        snap.resource_versions.remove(key);
+    }
+    snap.resource_versions.extend(delta.resource_versions);
+    snap.version = delta.version;
+    snap.generated_at_unix_sec = delta.generated_at_unix_sec;
+}
+
+// write to a temporary file next to `path` and rename it over, so a crash never leaves a
+// truncated cache; the snapshot holds TLS private keys, hence 0600. The directory is synced
+// too, or the rename itself may be lost on power failure.
+async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    // suffixed rather than `with_extension`, which would turn "snapshot.pb" into "snapshot.tmp"
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .await?;
+    file.write_all(bytes).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&tmp, path).await?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    tokio::fs::File::open(dir).await?.sync_all().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argon_config::{Cluster, Endpoint};
+    use crate::client_pool::ClientPool;
+    use std::sync::atomic::Ordering;
+    use tokio_util::sync::CancellationToken;
+
+    fn store() -> ConfigStore {
+        cached_store(None)
+    }
+
+    fn cached_store(cache_path: Option<PathBuf>) -> ConfigStore {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client_pool = Arc::new(ArcSwap::from_pointee(ClientPool::new_http_pool_connector(
+            1,
+        )));
+        ConfigStore::new(
+            Arc::default(),
+            Arc::new(RwLock::new(Arc::new(RouteTable::default()))),
+            Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            Locality::default(),
+            Arc::new(HealthChecker::new(client_pool, CancellationToken::new())),
+            ValidationMode::Permissive,
+            cache_path,
+        )
+    }
+
+    // empty directory of its own for each test
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("argon-config-store-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cluster(name: &str, lb_policy: &str, addresses: &[&str]) -> Cluster {
+        Cluster {
+            name: name.into(),
+            lb_policy: lb_policy.into(),
+            endpoints: addresses
+                .iter()
+                .map(|address| Endpoint {
+                    address: address.to_string(),
+                    port: 8080,
+                    weight: 1,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn route(host: &str, path: &str, cluster: &str) -> Route {
+        Route {
+            host: host.into(),
+            path: path.into(),
+            path_type: "Prefix".into(),
+            cluster: cluster.into(),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            version: "v1".into(),
+            clusters: vec![
+                cluster("rr", "RoundRobin", &["10.0.0.1", "10.0.0.2", "10.0.0.3"]),
+                cluster("lc", "LeastConn", &["10.0.1.1", "10.0.1.2"]),
+                cluster("other", "RoundRobin", &["10.0.2.1"]),
+            ],
+            routes: vec![
+                route("a.example", "/", "rr"),
+                route("b.example", "/", "lc"),
+                route("c.example", "/", "other"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn delta_keeps_lb_state_of_untouched_clusters() {
+        let store = store();
+        store.apply(snapshot()).await.unwrap();
+        let before = store.route_table.read().await.clone();
+        assert_eq!(
+            before.get_endpoint("rr", None).unwrap().endpoint.address,
+            "10.0.0.1"
+        );
+        let busy = before.get_endpoint("lc", None).unwrap();
+        busy.counter
+            .as_ref()
+            .unwrap()
+            .fetch_add(1, Ordering::Relaxed);
+
+        let delta = SnapshotDelta {
+            version: "v2".into(),
+            base_version: "v1".into(),
+            clusters: vec![cluster("other", "RoundRobin", &["10.0.2.2"])],
+            routes: vec![route("d.example", "/", "other")],
+            ..Default::default()
+        };
+        assert!(store.apply_delta(delta).await.unwrap().is_empty());
+
+        let after = store.route_table.read().await.clone();
+        assert_eq!(after.version(), "v2");
+        for name in ["rr", "lc"] {
+            assert!(Arc::ptr_eq(
+                &before.get_cluster_rules(name).unwrap(),
+                &after.get_cluster_rules(name).unwrap()
+            ));
+        }
+        assert!(!Arc::ptr_eq(
+            &before.get_cluster_rules("other").unwrap(),
+            &after.get_cluster_rules("other").unwrap()
+        ));
+        // the round-robin cursor carries on and the busy endpoint is still counted
+        assert_eq!(
+            after.get_endpoint("rr", None).unwrap().endpoint.address,
+            "10.0.0.2"
+        );
+        let idle = after.get_endpoint("lc", None).unwrap();
+        assert_ne!(idle.endpoint.address, busy.endpoint.address);
+        assert!(after.choose_route("d.example", "/").is_some());
+        assert_eq!(store.snapshot().await.routes.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn delta_removes_clusters_routes_and_resource_versions() {
+        let store = store();
+        let mut snap = snapshot();
+        snap.resource_versions
+            .insert("ing:default/c".into(), "7".into());
+        store.apply(snap).await.unwrap();
+
+        let delta = SnapshotDelta {
+            version: "v2".into(),
+            base_version: "v1".into(),
+            removed_clusters: vec!["other".into()],
+            removed_routes: vec![Route {
+                cluster: String::new(),
+                ..route("c.example", "/", "other")
+            }],
+            removed_resource_versions: vec!["ing:default/c".into()],
+            generated_at_unix_sec: 42,
+            ..Default::default()
+        };
+        assert!(store.apply_delta(delta).await.unwrap().is_empty());
+
+        let snap = store.snapshot().await;
+        assert_eq!(snap.version, "v2");
+        assert_eq!(snap.generated_at_unix_sec, 42);
+        assert!(snap.clusters.iter().all(|c| c.name != "other"));
+        assert!(snap.routes.iter().all(|r| r.host != "c.example"));
+        assert!(snap.resource_versions.is_empty());
+        let table = store.route_table.read().await.clone();
+        assert!(table.get_cluster_rules("other").is_none());
+        assert!(table.choose_route("c.example", "/").is_none());
+    }
+
+    #[tokio::test]
+    async fn delta_on_another_version_is_refused() {
+        let store = store();
+        store.apply(snapshot()).await.unwrap();
+
+        let delta = SnapshotDelta {
+            version: "v3".into(),
+            base_version: "v2".into(),
+            removed_clusters: vec!["rr".into()],
+            ..Default::default()
+        };
+        let rejected = store.apply_delta(delta).await.unwrap_err();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].kind, "SnapshotDelta");
+        assert_eq!(store.version().await, "v1");
+        assert_eq!(store.snapshot().await.clusters.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn load_cached_without_a_file_serves_nothing() {
+        let dir = cache_dir("missing");
+        let store = cached_store(Some(dir.join("snapshot.pb")));
+        assert!(!store.load_cached().await);
+        assert_eq!(store.version().await, "");
+        assert!(
+            store
+                .route_table
+                .read()
+                .await
+                .choose_route("a.example", "/")
+                .is_none()
+        );
+        assert!(!cached_store(None).load_cached().await);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn load_cached_ignores_a_corrupt_file() {
+        let dir = cache_dir("corrupt");
+        let path = dir.join("snapshot.pb");
+        std::fs::write(&path, [0xff; 32]).unwrap();
+        let store = cached_store(Some(path));
+        assert!(!store.load_cached().await);
+        assert_eq!(store.version().await, "");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn applied_snapshots_are_cached_and_loaded_after_a_restart() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = cache_dir("valid");
+        let path = dir.join("snapshot.pb");
+        // a neighbour that `with_extension("tmp")` would have overwritten
+        std::fs::write(dir.join("snapshot.tmp"), "unrelated").unwrap();
+
+        cached_store(Some(path.clone()))
+            .apply(snapshot())
+            .await
+            .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mut files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["snapshot.pb", "snapshot.tmp"]);
+        assert_eq!(
+            std::fs::read(dir.join("snapshot.tmp")).unwrap(),
+            b"unrelated"
+        );
+
+        let restarted = cached_store(Some(path));
+        assert!(restarted.load_cached().await);
+        assert_eq!(restarted.version().await, "v1");
+        let table = restarted.route_table.read().await.clone();
+        assert!(table.choose_route("a.example", "/").is_some());
+        assert!(table.get_cluster_rules("lc").is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::argon_config::Endpoint;
+
+// virtual nodes per unit of endpoint weight on the ring (ketama uses the same number)
+const RING_VNODES_PER_WEIGHT: usize = 160;
+const MAX_RING_SIZE: usize = 1 << 20;
+// prime, Envoy default
+const MAGLEV_TABLE_SIZE: usize = 65_537;
+const EMPTY_SLOT: u32 = u32::MAX;
+const MAGLEV_MAX_REHASH: usize = 8;
+
+// FNV-1a with murmur3 finalizer: stable across processes and builds, so every dataplane
+// replica maps the same key to the same endpoint
+pub fn hash_bytes(data: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in data {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+// tables are keyed by address:port (not by index), so an endpoint keeps its slots when
+// other endpoints come and go
+fn endpoint_key(endpoint: &Endpoint) -> String {
+    format!("{}:{}", endpoint.address, endpoint.port)
+}
+
+fn weight(endpoint: &Endpoint) -> usize {
+    endpoint.weight.max(0) as usize
+}
+
+#[derive(Debug)]
+pub struct RingHash {
+    ring: Vec<(u64, usize)>,
+}
+
+impl RingHash {
+    pub fn build(endpoints: &[Endpoint], candidates: &[usize]) -> Self {
+        let total_weight: usize = candidates.iter().map(|&i| weight(&endpoints[i])).sum();
+        let mut per_weight = RING_VNODES_PER_WEIGHT;
+        if total_weight.saturating_mul(per_weight) > MAX_RING_SIZE {
+            per_weight = (MAX_RING_SIZE / total_weight).max(1);
+        }
+
+        let mut ring = Vec::with_capacity(total_weight * per_weight);
+        for &idx in candidates {
+            let key = endpoint_key(&endpoints[idx]);
+            for vnode in 0..weight(&endpoints[idx]) * per_weight {
+                let h = hash_bytes(format!("{key}_{vnode}").as_bytes());
+                ring.push((h, idx));
+            }
+        }
+        ring.sort_unstable();
+
+        RingHash { ring }
+    }
+
+    // walk clockwise from the key until an accepted endpoint is found
+    pub fn pick(&self, hash: u64, accept: impl Fn(usize) -> bool) -> Option<usize> {
+        if self.ring.is_empty() {
+            return None;
+        }
+        let start = self.ring.partition_point(|(h, _)| *h < hash);
+        (0..self.ring.len())
+            .map(|i| self.ring[(start + i) % self.ring.len()].1)
+            .find(|&idx| accept(idx))
+    }
+}
+
+#[derive(Debug)]
+pub struct Maglev {
+    table: Vec<u32>,
+}
+
+struct MaglevEntry {
+    idx: usize,
+    offset: usize,
+    skip: usize,
+    weight: usize,
+    target: usize,
+    next: usize,
+}
+
+impl Maglev {
+    pub fn build(endpoints: &[Endpoint], candidates: &[usize]) -> Self {
+        let size = MAGLEV_TABLE_SIZE;
+        let mut entries: Vec<MaglevEntry> = candidates
+            .iter()
+            .filter(|&&idx| weight(&endpoints[idx]) > 0)
+            .map(|&idx| {
+                let key = endpoint_key(&endpoints[idx]);
+                MaglevEntry {
+                    idx,
+                    offset: (hash_bytes(key.as_bytes()) % size as u64) as usize,
+                    skip: (hash_bytes(format!("{key}#skip").as_bytes()) % (size as u64 - 1))
+                        as usize
+                        + 1,
+                    weight: weight(&endpoints[idx]),
+                    target: 0,
+                    next: 0,
+                }
+            })
+            .collect();
+
+        let Some(max_weight) = entries.iter().map(|e| e.weight).max() else {
+            return Maglev { table: Vec::new() };
+        };
+
+        // weighted population: an entry with max weight fills a slot every iteration,
+        // one with a third of it every third iteration
+        let mut table = vec![EMPTY_SLOT; size];
+        let mut filled = 0;
+        let mut iteration = 1;
+        while filled < size {
+            for entry in entries.iter_mut() {
+                if filled == size {
+                    break;
+                }
+                if iteration * entry.weight < entry.target {
+                    continue;
+                }
+                entry.target += max_weight;
+                loop {
+                    let slot = (entry.offset + entry.skip * entry.next) % size;
+                    entry.next += 1;
+                    if table[slot] == EMPTY_SLOT {
+                        table[slot] = entry.idx as u32;
+                        filled += 1;
+                        break;
+                    }
+                }
+            }
+            iteration += 1;
+        }
+
+        Maglev { table }
+    }
+
+    // rehash a few times when the slot owner is not accepted
+    pub fn pick(&self, hash: u64, accept: impl Fn(usize) -> bool) -> Option<usize> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let mut h = hash;
+        for _ in 0..MAGLEV_MAX_REHASH {
+            let idx = self.table[(h % self.table.len() as u64) as usize] as usize;
+            if accept(idx) {
+                return Some(idx);
+            }
+            h = hash_bytes(&h.to_le_bytes());
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum HashTable {
+    Ring(RingHash),
+    Maglev(Maglev),
+}
+
+impl HashTable {
+    pub fn pick(&self, hash: u64, accept: impl Fn(usize) -> bool) -> Option<usize> {
+        match self {
+            HashTable::Ring(ring) => ring.pick(hash, accept),
+            HashTable::Maglev(maglev) => maglev.pick(hash, accept),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints(count: usize) -> Vec<Endpoint> {
+        (0..count)
+            .map(|i| Endpoint {
+                address: format!("10.0.0.{}", i + 1),
+                port: 8080,
+                weight: 1,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    // address of the endpoint owning each of `keys` keys
+    fn owners(table: &HashTable, endpoints: &[Endpoint], keys: u64) -> Vec<String> {
+        (0..keys)
+            .map(|k| {
+                let idx = table
+                    .pick(hash_bytes(&k.to_le_bytes()), |_| true)
+                    .expect("owner");
+                endpoints[idx].address.clone()
+            })
+            .collect()
+    }
+
+    fn build(maglev: bool, endpoints: &[Endpoint]) -> HashTable {
+        let all: Vec<usize> = (0..endpoints.len()).collect();
+        if maglev {
+            HashTable::Maglev(Maglev::build(endpoints, &all))
+        } else {
+            HashTable::Ring(RingHash::build(endpoints, &all))
+        }
+    }
+
+    fn moved_share(before: &[String], after: &[String]) -> f64 {
+        let moved = before.iter().zip(after).filter(|(b, a)| b != a).count();
+        moved as f64 / before.len() as f64
+    }
+
+    #[test]
+    fn adding_an_endpoint_moves_only_its_share_of_keys() {
+        for maglev in [false, true] {
+            let five = endpoints(5);
+            let six = endpoints(6);
+            let before = owners(&build(maglev, &five), &five, 10_000);
+            let after = owners(&build(maglev, &six), &six, 10_000);
+
+            // ideal is 1/6 of the keys, all of them to the new endpoint
+            let share = moved_share(&before, &after);
+            assert!(share < 0.25, "maglev={maglev}: {share} of keys moved");
+            let to_new = before
+                .iter()
+                .zip(&after)
+                .filter(|(b, a)| b != a)
+                .filter(|(_, a)| a.as_str() == "10.0.0.6")
+                .count();
+            let moved = before.iter().zip(&after).filter(|(b, a)| b != a).count();
+            assert!(
+                to_new * 10 >= moved * 9,
+                "maglev={maglev}: {to_new}/{moved}"
+            );
+        }
+    }
+
+    #[test]
+    fn removing_an_endpoint_keeps_the_other_keys() {
+        for maglev in [false, true] {
+            let five = endpoints(5);
+            let four = endpoints(4);
+            let before = owners(&build(maglev, &five), &five, 10_000);
+            let after = owners(&build(maglev, &four), &four, 10_000);
+
+            let kept_moved = before
+                .iter()
+                .zip(&after)
+                .filter(|(b, a)| b != a && b.as_str() != "10.0.0.5")
+                .count();
+            assert!(
+                kept_moved * 20 < before.len(),
+                "maglev={maglev}: {kept_moved} keys of remaining endpoints moved"
+            );
+        }
+    }
+
+    #[test]
+    fn pick_skips_endpoints_not_accepted() {
+        for maglev in [false, true] {
+            let eps = endpoints(3);
+            let table = build(maglev, &eps);
+            for k in 0..100u64 {
+                let picked = table.pick(hash_bytes(&k.to_le_bytes()), |idx| idx != 0);
+                assert_ne!(picked, Some(0));
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::argon_config::Snapshot;
+use http::Uri;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+/// One controller the dataplane can connect to: a configured address, or one address of a
+/// DNS name resolving to several, dialed directly and verified against the name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Target {
+    pub uri: String,
+    /// TLS server name; empty when the configured address has no host
+    pub domain: String,
+}
+
+#[derive(Debug, Default)]
+struct TargetHealth {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+/// Controller replicas from CONTROLLER_ADDR. Each target backs off on its own, the one in
+/// use is kept while it works, and the others are tried in order when it fails.
+pub struct Controllers {
+    addrs: Vec<String>,
+    targets: Vec<Target>,
+    health: HashMap<String, TargetHealth>,
+    current: Option<String>,
+    /// targets that sent a snapshot older than the one in use since the last accepted one
+    stale: HashSet<String>,
+    /// newest of those snapshots
+    newest_stale: Option<Snapshot>,
+}
+
+impl Controllers {
+    pub fn new(addrs: Vec<String>) -> Self {
+        Self {
+            addrs,
+            targets: Vec::new(),
+            health: HashMap::new(),
+            current: None,
+            stale: HashSet::new(),
+            newest_stale: None,
+        }
+    }
+
+    // CONTROLLER_ADDR: comma-separated controller URIs, the first preferred at startup
+    pub fn addrs_from_env() -> Vec<String> {
+        let raw = std::env::var("CONTROLLER_ADDR").unwrap_or_default();
+        let addrs: Vec<String> = raw
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(String::from)
+            .collect();
+        if addrs.is_empty() {
+            vec!["https://127.0.0.1:18000".into()]
+        } else {
+            addrs
+        }
+    }
+
+    // expand DNS names into one target per address; run before each connect so replicas
+    // that came or went are picked up
+    pub async fn resolve(&mut self) {
+        let mut targets = Vec::new();
+        for addr in &self.addrs {
+            targets.extend(resolve_addr(addr).await);
+        }
+        if targets != self.targets {
+            info!(
+                targets = ?targets.iter().map(|t| t.uri.as_str()).collect::<Vec<_>>(),
+                "controller targets"
+            );
+        }
+        self.health
+            .retain(|uri, _| targets.iter().any(|t| &t.uri == uri));
+        self.stale
+            .retain(|uri| targets.iter().any(|t| &t.uri == uri));
+        self.targets = targets;
+    }
+
+    // the target to connect to and how long to wait first: the current one while it is not
+    // backing off, else the next in order that is not, else whichever is ready soonest
+    pub fn pick(&mut self) -> (Target, Duration) {
+        let now = Instant::now();
+        let start = self
+            .current
+            .as_ref()
+            .and_then(|uri| self.targets.iter().position(|t| &t.uri == uri))
+            .unwrap_or(0);
+        let len = self.targets.len();
+        let retry_at = |target: &Target| {
+            self.health
+                .get(&target.uri)
+                .and_then(|h| h.retry_at)
+                .unwrap_or(now)
+        };
+
+        let target = (0..len)
+            .map(|i| &self.targets[(start + i) % len])
+            .min_by_key(|target| retry_at(target).max(now))
+            .cloned()
+            .unwrap_or_else(|| Target {
+                uri: self.addrs.first().cloned().unwrap_or_default(),
+                domain: String::new(),
+            });
+        let wait = retry_at(&target).saturating_duration_since(now);
+        self.current = Some(target.uri.clone());
+        (target, wait)
+    }
+
+    pub fn succeeded(&mut self, target: &Target) {
+        self.health.remove(&target.uri);
+    }
+
+    pub fn failed(&mut self, target: &Target) {
+        let health = self.health.entry(target.uri.clone()).or_default();
+        health.failures = health.failures.saturating_add(1);
+        let backoff = BACKOFF_BASE
+            .saturating_mul(1 << (health.failures - 1).min(16))
+            .min(BACKOFF_MAX);
+        health.retry_at = Some(Instant::now() + backoff);
+    }
+
+    // `target` sent `snap`, older than the one in use. Once every target that can be reached
+    // did so, the controllers really went back: returns the newest of those snapshots to take.
+    // Targets failing to connect do not count, or one that is down would block it forever.
+    pub fn stale(&mut self, target: &Target, snap: Snapshot) -> Option<Snapshot> {
+        self.stale.insert(target.uri.clone());
+        if self
+            .newest_stale
+            .as_ref()
+            .is_none_or(|newest| snap.generated_at_unix_sec > newest.generated_at_unix_sec)
+        {
+            self.newest_stale = Some(snap);
+        }
+
+        let served_or_down = self.targets.iter().all(|t| {
+            self.stale.contains(&t.uri) || self.health.get(&t.uri).is_some_and(|h| h.failures > 0)
+        });
+        if !served_or_down {
+            return None;
+        }
+        self.stale.clear();
+        self.newest_stale.take()
+    }
+
+    pub fn accepted(&mut self) {
+        self.stale.clear();
+        self.newest_stale = None;
+    }
+}
+
+async fn resolve_addr(addr: &str) -> Vec<Target> {
+    let as_is = |domain: &str| {
+        vec![Target {
+            uri: addr.to_string(),
+            domain: domain.to_string(),
+        }]
+    };
+    let Ok(uri) = addr.parse::<Uri>() else {
+        return as_is("");
+    };
+    let Some(host) = uri.host() else {
+        return as_is("");
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.parse::<IpAddr>().is_ok() {
+        return as_is(host);
+    }
+
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("http") {
+            80
+        } else {
+            443
+        });
+    let mut ips: Vec<IpAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(addrs) => addrs.map(|a| a.ip()).collect(),
+        Err(err) => {
+            warn!(%addr, %err, "failed to resolve controller address");
+            return as_is(host);
+        }
+    };
+    ips.sort();
+    ips.dedup();
+    if ips.len() < 2 {
+        return as_is(host);
+    }
+
+    let scheme = uri.scheme_str().unwrap_or("https");
+    ips.into_iter()
+        .map(|ip| Target {
+            uri: format!("{}://{}", scheme, SocketAddr::new(ip, port)),
+            domain: host.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn controllers(count: usize) -> (Controllers, Vec<Target>) {
+        let addrs = (1..=count)
+            .map(|i| format!("https://127.0.0.{i}:18000"))
+            .collect();
+        let mut controllers = Controllers::new(addrs);
+        controllers.resolve().await;
+        let targets = controllers.targets.clone();
+        (controllers, targets)
+    }
+
+    fn snapshot(version: &str, generated_at: i64) -> Snapshot {
+        Snapshot {
+            version: version.into(),
+            generated_at_unix_sec: generated_at,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn pick_keeps_the_current_target_and_fails_over_in_order() {
+        let (mut controllers, targets) = controllers(3).await;
+        assert_eq!(targets.len(), 3);
+
+        let (first, wait) = controllers.pick();
+        assert_eq!(first, targets[0]);
+        assert_eq!(wait, Duration::ZERO);
+        assert_eq!(controllers.pick().0, targets[0]);
+
+        controllers.failed(&targets[0]);
+        assert_eq!(controllers.pick().0, targets[1]);
+        controllers.failed(&targets[1]);
+        assert_eq!(controllers.pick().0, targets[2]);
+
+        // all backing off: the one ready soonest, with the time left
+        controllers.failed(&targets[2]);
+        let (next, wait) = controllers.pick();
+        assert_eq!(next, targets[0]);
+        assert!(wait > Duration::ZERO && wait <= BACKOFF_BASE);
+    }
+
+    #[tokio::test]
+    async fn failed_backs_off_exponentially_up_to_the_cap() {
+        let (mut controllers, targets) = controllers(1).await;
+        let backoff = |controllers: &Controllers| {
+            controllers.health[&targets[0].uri]
+                .retry_at
+                .expect("backing off")
+                .saturating_duration_since(Instant::now())
+        };
+
+        controllers.failed(&targets[0]);
+        assert!(backoff(&controllers) <= BACKOFF_BASE);
+        controllers.failed(&targets[0]);
+        assert!(backoff(&controllers) > BACKOFF_BASE);
+        assert!(backoff(&controllers) <= BACKOFF_BASE * 2);
+        for _ in 0..20 {
+            controllers.failed(&targets[0]);
+        }
+        assert!(backoff(&controllers) <= BACKOFF_MAX);
+        assert!(backoff(&controllers) > BACKOFF_MAX / 2);
+
+        controllers.succeeded(&targets[0]);
+        assert_eq!(controllers.pick().1, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn stale_waits_for_every_reachable_target_then_takes_the_newest() {
+        let (mut controllers, targets) = controllers(3).await;
+
+        assert!(controllers.stale(&targets[0], snapshot("a", 10)).is_none());
+        assert!(controllers.stale(&targets[1], snapshot("b", 20)).is_none());
+        // the third never answers: it does not count once it fails to connect
+        controllers.failed(&targets[2]);
+        let taken = controllers
+            .stale(&targets[0], snapshot("a", 10))
+            .expect("taken");
+        assert_eq!(taken.version, "b");
+
+        // a new round starts after that
+        assert!(controllers.stale(&targets[0], snapshot("c", 5)).is_none());
+    }
+
+    #[tokio::test]
+    async fn accepted_snapshot_starts_a_new_stale_round() {
+        let (mut controllers, targets) = controllers(2).await;
+
+        assert!(controllers.stale(&targets[0], snapshot("a", 10)).is_none());
+        controllers.accepted();
+        assert!(controllers.stale(&targets[1], snapshot("b", 5)).is_none());
+        let taken = controllers
+            .stale(&targets[0], snapshot("c", 4))
+            .expect("taken");
+        assert_eq!(taken.version, "b");
+    }
+}
@@ -0,0 +1,161 @@
+use crate::health::HealthState;
+use crate::snapshot::{EndpointStats, RouteTable};
+use dashmap::DashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EndpointId {
+    pub cluster: String,
+    pub address: String,
+    pub port: i32,
+}
+
+/// Runtime state of one cluster endpoint, shared by every route table that has it.
+#[derive(Clone, Debug, Default)]
+pub struct EndpointState {
+    /// in-flight requests, held by the requests themselves
+    pub active: Arc<AtomicUsize>,
+    pub stats: Arc<EndpointStats>,
+    pub health: Arc<HealthState>,
+}
+
+/// Endpoint state per cluster endpoint; outlives route tables so requests still in flight
+/// on the old table and balancing decisions on the new one see the same counters, latency
+/// and health after a snapshot update.
+#[derive(Debug, Default)]
+pub struct EndpointRegistry {
+    states: DashMap<EndpointId, EndpointState>,
+}
+
+impl EndpointRegistry {
+    pub fn state(&self, id: EndpointId) -> EndpointState {
+        self.states.entry(id).or_default().clone()
+    }
+
+    // forget endpoints `route_table` no longer has, and reset the health of those for which
+    // `health_tracked` says nothing checks it anymore, so turning checks back on starts fresh
+    pub fn sync(&self, route_table: &RouteTable, health_tracked: impl Fn(&EndpointId) -> bool) {
+        let wanted: HashSet<EndpointId> = route_table
+            .clusters()
+            .flat_map(|cluster| {
+                cluster.endpoints().iter().map(|endpoint| EndpointId {
+                    cluster: cluster.name().to_string(),
+                    address: endpoint.address.clone(),
+                    port: endpoint.port,
+                })
+            })
+            .collect();
+        self.states.retain(|id, state| {
+            if !health_tracked(id) {
+                state.health = Arc::default();
+            }
+            wanted.contains(id)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argon_config::{Cluster, Endpoint, Snapshot};
+    use crate::snapshot::Locality;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    fn snapshot(addresses: &[&str]) -> Snapshot {
+        Snapshot {
+            clusters: vec![Cluster {
+                name: "svc".into(),
+                lb_policy: "RoundRobin".into(),
+                endpoints: addresses
+                    .iter()
+                    .map(|address| Endpoint {
+                        address: address.to_string(),
+                        port: 8080,
+                        weight: 1,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn id(address: &str) -> EndpointId {
+        EndpointId {
+            cluster: "svc".into(),
+            address: address.into(),
+            port: 8080,
+        }
+    }
+
+    #[test]
+    fn endpoint_state_survives_a_rebuilt_route_table() {
+        let registry = EndpointRegistry::default();
+        let locality = Locality::default();
+        let old = RouteTable::new(
+            &snapshot(&["10.0.0.1"]),
+            &locality,
+            &registry,
+            &RouteTable::default(),
+        );
+        let selected = old.get_endpoint("svc", None).unwrap();
+        selected
+            .counter
+            .as_ref()
+            .unwrap()
+            .fetch_add(1, Ordering::Relaxed);
+        selected
+            .stats
+            .as_ref()
+            .unwrap()
+            .record_latency(Duration::from_millis(7));
+
+        // a new endpoint changes the cluster, so its rule is rebuilt
+        let new = RouteTable::new(
+            &snapshot(&["10.0.0.1", "10.0.0.2"]),
+            &locality,
+            &registry,
+            &old,
+        );
+        let state = registry.state(id("10.0.0.1"));
+        assert!(Arc::ptr_eq(
+            selected.counter.as_ref().unwrap(),
+            &state.active
+        ));
+        assert!(Arc::ptr_eq(selected.stats.as_ref().unwrap(), &state.stats));
+        let cluster = new.get_cluster_rules("svc").unwrap();
+        let status = cluster.endpoint_status().next().unwrap();
+        assert_eq!(status.active, 1);
+        assert_eq!(status.latency_ewma_us, 7000.0);
+    }
+
+    #[test]
+    fn sync_forgets_removed_endpoints_and_resets_untracked_health() {
+        let registry = EndpointRegistry::default();
+        let locality = Locality::default();
+        let old = RouteTable::new(
+            &snapshot(&["10.0.0.1", "10.0.0.2"]),
+            &locality,
+            &registry,
+            &RouteTable::default(),
+        );
+        let kept = registry.state(id("10.0.0.1"));
+        let removed = registry.state(id("10.0.0.2"));
+
+        let new = RouteTable::new(&snapshot(&["10.0.0.1"]), &locality, &registry, &old);
+        registry.sync(&new, |_| false);
+        assert!(!registry.states.contains_key(&id("10.0.0.2")));
+        assert!(!Arc::ptr_eq(
+            &registry.state(id("10.0.0.2")).active,
+            &removed.active
+        ));
+
+        let state = registry.state(id("10.0.0.1"));
+        assert!(Arc::ptr_eq(&state.active, &kept.active));
+        assert!(!Arc::ptr_eq(&state.health, &kept.health));
+    }
+}
@@ -1,12 +1,11 @@
 use anyhow::{Context, anyhow};
-use http::Uri;
-use rustls::sign::CertifiedKey;
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{Notify, RwLock};
+use tokio::sync::{Notify, RwLock, mpsc};
 use tokio::time::{sleep, timeout};
+use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
+use tonic::Streaming;
 use tonic::transport::{Channel, ClientTlsConfig, Identity};
 use tracing::{info, warn};
 
@@ -18,10 +17,13 @@ mod argon_config {
 }
 
 use crate::argon_config::{
-    Snapshot, WatchRequest, config_discovery_client, config_discovery_client::ConfigDiscoveryClient,
+    ConfigError, DiscoveryRequest, DiscoveryResponse, Snapshot, WatchRequest,
+    config_discovery_client, config_discovery_client::ConfigDiscoveryClient,
+    discovery_response::Kind,
 };
-use crate::certs;
-use crate::snapshot::RouteTable;
+use crate::config_store::ConfigStore;
+use crate::controllers::Controllers;
+use crate::snapshot::Rejection;
 
 const CERT_CA_NAME: &str = "ca.crt";
 const CERT_NAME: &str = "tls.crt";
@@ -31,34 +33,21 @@ pub struct GrpcManager {
     cancel: CancellationToken,
     handle: tokio::task::JoinHandle<()>,
     certs_watcher_handle: tokio::task::JoinHandle<()>,
-    ready: Arc<RwLock<bool>>,
-    snapshot: Arc<RwLock<Snapshot>>,
-    route_table: Arc<RwLock<Arc<RouteTable>>>,
-    sni: Arc<ArcSwap<HashMap<String, Arc<CertifiedKey>>>>,
-    ca_updated: Arc<Notify>,
-    ca_pem: Arc<ArcSwap<Vec<u8>>>,
-    client_pem_updated: Arc<Notify>,
-    client_pem: Arc<ArcSwap<Vec<u8>>>,
-    client_key_pem: Arc<ArcSwap<Vec<u8>>>,
 }
 
 impl GrpcManager {
     pub fn start(
-        controller_addr: String,
+        controller_addrs: Vec<String>,
         node_id: String,
         certs_dir: PathBuf,
         ready: Arc<RwLock<bool>>,
-        snapshot: Arc<RwLock<Snapshot>>,
-        route_table: Arc<RwLock<Arc<RouteTable>>>,
-        sni: Arc<ArcSwap<HashMap<String, Arc<CertifiedKey>>>>,
+        store: ConfigStore,
     ) -> Self {
         let cancel = CancellationToken::new();
         let cancel_child = cancel.clone();
 
         let ready_for_task = ready.clone();
-        let snapshot_for_task = snapshot.clone();
-        let route_table_for_task = route_table.clone();
-        let sni_for_task = sni.clone();
+        let store_for_task = store.clone();
         let ca_updated = Arc::new(Notify::new());
         let client_pem_updated = Arc::new(Notify::new());
         let ca_pem: Arc<ArcSwap<Vec<u8>>> = Arc::new(ArcSwap::from_pointee(Vec::new()));
@@ -90,23 +79,20 @@ impl GrpcManager {
                         }
 
                         _ = sleep(Duration::from_secs(2)) => {
-                            if let Ok(changed) = try_load_and_store(&cert_ca_path, &ca_pem_clone, "CA").await {
-                                if changed {
+                            if let Ok(changed) = try_load_and_store(&cert_ca_path, &ca_pem_clone, "CA").await
+                                && changed {
                                     ca_updated_clone.notify_one();
                                 }
-                            }
 
-                            if let Ok(changed) = try_load_and_store(&client_pem_path, &client_pem_clone, "client certificate").await {
-                                if changed {
+                            if let Ok(changed) = try_load_and_store(&client_pem_path, &client_pem_clone, "client certificate").await
+                                && changed {
                                     client_pem_updated_clone.notify_one();
                                 }
-                            }
 
-                            if let Ok(changed) = try_load_and_store(&client_key_pem_path, &client_key_pem_clone, "client key").await {
-                                if changed {
+                            if let Ok(changed) = try_load_and_store(&client_key_pem_path, &client_key_pem_clone, "client key").await
+                                && changed {
                                     client_pem_updated_clone.notify_one();
                                 }
-                            }
                         }
                     }
                 }
@@ -114,8 +100,7 @@ impl GrpcManager {
         };
 
         let handle = tokio::spawn(async move {
-            let mut backoff_ms: u64 = 500;
-            let backoff_max: u64 = 10_000;
+            let mut controllers = Controllers::new(controller_addrs);
 
             loop {
                 if cancel_child.is_cancelled() {
@@ -123,6 +108,12 @@ impl GrpcManager {
                     break;
                 }
 
+                controllers.resolve().await;
+                let (target, wait) = controllers.pick();
+                if !wait.is_zero() {
+                    sleep(wait).await;
+                }
+
                 // Result<Channel, String>
                 let connect_fut = async {
                     let ca_cert_bytes = ca_pem_for_grpc_loop.load().clone();
@@ -144,20 +135,19 @@ impl GrpcManager {
                         client_key_bytes.as_ref().clone(),
                     );
 
-                    let uri = controller_addr
-                        .parse::<Uri>()
-                        .context("controller address is not a valid URI")?;
-                    let host = uri
-                        .host()
-                        .ok_or_else(|| anyhow!("controller address is missing host component"))?
-                        .to_string();
+                    if target.domain.is_empty() {
+                        return Err(anyhow!(
+                            "controller address {} is not a valid URI with a host",
+                            target.uri
+                        ));
+                    }
 
                     let tls_config = ClientTlsConfig::new()
                         .ca_certificate(ca)
                         .identity(identity)
-                        .domain_name(host);
+                        .domain_name(target.domain.clone());
 
-                    let endpoint = Channel::from_shared(controller_addr.clone())
+                    let endpoint = Channel::from_shared(target.uri.clone())
                         .context("failed to create gRPC endpoint from controller address")?;
 
                     let ch = endpoint
@@ -172,20 +162,17 @@ impl GrpcManager {
 
                 let channel = match timeout(Duration::from_secs(10), connect_fut).await {
                     Ok(Ok(ch)) => {
-                        info!("gRPC connected: {}", controller_addr);
-                        backoff_ms = 500;
+                        info!("gRPC connected: {}", target.uri);
                         ch
                     }
                     Ok(Err(e)) => {
-                        warn!("gRPC connect failed: {:?}", e);
-                        sleep(Duration::from_millis(backoff_ms)).await;
-                        backoff_ms = (backoff_ms.saturating_mul(2)).min(backoff_max);
+                        warn!("gRPC connect to {} failed: {:?}", target.uri, e);
+                        controllers.failed(&target);
                         continue;
                     }
                     Err(_) => {
-                        warn!("gRPC connect timeout");
-                        sleep(Duration::from_millis(backoff_ms)).await;
-                        backoff_ms = (backoff_ms.saturating_mul(2)).min(backoff_max);
+                        warn!("gRPC connect to {} timeout", target.uri);
+                        controllers.failed(&target);
                         continue;
                     }
                 };
@@ -193,30 +180,31 @@ impl GrpcManager {
                 let mut client: ConfigDiscoveryClient<Channel> =
                     config_discovery_client::ConfigDiscoveryClient::new(channel);
 
-                // open sream Watch
-                let mut stream = match client
-                    .watch(WatchRequest {
-                        node_id: node_id.clone(),
-                    })
+                // open snapshot stream
+                let version_info = store_for_task.version().await;
+                let (acks, mut stream) = match open_stream(&mut client, &node_id, &version_info)
                     .await
                 {
-                    Ok(resp) => {
-                        info!("gRPC watch stream established");
+                    Ok(opened) => {
+                        info!(acks = opened.0.is_some(), controller = %target.uri, "gRPC watch stream established");
+                        controllers.succeeded(&target);
                         {
                             let mut r = ready_for_task.write().await;
                             *r = true;
                         }
-                        resp.into_inner()
+                        opened
                     }
                     Err(e) => {
-                        warn!("watch RPC failed: {}", e);
-                        sleep(Duration::from_millis(backoff_ms)).await;
-                        backoff_ms = (backoff_ms.saturating_mul(2)).min(backoff_max);
+                        warn!("watch RPC to {} failed: {}", target.uri, e);
+                        controllers.failed(&target);
                         continue;
                     }
                 };
 
                 let mut got_first = false;
+                // set when the controller went away or fell behind, so the next connect
+                // fails over instead of coming straight back
+                let mut lost = false;
 
                 // read stream Snapshots
                 loop {
@@ -236,47 +224,119 @@ impl GrpcManager {
 
                         msg = stream.message() => {
                             match msg {
-                                Ok(Some(snap)) => {
-                                    // update shared snapshot
+                                Ok(Some(DiscoveryResponse { kind: Some(Kind::Snapshot(snap)) })) => {
+                                    // a replica behind the one that served the snapshot in use;
+                                    // taken only once every reachable replica turned out to be behind
+                                    let in_use_at = store_for_task.generated_at().await;
+                                    let snap = if snap.generated_at_unix_sec > 0
+                                        && snap.generated_at_unix_sec < in_use_at
                                     {
-                                        let mut slot = snapshot_for_task.write().await;
-                                        *slot = snap.clone();
-                                    }
-
-                                    // update route_table
-                                    let build_route_table = RouteTable::new(&snap);
-                                    {
-                                        let mut write_route_table = route_table_for_task.write().await;
-                                        *write_route_table = Arc::new(build_route_table);
-                                    }
-
-                                    // update TLS list
-                                    let certs = certs::certificates_from_snap(&snap);
-                                    sni_for_task.store(Arc::new(certs));
-
-                                    if !got_first {
-                                        got_first = true;
-                                        info!(
-                                            "received first snapshot: version={}, routes={}, clusters={}",
-                                            snap.version,
-                                            snap.routes.len(),
-                                            snap.clusters.len()
-                                        );
+                                        let (version, generated_at) =
+                                            (snap.version.clone(), snap.generated_at_unix_sec);
+                                        match controllers.stale(&target, snap) {
+                                            Some(newest) => {
+                                                warn!(
+                                                    version = %newest.version,
+                                                    generated_at = newest.generated_at_unix_sec,
+                                                    in_use_generated_at = in_use_at,
+                                                    "every reachable controller is behind the snapshot in use, taking the newest they sent"
+                                                );
+                                                newest
+                                            }
+                                            None => {
+                                                warn!(
+                                                    controller = %target.uri,
+                                                    %version,
+                                                    generated_at,
+                                                    in_use_generated_at = in_use_at,
+                                                    "controller sent an older snapshot than the one in use, failing over"
+                                                );
+                                                if let Some(acks) = &acks {
+                                                    let in_use = store_for_task.version().await;
+                                                    let stale = Rejection::new(
+                                                        "Snapshot",
+                                                        &version,
+                                                        format!("older than snapshot {in_use} in use"),
+                                                    );
+                                                    let nack = discovery_request(&node_id, &in_use, &version, &[stale]);
+                                                    let _ = acks.send(nack).await;
+                                                }
+                                                lost = true;
+                                                break;
+                                            }
+                                        }
                                     } else {
-                                        info!(
-                                            "snapshot update: version={}, routes={}, clusters={}",
-                                            snap.version,
-                                            snap.routes.len(),
-                                            snap.clusters.len()
-                                        );
+                                        snap
+                                    };
+                                    let version = snap.version.clone();
+                                    let (routes, clusters) = (snap.routes.len(), snap.clusters.len());
+                                    let rejected = match store_for_task.apply(snap).await {
+                                        Ok(rejected) => {
+                                            if !got_first {
+                                                got_first = true;
+                                                info!(
+                                                    "received first snapshot: version={}, routes={}, clusters={}",
+                                                    version, routes, clusters
+                                                );
+                                            } else {
+                                                info!(
+                                                    "snapshot update: version={}, routes={}, clusters={}",
+                                                    version, routes, clusters
+                                                );
+                                            }
+                                            controllers.accepted();
+                                            rejected
+                                        }
+                                        Err(rejected) => rejected,
+                                    };
+
+                                    // ACK, or NACK with what was rejected
+                                    if let Some(acks) = &acks {
+                                        let in_use = store_for_task.version().await;
+                                        let ack = discovery_request(&node_id, &in_use, &version, &rejected);
+                                        if acks.send(ack).await.is_err() {
+                                            warn!("failed to acknowledge snapshot: stream closed");
+                                        }
                                     }
                                 }
+                                Ok(Some(DiscoveryResponse { kind: Some(Kind::Delta(delta)) })) => {
+                                    let version = delta.version.clone();
+                                    let (changed, removed) = (
+                                        delta.routes.len() + delta.clusters.len() + delta.server_tls.len(),
+                                        delta.removed_routes.len() + delta.removed_clusters.len() + delta.removed_server_tls.len(),
+                                    );
+                                    let rejected = match store_for_task.apply_delta(delta).await {
+                                        Ok(rejected) => {
+                                            info!(
+                                                "snapshot delta: version={}, changed={}, removed={}",
+                                                version, changed, removed
+                                            );
+                                            controllers.accepted();
+                                            rejected
+                                        }
+                                        Err(rejected) => rejected,
+                                    };
+
+                                    // deltas only come over Stream, so there is always someone to ACK
+                                    if let Some(acks) = &acks {
+                                        let in_use = store_for_task.version().await;
+                                        let ack = discovery_request(&node_id, &in_use, &version, &rejected);
+                                        if acks.send(ack).await.is_err() {
+                                            warn!("failed to acknowledge snapshot delta: stream closed");
+                                        }
+                                    }
+                                }
+                                Ok(Some(DiscoveryResponse { kind: None })) => {
+                                    warn!("empty discovery response, ignoring");
+                                }
                                 Ok(None) => {
                                     warn!("gRPC stream closed by server");
+                                    lost = true;
                                     break; // переподключение
                                 }
                                 Err(status) => {
                                     warn!("gRPC stream error: {}", status);
+                                    lost = true;
                                     break; // переподключение
                                 }
                             }
@@ -290,8 +350,9 @@ impl GrpcManager {
                 //     *r = false;
                 // }
 
-                sleep(Duration::from_millis(backoff_ms)).await;
-                backoff_ms = (backoff_ms.saturating_mul(2)).min(backoff_max);
+                if lost {
+                    controllers.failed(&target);
+                }
                 info!("reconnecting...");
             }
         });
@@ -300,15 +361,6 @@ impl GrpcManager {
             cancel,
             handle,
             certs_watcher_handle,
-            ready,
-            snapshot,
-            route_table,
-            sni,
-            ca_updated,
-            ca_pem,
-            client_pem_updated,
-            client_pem,
-            client_key_pem,
         }
     }
 
@@ -317,13 +369,67 @@ impl GrpcManager {
         let _ = tokio::join!(self.handle, self.certs_watcher_handle);
         info!("gRPC manager stopped");
     }
+}
 
-    pub async fn is_ready(&self) -> bool {
-        *self.ready.read().await
+// Stream with ACK/NACK when the controller implements it, plain Watch otherwise
+async fn open_stream(
+    client: &mut ConfigDiscoveryClient<Channel>,
+    node_id: &str,
+    version_info: &str,
+) -> Result<(Option<mpsc::Sender<DiscoveryRequest>>, ConfigStream), tonic::Status> {
+    let (acks, rx) = mpsc::channel(16);
+    let _ = acks.try_send(discovery_request(node_id, version_info, "", &[]));
+    match client.stream(ReceiverStream::new(rx)).await {
+        Ok(resp) => return Ok((Some(acks), ConfigStream::Stream(resp.into_inner()))),
+        Err(status) if status.code() == tonic::Code::Unimplemented => {
+            info!("controller does not implement Stream, watching without ACK/NACK");
+        }
+        Err(status) => return Err(status),
     }
 
-    pub async fn latest_snapshot(&self) -> Snapshot {
-        self.snapshot.read().await.clone()
+    let resp = client
+        .watch(WatchRequest {
+            node_id: node_id.to_string(),
+        })
+        .await?;
+    Ok((None, ConfigStream::Watch(resp.into_inner())))
+}
+
+// Watch only sends full snapshots; they are read as DiscoveryResponse like Stream's
+enum ConfigStream {
+    Stream(Streaming<DiscoveryResponse>),
+    Watch(Streaming<Snapshot>),
+}
+
+impl ConfigStream {
+    async fn message(&mut self) -> Result<Option<DiscoveryResponse>, tonic::Status> {
+        match self {
+            Self::Stream(stream) => stream.message().await,
+            Self::Watch(stream) => Ok(stream.message().await?.map(|snap| DiscoveryResponse {
+                kind: Some(Kind::Snapshot(snap)),
+            })),
+        }
+    }
+}
+
+fn discovery_request(
+    node_id: &str,
+    version_info: &str,
+    response_version: &str,
+    rejected: &[Rejection],
+) -> DiscoveryRequest {
+    DiscoveryRequest {
+        node_id: node_id.to_string(),
+        version_info: version_info.to_string(),
+        response_version: response_version.to_string(),
+        errors: rejected
+            .iter()
+            .map(|r| ConfigError {
+                kind: r.kind.to_string(),
+                name: r.name.clone(),
+                message: r.message.clone(),
+            })
+            .collect(),
     }
 }
 
@@ -363,3 +469,204 @@ async fn try_load_and_store(
 fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
     a == b
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argon_config::config_discovery_server::{ConfigDiscovery, ConfigDiscoveryServer};
+    use crate::argon_config::{Cluster, Route};
+    use crate::endpoint_registry::EndpointRegistry;
+    use crate::snapshot::{Locality, RouteTable};
+    use std::pin::Pin;
+    use tokio::net::TcpListener;
+    use tokio_stream::{Stream, StreamExt};
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+
+    type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, tonic::Status>> + Send>>;
+
+    // controller sending one snapshot and handing every request it gets to the test
+    struct StubController {
+        stream: Result<(), tonic::Code>,
+        snapshot: Snapshot,
+        watches: mpsc::UnboundedSender<WatchRequest>,
+        requests: mpsc::UnboundedSender<DiscoveryRequest>,
+    }
+
+    #[tonic::async_trait]
+    impl ConfigDiscovery for StubController {
+        type WatchStream = ResponseStream<Snapshot>;
+        type StreamStream = ResponseStream<DiscoveryResponse>;
+
+        async fn watch(
+            &self,
+            request: tonic::Request<WatchRequest>,
+        ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status> {
+            let _ = self.watches.send(request.into_inner());
+            let snapshots = tokio_stream::iter([Ok(self.snapshot.clone())]);
+            Ok(tonic::Response::new(Box::pin(
+                snapshots.chain(tokio_stream::pending()),
+            )))
+        }
+
+        async fn stream(
+            &self,
+            request: tonic::Request<tonic::Streaming<DiscoveryRequest>>,
+        ) -> Result<tonic::Response<Self::StreamStream>, tonic::Status> {
+            if let Err(code) = self.stream {
+                return Err(tonic::Status::new(code, "stub"));
+            }
+            let mut incoming = request.into_inner();
+            let requests = self.requests.clone();
+            tokio::spawn(async move {
+                while let Ok(Some(request)) = incoming.message().await {
+                    let _ = requests.send(request);
+                }
+            });
+            let responses = tokio_stream::iter([Ok(DiscoveryResponse {
+                kind: Some(Kind::Snapshot(self.snapshot.clone())),
+            })]);
+            // kept open: the dataplane ACKs on the same call
+            Ok(tonic::Response::new(Box::pin(
+                responses.chain(tokio_stream::pending()),
+            )))
+        }
+    }
+
+    struct Stub {
+        client: ConfigDiscoveryClient<Channel>,
+        watches: mpsc::UnboundedReceiver<WatchRequest>,
+        requests: mpsc::UnboundedReceiver<DiscoveryRequest>,
+    }
+
+    async fn controller(stream: Result<(), tonic::Code>) -> Stub {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        let (watches_tx, watches) = mpsc::unbounded_channel();
+        let (requests_tx, requests) = mpsc::unbounded_channel();
+        let stub = StubController {
+            stream,
+            snapshot: snapshot(),
+            watches: watches_tx,
+            requests: requests_tx,
+        };
+        tokio::spawn(
+            Server::builder()
+                .add_service(ConfigDiscoveryServer::new(stub))
+                .serve_with_incoming(incoming),
+        );
+        let client = ConfigDiscoveryClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+        Stub {
+            client,
+            watches,
+            requests,
+        }
+    }
+
+    // "empty" has no endpoints, so the snapshot is applied with a rejection
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            version: "v2".into(),
+            clusters: vec![Cluster {
+                name: "empty".into(),
+                lb_policy: "RoundRobin".into(),
+                ..Default::default()
+            }],
+            routes: vec![Route {
+                host: "app.example".into(),
+                path: "/".into(),
+                path_type: "Prefix".into(),
+                cluster: "empty".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    async fn next<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> T {
+        timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("stub controller got nothing")
+            .unwrap()
+    }
+
+    fn snapshot_of(message: Option<DiscoveryResponse>) -> Snapshot {
+        match message {
+            Some(DiscoveryResponse {
+                kind: Some(Kind::Snapshot(snap)),
+            }) => snap,
+            other => panic!("expected a snapshot, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_watch_when_stream_is_unimplemented() {
+        let mut stub = controller(Err(tonic::Code::Unimplemented)).await;
+
+        let (acks, mut stream) = open_stream(&mut stub.client, "node-1", "v1").await.unwrap();
+        assert!(acks.is_none(), "Watch has nobody to ACK to");
+        assert!(matches!(stream, ConfigStream::Watch(_)));
+        assert_eq!(next(&mut stub.watches).await.node_id, "node-1");
+        assert_eq!(snapshot_of(stream.message().await.unwrap()).version, "v2");
+        assert!(stub.requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn other_stream_errors_are_not_retried_as_watch() {
+        let mut stub = controller(Err(tonic::Code::PermissionDenied)).await;
+
+        let err = open_stream(&mut stub.client, "node-1", "v1").await.err();
+        assert_eq!(
+            err.map(|status| status.code()),
+            Some(tonic::Code::PermissionDenied)
+        );
+        assert!(stub.watches.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn stream_reports_the_version_in_use_and_nacks_rejections() {
+        let mut stub = controller(Ok(())).await;
+
+        let (acks, mut stream) = open_stream(&mut stub.client, "node-1", "v1").await.unwrap();
+        let acks = acks.expect("Stream carries ACKs");
+        let hello = next(&mut stub.requests).await;
+        assert_eq!(hello.node_id, "node-1");
+        assert_eq!(hello.version_info, "v1");
+        assert!(hello.response_version.is_empty());
+        assert!(hello.errors.is_empty());
+
+        let snap = snapshot_of(stream.message().await.unwrap());
+        let table = RouteTable::new(
+            &snap,
+            &Locality::default(),
+            &EndpointRegistry::default(),
+            &RouteTable::default(),
+        );
+        assert!(!table.rejected().is_empty());
+        acks.send(discovery_request(
+            "node-1",
+            "v1",
+            &snap.version,
+            table.rejected(),
+        ))
+        .await
+        .unwrap();
+
+        let nack = next(&mut stub.requests).await;
+        assert_eq!(nack.version_info, "v1");
+        assert_eq!(nack.response_version, "v2");
+        assert!(
+            nack.errors.contains(&ConfigError {
+                kind: "Cluster".into(),
+                name: "empty".into(),
+                message: "no endpoints".into(),
+            }),
+            "{:?}",
+            nack.errors
+        );
+        assert_eq!(nack.errors.len(), table.rejected().len());
+    }
+}
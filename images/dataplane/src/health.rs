@@ -0,0 +1,331 @@
+use crate::client_pool::ClientPool;
+use crate::endpoint_registry::{EndpointId, EndpointRegistry};
+use crate::snapshot::OutlierDetectionRule;
+use crate::snapshot::{BackendProtocol, HealthCheckRule, RouteTable};
+use crate::utils;
+use arc_swap::ArcSwap;
+use http::{Method, Request, Uri, Version};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use tokio_util::future::FutureExt;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+// monotonic milliseconds since the first call, for lock-free deadlines
+fn now_ms() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// Health of one endpoint: active probe result plus passive outlier ejection.
+/// Endpoints start healthy so a fresh snapshot never blackholes.
+#[derive(Debug)]
+pub struct HealthState {
+    healthy: AtomicBool,
+    successes: AtomicU32,
+    failures: AtomicU32,
+    consecutive_errors: AtomicU32,
+    ejected_until_ms: AtomicU64,
+    ejections: AtomicU32,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        Self {
+            healthy: AtomicBool::new(true),
+            successes: AtomicU32::new(0),
+            failures: AtomicU32::new(0),
+            consecutive_errors: AtomicU32::new(0),
+            ejected_until_ms: AtomicU64::new(0),
+            ejections: AtomicU32::new(0),
+        }
+    }
+}
+
+impl HealthState {
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed) && !self.is_ejected()
+    }
+
+    pub fn is_ejected(&self) -> bool {
+        now_ms() < self.ejected_until_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.is_ejected() {
+            "ejected"
+        } else if self.healthy.load(Ordering::Relaxed) {
+            "healthy"
+        } else {
+            "unhealthy"
+        }
+    }
+
+    // count a real request outcome; true when the endpoint crossed the error threshold
+    pub fn record_outcome(&self, failed: bool, rule: &OutlierDetectionRule) -> bool {
+        if !failed {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+            return false;
+        }
+        let errors = self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        errors >= rule.consecutive_errors && !self.is_ejected()
+    }
+
+    // eject for base * ejections (capped); the multiplier resets once the endpoint
+    // stayed in rotation for longer than the max ejection time
+    pub fn eject(&self, rule: &OutlierDetectionRule) -> Duration {
+        let now = now_ms();
+        let last_until = self.ejected_until_ms.load(Ordering::Relaxed);
+        if now.saturating_sub(last_until) > rule.max_ejection.as_millis() as u64 {
+            self.ejections.store(0, Ordering::Relaxed);
+        }
+        let ejections = self.ejections.fetch_add(1, Ordering::Relaxed) + 1;
+        let duration = rule
+            .base_ejection
+            .saturating_mul(ejections)
+            .min(rule.max_ejection);
+        self.ejected_until_ms
+            .store(now + duration.as_millis() as u64, Ordering::Relaxed);
+        self.consecutive_errors.store(0, Ordering::Relaxed);
+        duration
+    }
+
+    // returns the new health when the probe result flips it
+    fn record(&self, ok: bool, rule: &HealthCheckRule) -> Option<bool> {
+        if ok {
+            self.failures.store(0, Ordering::Relaxed);
+            let successes = self.successes.fetch_add(1, Ordering::Relaxed) + 1;
+            if successes >= rule.healthy_threshold && !self.healthy.swap(true, Ordering::Relaxed) {
+                return Some(true);
+            }
+        } else {
+            self.successes.store(0, Ordering::Relaxed);
+            let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+            if failures >= rule.unhealthy_threshold && self.healthy.swap(false, Ordering::Relaxed) {
+                return Some(false);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ProbeTarget {
+    rule: HealthCheckRule,
+    uri: Uri,
+    version: Version,
+    insecure_skip_verify: bool,
+}
+
+struct RunningProbe {
+    target: ProbeTarget,
+    cancel: CancellationToken,
+}
+
+pub struct HealthChecker {
+    registry: EndpointRegistry,
+    client_pool: Arc<ArcSwap<ClientPool>>,
+    probes: Mutex<HashMap<EndpointId, RunningProbe>>,
+    cancel: CancellationToken,
+}
+
+impl HealthChecker {
+    pub fn new(client_pool: Arc<ArcSwap<ClientPool>>, cancel: CancellationToken) -> Self {
+        Self {
+            registry: EndpointRegistry::default(),
+            client_pool,
+            probes: Mutex::new(HashMap::new()),
+            cancel,
+        }
+    }
+
+    pub fn registry(&self) -> &EndpointRegistry {
+        &self.registry
+    }
+
+    // start probes for new endpoints, restart changed ones, stop and forget removed ones
+    pub fn sync(&self, route_table: &RouteTable) {
+        let mut wanted: HashMap<EndpointId, ProbeTarget> = HashMap::new();
+        let mut tracked: HashSet<EndpointId> = HashSet::new();
+        for cluster in route_table.clusters() {
+            if cluster.outlier_detection.is_some() {
+                tracked.extend(cluster.endpoints().iter().map(|endpoint| EndpointId {
+                    cluster: cluster.name().to_string(),
+                    address: endpoint.address.clone(),
+                    port: endpoint.port,
+                }));
+            }
+            let Some(rule) = cluster.health_check.as_deref() else {
+                continue;
+            };
+            let is_tls = matches!(
+                cluster.backend_protocol,
+                BackendProtocol::H1Ssl | BackendProtocol::H2Ssl
+            );
+            let version = match cluster.backend_protocol {
+                BackendProtocol::H2 | BackendProtocol::H2Ssl => Version::HTTP_2,
+                _ => Version::HTTP_11,
+            };
+            for endpoint in cluster.endpoints() {
+                let scheme = if is_tls { "https" } else { "http" };
+                let raw = format!(
+                    "{}://{}:{}{}",
+                    scheme, endpoint.address, endpoint.port, rule.path
+                );
+                let Ok(uri) = raw.parse::<Uri>() else {
+                    warn!(cluster = %cluster.name(), uri = %raw, "invalid health check uri");
+                    continue;
+                };
+                let key = EndpointId {
+                    cluster: cluster.name().to_string(),
+                    address: endpoint.address.clone(),
+                    port: endpoint.port,
+                };
+                wanted.insert(
+                    key,
+                    ProbeTarget {
+                        rule: rule.clone(),
+                        uri,
+                        version,
+                        insecure_skip_verify: cluster.backend_tls_insecure_skip_verify,
+                    },
+                );
+            }
+        }
+
+        let mut probes = self.probes.lock().unwrap_or_else(|p| p.into_inner());
+
+        probes.retain(|key, running| {
+            let keep = wanted.get(key) == Some(&running.target);
+            if !keep {
+                running.cancel.cancel();
+            }
+            keep
+        });
+        self.registry.sync(route_table, |id| {
+            wanted.contains_key(id) || tracked.contains(id)
+        });
+
+        for (key, target) in wanted {
+            if probes.contains_key(&key) {
+                continue;
+            }
+            let cancel = self.cancel.child_token();
+            let state = self.registry.state(key.clone()).health;
+            tokio::spawn(probe_loop(
+                key.clone(),
+                target.clone(),
+                state,
+                self.client_pool.clone(),
+                cancel.clone(),
+            ));
+            probes.insert(key, RunningProbe { target, cancel });
+        }
+    }
+}
+
+async fn probe_loop(
+    key: EndpointId,
+    target: ProbeTarget,
+    state: Arc<HealthState>,
+    client_pool: Arc<ArcSwap<ClientPool>>,
+    cancel: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(target.rule.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+
+        let ok = probe(&client_pool, &target).await;
+        match state.record(ok, &target.rule) {
+            Some(true) => info!(
+                cluster = %key.cluster, address = %key.address, port = key.port,
+                "endpoint is healthy"
+            ),
+            Some(false) => warn!(
+                cluster = %key.cluster, address = %key.address, port = key.port,
+                "endpoint is unhealthy"
+            ),
+            None => {}
+        }
+    }
+}
+
+async fn probe(client_pool: &ArcSwap<ClientPool>, target: &ProbeTarget) -> bool {
+    let request = match Request::builder()
+        .method(Method::GET)
+        .uri(target.uri.clone())
+        .version(target.version)
+        .body(utils::empty())
+    {
+        Ok(r) => r,
+        Err(_) => return false,
+    };
+
+    let pool = client_pool.load();
+    let client = if target.insecure_skip_verify {
+        &pool.connector_insecure
+    } else {
+        &pool.connector
+    };
+
+    match client.request(request).timeout(target.rule.timeout).await {
+        Ok(Ok(resp)) => target.rule.accepts(resp.status().as_u16()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> OutlierDetectionRule {
+        OutlierDetectionRule {
+            consecutive_errors: 3,
+            base_ejection: Duration::from_secs(30),
+            max_ejection: Duration::from_secs(75),
+            max_ejection_percent: 10,
+        }
+    }
+
+    #[test]
+    fn outcome_crosses_the_threshold_on_consecutive_errors_only() {
+        let state = HealthState::default();
+        let rule = rule();
+        assert!(!state.record_outcome(true, &rule));
+        assert!(!state.record_outcome(true, &rule));
+        assert!(!state.record_outcome(false, &rule));
+        assert!(!state.record_outcome(true, &rule));
+        assert!(!state.record_outcome(true, &rule));
+        assert!(state.record_outcome(true, &rule));
+    }
+
+    #[test]
+    fn ejection_grows_with_each_ejection_up_to_the_max() {
+        let state = HealthState::default();
+        let rule = rule();
+        assert_eq!(state.eject(&rule), Duration::from_secs(30));
+        assert!(state.is_ejected());
+        assert_eq!(state.status(), "ejected");
+        assert!(!state.is_healthy());
+        assert_eq!(state.eject(&rule), Duration::from_secs(60));
+        assert_eq!(state.eject(&rule), Duration::from_secs(75));
+    }
+
+    #[test]
+    fn ejected_endpoint_is_not_ejected_again() {
+        let state = HealthState::default();
+        let rule = rule();
+        state.eject(&rule);
+        for _ in 0..5 {
+            assert!(!state.record_outcome(true, &rule));
+        }
+    }
+}
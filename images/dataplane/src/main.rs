@@ -1,14 +1,26 @@
+mod access_log;
+mod admin;
 mod certs;
+mod circuit_breaker;
 mod client_pool;
+mod config_store;
+mod consistent_hash;
+mod controllers;
+mod endpoint_registry;
 mod grpc;
+mod health;
+mod metrics;
 mod proxy;
+mod retry_budget;
 mod snapshot;
+mod static_config;
+mod telemetry;
 mod utils;
 
 use arc_swap::ArcSwap;
 use bytes::Bytes;
 use http::StatusCode;
-use http_body_util::{BodyExt, combinators::BoxBody};
+use http_body_util::{BodyExt, Limited, combinators::BoxBody};
 use hyper::body::Incoming;
 use hyper::service::service_fn;
 use hyper::{Method, Request, Response};
@@ -17,7 +29,7 @@ use hyper_util::{rt::TokioExecutor, server::conn::auto};
 use rustls::ServerConfig;
 use rustls::server::ResolvesServerCert;
 use rustls::sign::CertifiedKey;
-use snapshot::RouteTable;
+use snapshot::{Locality, RouteTable, ValidationMode};
 use std::collections::HashMap;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
@@ -31,11 +43,20 @@ use tokio_util::sync::CancellationToken;
 mod argon_config {
     include!("argon.config.rs");
 }
+use crate::access_log::AccessLog;
 use crate::client_pool::ClientPool;
+use crate::config_store::ConfigStore;
+use crate::controllers::Controllers;
 use crate::grpc::GrpcManager;
-use crate::proxy::{FrontendTls, proxy_handler};
+use crate::health::HealthChecker;
+use crate::metrics::Metrics;
+use crate::proxy::{ClientAddr, FrontendTls, RequestIdHeader, TlsServerName, proxy_handler};
+use crate::static_config::StaticConfig;
+use crate::telemetry::Telemetry;
 use argon_config::Snapshot;
 
+const ROUTE_TEST_MAX_BODY: usize = 64 * 1024;
+
 #[derive(Clone, Default)]
 struct AppState {
     client_pool: Arc<ArcSwap<ClientPool>>,
@@ -43,6 +64,10 @@ struct AppState {
     snapshot: Arc<RwLock<Snapshot>>,
     route_table: Arc<RwLock<Arc<RouteTable>>>,
     sni: Arc<ArcSwap<HashMap<String, Arc<CertifiedKey>>>>,
+    metrics: Arc<Metrics>,
+    access_log: Option<Arc<AccessLog>>,
+    telemetry: Option<Arc<Telemetry>>,
+    request_id_header: RequestIdHeader,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -53,7 +78,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let certs_dir = PathBuf::from("/certs");
     let thread_count =
         std::env::var("COUNT_THREADS").unwrap_or_else(|_| num_cpus::get().to_string());
-    let thread_count = thread_count.parse::<usize>().unwrap_or_else(|_| 1);
+    let thread_count = thread_count.parse::<usize>().unwrap_or(1);
     tokio::runtime::Builder::new_multi_thread()
         .worker_threads(thread_count)
         .enable_all()
@@ -64,9 +89,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let http_port = std::env::var("HTTP_PORT").unwrap_or_else(|_| "8080".to_string());
             let https_port = std::env::var("HTTPS_PORT").unwrap_or_else(|_| "8443".to_string());
             let admin_port = std::env::var("ADMIN_PORT").unwrap_or_else(|_| "8181".to_string());
-            let controller_addr = std::env::var("CONTROLLER_ADDR")
-                .unwrap_or_else(|_| "https://127.0.0.1:18000".into());
+            let controller_addrs = Controllers::addrs_from_env();
             let node_id = std::env::var("NODE_ID").unwrap_or_else(|_| "dp-axum".into());
+            let locality = Locality::from_env();
 
             // log
             tracing_subscriber::fmt()
@@ -75,6 +100,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .compact()
                 .init();
 
+            tracing::info!(zone = %locality.zone, region = %locality.region, "dataplane locality");
+
             // start not-ready; snap is empty (Default)
             let state = AppState {
                 ready: Arc::new(RwLock::new(false)),
@@ -84,6 +111,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 client_pool: Arc::new(ArcSwap::new(Arc::new(ClientPool::new_http_pool_connector(
                     thread_count,
                 )))),
+                metrics: Arc::new(Metrics::new()),
+                access_log: AccessLog::from_env().await?,
+                telemetry: Telemetry::from_env()?,
+                request_id_header: RequestIdHeader::from_env(),
             };
 
             // shutdown token
@@ -91,6 +122,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let shutdown_http = shutdown.clone();
             let shutdown_https = shutdown.clone();
             let shutdown_select = shutdown.clone();
+            let health = Arc::new(HealthChecker::new(
+                state.client_pool.clone(),
+                shutdown.child_token(),
+            ));
 
             // Ctrl+C / SIGTERM -> cancel
             tokio::spawn(async move {
@@ -98,16 +133,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 shutdown.cancel();
             });
 
-            // gRPC watcher
-            let manager = GrpcManager::start(
-                controller_addr,
-                node_id,
-                certs_dir,
-                state.ready.clone(),
+            // config source: gRPC watcher, or a local file in standalone mode
+            let store = ConfigStore::new(
                 state.snapshot.clone(),
                 state.route_table.clone(),
                 state.sni.clone(),
+                locality,
+                health,
+                ValidationMode::from_env(),
+                ConfigStore::cache_path_from_env(),
             );
+            let (manager, static_config) = match StaticConfig::path_from_env() {
+                Some(path) => (
+                    None,
+                    Some(StaticConfig::start(path, state.ready.clone(), store)),
+                ),
+                None => {
+                    // become ready from the cached config; the controller replaces it once reachable
+                    if store.load_cached().await {
+                        *state.ready.write().await = true;
+                    }
+                    let manager = GrpcManager::start(
+                        controller_addrs,
+                        node_id,
+                        certs_dir,
+                        state.ready.clone(),
+                        store,
+                    );
+                    (Some(manager), None)
+                }
+            };
 
             // healthcheck
             let addr = SocketAddr::from((Ipv4Addr::new(0, 0, 0, 0), admin_port.parse::<u16>()?));
@@ -124,8 +179,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                             tokio::spawn(async move {
                                 let svc = service_fn(move |request: Request<Incoming>| {
-                                    let ready = state_for_conn.ready.clone();
-                                    async move { echo(request, ready).await }
+                                    let state = state_for_conn.clone();
+                                    async move { echo(request, state).await }
                                 });
 
                                 if let Err(err) = ab.serve_connection(io_admin, svc).await {
@@ -147,7 +202,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let dummy_cert = certs::make_dummy_cert()?;
             let server_cert_resolver: Arc<dyn ResolvesServerCert> =
                 Arc::new(certs::DynResolver::new(dummy_cert, state.sni.clone()));
-            let mut server_config = ServerConfig::builder()
+            let server_config = ServerConfig::builder()
                 .with_no_client_auth()
                 .with_cert_resolver(server_cert_resolver);
 
@@ -171,7 +226,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
 
             // shutdown gRPC server after http server
-            manager.shutdown().await;
+            if let Some(manager) = manager {
+                manager.shutdown().await;
+            }
+            if let Some(static_config) = static_config {
+                static_config.shutdown().await;
+            }
+
+            // export spans of the drained requests
+            if let Some(telemetry) = state.telemetry.clone() {
+                let _ = tokio::task::spawn_blocking(move || telemetry.shutdown()).await;
+            }
 
             Ok(())
         })
@@ -198,13 +263,14 @@ async fn run_http(
             }
             res = listener.accept() => {
                 match res {
-                    Ok((stream, _)) => {
+                    Ok((stream, peer)) => {
                         let io = TokioIo::new(stream);
                         let state_cloned = state.clone();
                         let builder = builder.clone();
                         conns.spawn(async move {
                             let svc = service_fn(move |mut req: Request<Incoming>| {
                                 req.extensions_mut().insert(FrontendTls(false));
+                                req.extensions_mut().insert(ClientAddr(peer));
                                 proxy_handler(req, state_cloned.clone())
                             });
                             if let Err(err) = builder.serve_connection_with_upgrades(io, svc).await {
@@ -245,14 +311,23 @@ async fn run_https(
             }
             res = listener.accept() => {
                 match res {
-                    Ok((stream, _)) => {
+                    Ok((stream, peer)) => {
                         let tls_acceptor = tls_acceptor.clone();
                         let state_cloned = state.clone();
                         conns.spawn(async move {
                             let tls_stream = match tls_acceptor.accept(stream).await {
                                 Ok(s) => s,
-                                Err(err) => { tracing::error!("TLS accept error: {err}"); return; }
+                                Err(err) => {
+                                    state_cloned.metrics.tls_handshake_failed();
+                                    tracing::error!("TLS accept error: {err}");
+                                    return;
+                                }
                             };
+                            let sni = tls_stream
+                                .get_ref()
+                                .1
+                                .server_name()
+                                .map(|name| TlsServerName(name.to_string()));
                             let io = TokioIo::new(tls_stream);
                             let mut builder = auto::Builder::new(TokioExecutor::new());
                             builder.http1().title_case_headers(true);
@@ -263,6 +338,10 @@ async fn run_https(
 
                             let svc = service_fn(move |mut req: Request<Incoming>| {
                                 req.extensions_mut().insert(FrontendTls(true));
+                                req.extensions_mut().insert(ClientAddr(peer));
+                                if let Some(sni) = &sni {
+                                    req.extensions_mut().insert(sni.clone());
+                                }
                                 proxy_handler(req, state_cloned.clone())
                             });
                             if let Err(err) = builder.serve_connection_with_upgrades(io, svc).await {
@@ -283,9 +362,9 @@ async fn run_https(
     Ok(())
 }
 
-pub async fn echo(
+async fn echo(
     req: Request<Incoming>,
-    ready: Arc<RwLock<bool>>,
+    state: AppState,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     let method = req.method();
     let path = req.uri().path();
@@ -294,7 +373,7 @@ pub async fn echo(
         (&Method::POST, "/echo") => Ok(Response::new(req.into_body().boxed())),
         (&Method::GET, "/healthz") => Ok(Response::new(utils::full("Ok"))),
         (&Method::GET, "/readyz") => {
-            if *ready.read().await {
+            if *state.ready.read().await {
                 let mut ok = Response::new(utils::empty());
                 *ok.status_mut() = StatusCode::OK;
                 Ok(ok)
@@ -304,6 +383,42 @@ pub async fn echo(
                 Ok(service_unavailable)
             }
         }
+        (&Method::GET, "/metrics") => {
+            let route_table = state.route_table.read().await.clone();
+            let body = state.metrics.render(&route_table);
+            let mut resp = Response::new(utils::full(body));
+            resp.headers_mut().insert(
+                http::header::CONTENT_TYPE,
+                http::HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+            );
+            Ok(resp)
+        }
+        (&Method::GET, "/config_dump") => {
+            let body = admin::config_dump(&*state.snapshot.read().await);
+            Ok(json_response(body))
+        }
+        (&Method::GET, "/routes") => {
+            let route_table = state.route_table.read().await.clone();
+            Ok(json_response(admin::routes(&route_table)))
+        }
+        (&Method::GET, "/clusters") => {
+            let route_table = state.route_table.read().await.clone();
+            Ok(json_response(admin::clusters(&route_table)))
+        }
+        (&Method::POST, "/route_test") => {
+            let body = match Limited::new(req.into_body(), ROUTE_TEST_MAX_BODY)
+                .collect()
+                .await
+            {
+                Ok(body) => body.to_bytes(),
+                Err(err) => return Ok(bad_request(format!("failed to read body: {err}"))),
+            };
+            let route_table = state.route_table.read().await.clone();
+            match admin::route_test(&route_table, &body) {
+                Ok(body) => Ok(json_response(body)),
+                Err(err) => Ok(bad_request(err)),
+            }
+        }
         _ => {
             let mut not_found = Response::new(utils::empty());
             *not_found.status_mut() = StatusCode::NOT_FOUND;
@@ -311,3 +426,18 @@ pub async fn echo(
         }
     }
 }
+
+fn bad_request(message: String) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let mut resp = Response::new(utils::full(message));
+    *resp.status_mut() = StatusCode::BAD_REQUEST;
+    resp
+}
+
+fn json_response(body: String) -> Response<BoxBody<Bytes, hyper::Error>> {
+    let mut resp = Response::new(utils::full(body));
+    resp.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    resp
+}
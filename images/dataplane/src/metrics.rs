@@ -0,0 +1,355 @@
+use crate::snapshot::RouteTable;
+use http::StatusCode;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Labels of the route a request matched; empty until routing got that far.
+#[derive(Clone, Debug, Default)]
+pub struct RouteLabels {
+    pub host: String,
+    pub route: String,
+    pub cluster: String,
+}
+
+/// Prometheus metrics of the dataplane, served on the admin listener at /metrics.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    upstream_errors: IntCounterVec,
+    retries: IntCounterVec,
+    hedges: IntCounterVec,
+    retry_budget_exhausted: IntCounterVec,
+    tls_handshake_failures: IntCounter,
+    upstream_active: IntGaugeVec,
+    snapshot_info: IntGaugeVec,
+    snapshot_updated: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("argon".to_string()), None).expect("valid metrics prefix");
+
+        let requests = IntCounterVec::new(
+            Opts::new(
+                "requests_total",
+                "Requests by route, cluster and status class",
+            ),
+            &["host", "route", "cluster", "status_class"],
+        )
+        .expect("valid metric");
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "request_duration_seconds",
+                "Request latency from routing to response headers",
+            ),
+            &["host", "route", "cluster"],
+        )
+        .expect("valid metric");
+        let upstream_errors = IntCounterVec::new(
+            Opts::new(
+                "upstream_errors_total",
+                "Failed upstream attempts by kind: connect, reset, timeout",
+            ),
+            &["cluster", "kind"],
+        )
+        .expect("valid metric");
+        let retries = IntCounterVec::new(
+            Opts::new("upstream_retries_total", "Upstream retries"),
+            &["cluster"],
+        )
+        .expect("valid metric");
+        let hedges = IntCounterVec::new(
+            Opts::new("upstream_hedges_total", "Hedged upstream attempts"),
+            &["cluster"],
+        )
+        .expect("valid metric");
+        let retry_budget_exhausted = IntCounterVec::new(
+            Opts::new(
+                "retry_budget_exhausted_total",
+                "Retries and hedges skipped because the retry budget was exhausted",
+            ),
+            &["cluster"],
+        )
+        .expect("valid metric");
+        let tls_handshake_failures = IntCounter::new(
+            "tls_handshake_failures_total",
+            "Failed TLS handshakes on the HTTPS listener",
+        )
+        .expect("valid metric");
+        let upstream_active = IntGaugeVec::new(
+            Opts::new(
+                "upstream_active_requests",
+                "Requests in flight per endpoint",
+            ),
+            &["cluster", "endpoint"],
+        )
+        .expect("valid metric");
+        let snapshot_info = IntGaugeVec::new(
+            Opts::new("snapshot_info", "Version of the applied snapshot"),
+            &["version"],
+        )
+        .expect("valid metric");
+        let snapshot_updated = IntGauge::new(
+            "snapshot_last_update_timestamp_seconds",
+            "Unix time the current snapshot was applied",
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(request_duration.clone()),
+            Box::new(upstream_errors.clone()),
+            Box::new(retries.clone()),
+            Box::new(hedges.clone()),
+            Box::new(retry_budget_exhausted.clone()),
+            Box::new(tls_handshake_failures.clone()),
+            Box::new(upstream_active.clone()),
+            Box::new(snapshot_info.clone()),
+            Box::new(snapshot_updated.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric registered once");
+        }
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            upstream_errors,
+            retries,
+            hedges,
+            retry_budget_exhausted,
+            tls_handshake_failures,
+            upstream_active,
+            snapshot_info,
+            snapshot_updated,
+        }
+    }
+
+    pub fn observe_request(&self, labels: &RouteLabels, status: StatusCode, elapsed: Duration) {
+        let route = [
+            labels.host.as_str(),
+            labels.route.as_str(),
+            labels.cluster.as_str(),
+        ];
+        self.requests
+            .with_label_values(&[route[0], route[1], route[2], status_class(status)])
+            .inc();
+        self.request_duration
+            .with_label_values(&route)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn upstream_error(&self, cluster: &str, kind: &str) {
+        self.upstream_errors
+            .with_label_values(&[cluster, kind])
+            .inc();
+    }
+
+    pub fn retry(&self, cluster: &str) {
+        self.retries.with_label_values(&[cluster]).inc();
+    }
+
+    pub fn hedge(&self, cluster: &str) {
+        self.hedges.with_label_values(&[cluster]).inc();
+    }
+
+    pub fn retry_budget_exhausted(&self, cluster: &str) {
+        self.retry_budget_exhausted
+            .with_label_values(&[cluster])
+            .inc();
+    }
+
+    pub fn tls_handshake_failed(&self) {
+        self.tls_handshake_failures.inc();
+    }
+
+    // Prometheus text format; gauges read from the route table are refreshed on each scrape
+    pub fn render(&self, route_table: &RouteTable) -> String {
+        self.upstream_active.reset();
+        for cluster in route_table.clusters() {
+            for (endpoint, active) in cluster.active_requests() {
+                let addr = format!("{}:{}", endpoint.address, endpoint.port);
+                self.upstream_active
+                    .with_label_values(&[cluster.name(), addr.as_str()])
+                    .set(active as i64);
+            }
+        }
+
+        self.snapshot_info.reset();
+        if let Some(updated_at) = route_table.updated_at() {
+            self.snapshot_info
+                .with_label_values(&[route_table.version()])
+                .set(1);
+            let unix = updated_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            self.snapshot_updated.set(unix as i64);
+        }
+
+        let mut buf = Vec::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            tracing::error!(%err, "failed to encode metrics");
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argon_config::{Cluster, Endpoint, Snapshot};
+    use crate::endpoint_registry::EndpointRegistry;
+    use crate::snapshot::Locality;
+
+    fn route_table() -> RouteTable {
+        let snapshot = Snapshot {
+            version: "v7".into(),
+            clusters: vec![Cluster {
+                name: "app".into(),
+                lb_policy: "LeastConn".into(),
+                endpoints: vec![Endpoint {
+                    address: "10.0.0.1".into(),
+                    port: 8080,
+                    weight: 1,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        RouteTable::new(
+            &snapshot,
+            &Locality::default(),
+            &EndpointRegistry::default(),
+            &RouteTable::default(),
+        )
+    }
+
+    // value of the sample with exactly this name and label set
+    fn sample(text: &str, series: &str) -> Option<f64> {
+        text.lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+            .and_then(|value| value.parse().ok())
+    }
+
+    #[test]
+    fn scrape_exposes_argon_metrics_with_their_labels() {
+        let metrics = Metrics::new();
+        let labels = RouteLabels {
+            host: "app.example".into(),
+            route: "/".into(),
+            cluster: "app".into(),
+        };
+        metrics.observe_request(&labels, StatusCode::OK, Duration::from_millis(3));
+        metrics.observe_request(&labels, StatusCode::NO_CONTENT, Duration::from_millis(30));
+        metrics.observe_request(&labels, StatusCode::BAD_GATEWAY, Duration::from_secs(2));
+        metrics.upstream_error("app", "timeout");
+        metrics.retry("app");
+        metrics.hedge("app");
+        metrics.retry_budget_exhausted("app");
+        metrics.retry_budget_exhausted("app");
+        metrics.tls_handshake_failed();
+
+        let table = route_table();
+        let in_flight = table.get_endpoint("app", None).unwrap();
+        in_flight
+            .counter
+            .as_ref()
+            .unwrap()
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let text = metrics.render(&table);
+
+        let route = r#"cluster="app",host="app.example",route="/""#;
+        for (series, value) in [
+            (
+                format!("argon_requests_total{{{route},status_class=\"2xx\"}}"),
+                2.0,
+            ),
+            (
+                format!("argon_requests_total{{{route},status_class=\"5xx\"}}"),
+                1.0,
+            ),
+            (
+                format!("argon_request_duration_seconds_count{{{route}}}"),
+                3.0,
+            ),
+            (
+                format!("argon_request_duration_seconds_bucket{{{route},le=\"0.005\"}}"),
+                1.0,
+            ),
+            (
+                format!("argon_request_duration_seconds_bucket{{{route},le=\"0.05\"}}"),
+                2.0,
+            ),
+            (
+                format!("argon_request_duration_seconds_bucket{{{route},le=\"+Inf\"}}"),
+                3.0,
+            ),
+            (
+                r#"argon_upstream_errors_total{cluster="app",kind="timeout"}"#.into(),
+                1.0,
+            ),
+            (r#"argon_upstream_retries_total{cluster="app"}"#.into(), 1.0),
+            (r#"argon_upstream_hedges_total{cluster="app"}"#.into(), 1.0),
+            (
+                r#"argon_retry_budget_exhausted_total{cluster="app"}"#.into(),
+                2.0,
+            ),
+            ("argon_tls_handshake_failures_total".into(), 1.0),
+            (
+                r#"argon_upstream_active_requests{cluster="app",endpoint="10.0.0.1:8080"}"#.into(),
+                1.0,
+            ),
+            (r#"argon_snapshot_info{version="v7"}"#.into(), 1.0),
+        ] {
+            assert_eq!(sample(&text, &series), Some(value), "{series} in\n{text}");
+        }
+        let sum = sample(
+            &text,
+            &format!("argon_request_duration_seconds_sum{{{route}}}"),
+        );
+        assert!((sum.unwrap() - 2.033).abs() < 1e-9);
+        assert!(sample(&text, "argon_snapshot_last_update_timestamp_seconds").unwrap() > 0.0);
+        assert!(text.contains("# TYPE argon_request_duration_seconds histogram"));
+        assert!(text.contains("# TYPE argon_requests_total counter"));
+        assert!(text.contains("# TYPE argon_upstream_active_requests gauge"));
+    }
+
+    #[test]
+    fn scrape_refreshes_gauges_from_the_route_table() {
+        let metrics = Metrics::new();
+        let text = metrics.render(&route_table());
+        assert_eq!(
+            sample(&text, r#"argon_snapshot_info{version="v7"}"#),
+            Some(1.0)
+        );
+
+        // an empty table drops the previous version and endpoints instead of keeping them
+        let text = metrics.render(&RouteTable::default());
+        assert!(!text.contains("argon_snapshot_info{"));
+        assert!(!text.contains("argon_upstream_active_requests{"));
+    }
+}
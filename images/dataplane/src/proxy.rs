@@ -1,9 +1,19 @@
 use crate::AppState;
+use crate::access_log::{AccessLogEntry, LoggedBody};
+use crate::argon_config::Endpoint;
+use crate::circuit_breaker::RetryPermit;
+use crate::consistent_hash::hash_bytes;
+use crate::health::HealthState;
+use crate::metrics::RouteLabels;
+use crate::retry_budget::BudgetPermit;
 use crate::snapshot::{
-    AuthConfigDex, BackendProtocol, ClusterRule, HeaderRewriteMode, HeaderRewriteRule, RouteRule,
-    RouteTable, SelectedEndpoint,
+    AuthConfigDex, BackendProtocol, ClusterRule, EndpointStats, HashPolicyRule, HeaderRewriteMode,
+    HeaderRewriteRule, RouteAccessLog, RouteRule, RouteTable, SelectedEndpoint,
+    SessionAffinityRule,
 };
-use bytes::Bytes;
+use crate::telemetry;
+use crate::utils::{self, PrefixedBody};
+use bytes::{Bytes, BytesMut};
 use http::uri::{Authority, PathAndQuery};
 use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, Version, header};
 use http_body_util::Full;
@@ -13,24 +23,111 @@ use hyper::{Request, Response};
 use hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{Context, KeyValue};
+use std::collections::VecDeque;
 use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::time::error::Elapsed;
 use tokio_util::future::FutureExt;
+use tracing::Instrument;
+use uuid::Uuid;
 
 #[derive(Clone, Copy, Debug)]
 pub struct FrontendTls(pub bool);
 
+#[derive(Clone, Copy, Debug)]
+pub struct ClientAddr(pub SocketAddr);
+
+/// SNI the client sent in the TLS handshake.
+#[derive(Clone, Debug)]
+pub struct TlsServerName(pub String);
+
+/// Header carrying the request id: REQUEST_ID_HEADER, default x-request-id.
+#[derive(Clone, Debug)]
+pub struct RequestIdHeader(pub HeaderName);
+
+impl Default for RequestIdHeader {
+    fn default() -> Self {
+        RequestIdHeader(HeaderName::from_static("x-request-id"))
+    }
+}
+
+impl RequestIdHeader {
+    pub fn from_env() -> Self {
+        let Ok(name) = std::env::var("REQUEST_ID_HEADER") else {
+            return Self::default();
+        };
+        match HeaderName::from_bytes(name.trim().as_bytes()) {
+            Ok(name) => RequestIdHeader(name),
+            Err(err) => {
+                tracing::warn!(header = %name, %err, "invalid REQUEST_ID_HEADER, using x-request-id");
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Id of the request: the client's when it sent a usable one, otherwise a new UUID.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+tokio::task_local! {
+    // request id of the request being handled, for error bodies built by `text()`
+    static REQUEST_ID: String;
+}
+
+const MAX_REQUEST_ID_LEN: usize = 200;
+
+// keep or generate the request id; it is forwarded upstream with the request headers
+fn ensure_request_id(req: &mut Request<Incoming>, header_name: &HeaderName) -> String {
+    let from_client = req
+        .headers()
+        .get(header_name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string);
+    let id = match from_client {
+        Some(id) => id,
+        None => {
+            let id = Uuid::new_v4().to_string();
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                req.headers_mut().insert(header_name.clone(), value);
+            }
+            id
+        }
+    };
+    req.extensions_mut().insert(RequestId(id.clone()));
+    id
+}
+
+/// What routing learned about a request, for metrics and the access log.
+#[derive(Debug, Default)]
+struct RequestInfo {
+    labels: RouteLabels,
+    access_log: RouteAccessLog,
+    upstream: Option<String>,
+    retries: u32,
+    /// server span of the request when tracing is enabled
+    trace: Option<Context>,
+}
+
 type ProxyResponse = Response<BoxBody<Bytes, hyper::Error>>;
-type ProxyResult<T> = Result<T, ProxyResponse>;
+// boxed: a response is large for the error side of a Result
+type ProxyResult<T> = Result<T, Box<ProxyResponse>>;
 
 // hop-by-hop headers that cannot be proxied (RFC 7230)
 
 static PROXY_CONNECTION: HeaderName = HeaderName::from_static("proxy-connection");
 static KEEP_ALIVE: HeaderName = HeaderName::from_static("keep-alive");
 
+static X_ARGON_CIRCUIT_BREAKER: HeaderName = HeaderName::from_static("x-argon-circuit-breaker");
+
 static HOP_HEADERS_REF: &[&HeaderName] = &[
     &header::CONNECTION,
     &header::PROXY_AUTHENTICATE,
@@ -46,68 +143,160 @@ static HOP_HEADERS_REF: &[&HeaderName] = &[
 pub async fn proxy_handler(
     mut req: Request<hyper::body::Incoming>,
     state: AppState,
+) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
+    let started = Instant::now();
+    let id_header = &state.request_id_header.0;
+    let request_id = ensure_request_id(&mut req, id_header);
+    let entry = state
+        .access_log
+        .as_ref()
+        .map(|_| AccessLogEntry::capture(&req));
+    let mut info = RequestInfo {
+        trace: state.telemetry.as_ref().map(|t| t.start_server(&req)),
+        ..Default::default()
+    };
+    let method = req.method().clone();
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut resp = REQUEST_ID
+        .scope(
+            request_id.clone(),
+            route_request(req, &state, &mut info).instrument(span),
+        )
+        .await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        resp.headers_mut().insert(id_header.clone(), value);
+    }
+    state
+        .metrics
+        .observe_request(&info.labels, resp.status(), started.elapsed());
+    if let Some(cx) = &info.trace {
+        if !info.labels.route.is_empty() {
+            let span = cx.span();
+            span.update_name(format!("{} {}", method, info.labels.route));
+            span.set_attribute(KeyValue::new("http.route", info.labels.route.clone()));
+        }
+        telemetry::end_span(cx, Some(resp.status()), None);
+    }
+
+    let (Some(log), Some(mut entry)) = (state.access_log.as_ref(), entry) else {
+        return Ok(resp);
+    };
+    if !log.sampled(info.access_log) {
+        return Ok(resp);
+    }
+    entry.status = resp.status().as_u16();
+    entry.route = info.labels.route;
+    entry.cluster = info.labels.cluster;
+    entry.upstream = info.upstream;
+    entry.retries = info.retries;
+    let log = log.clone();
+    Ok(resp.map(|body| LoggedBody::new(body, log, entry, started).boxed()))
+}
+
+async fn route_request(
+    mut req: Request<hyper::body::Incoming>,
+    state: &AppState,
+    info: &mut RequestInfo,
 ) -> Result<Response<BoxBody<Bytes, hyper::Error>>, hyper::Error> {
     let frontend_is_tls = req
         .extensions()
         .get::<FrontendTls>()
         .map(|f| f.0)
         .unwrap_or(false);
-    let route_table_guard = state.route_table.read().await;
-    let route_table = &**route_table_guard;
+    // cloned out of the lock: a request waiting on the circuit breaker must not block snapshot updates
+    let route_table = state.route_table.read().await.clone();
+    let route_table = route_table.as_ref();
 
     let host = match extract_host(&req) {
         Ok(h) => h,
-        Err(resp) => return Ok(resp),
+        Err(resp) => return Ok(*resp),
     };
 
     let path = req.uri().path();
 
     let rule = match resolve_route(route_table, &host, path) {
         Ok(r) => r,
-        Err(resp) => return Ok(resp),
+        Err(resp) => return Ok(*resp),
     };
+    info.labels.host = rule.host().to_string();
+    info.labels.route = rule.path().to_string();
+    info.labels.cluster = rule.cluster.clone();
+    info.access_log = rule.access_log();
 
     let cluster_rules = match resolve_cluster(route_table, rule) {
         Ok(r) => r,
-        Err(resp) => return Ok(resp),
+        Err(resp) => return Ok(*resp),
     };
     let header_rewrites = cluster_rules.request_headers.clone();
+    let hash = cluster_rules
+        .hash_policy
+        .as_ref()
+        .and_then(|policy| request_hash(&req, policy));
+
+    // fail fast when the cluster is over its concurrency limits
+    let _request_permit = match &cluster_rules.circuit_breaker {
+        Some(breaker) => match breaker.admit().await {
+            Ok(permit) => Some(permit),
+            Err(tripped) => {
+                tracing::warn!(cluster = %cluster_rules.name(), breaker = tripped.as_str(), "circuit breaker open");
+                let mut resp = text(StatusCode::SERVICE_UNAVAILABLE, "upstream overloaded");
+                resp.headers_mut().insert(
+                    X_ARGON_CIRCUIT_BREAKER.clone(),
+                    HeaderValue::from_static(tripped.as_str()),
+                );
+                return Ok(resp);
+            }
+        },
+        None => None,
+    };
 
-    let selection = match resolve_endpoint(route_table, rule) {
-        Ok(sel) => sel,
-        Err(resp) => return Ok(resp),
+    // session affinity: honor the pinned endpoint while it is still in the snapshot
+    let pinned = cluster_rules
+        .session_affinity
+        .as_deref()
+        .and_then(|affinity| {
+            let cookie = cookie_value(req.headers(), &affinity.cookie_name)?;
+            route_table.get_pinned_endpoint(rule.cluster.as_str(), cookie)
+        });
+    let is_pinned = pinned.is_some();
+
+    let selection = match pinned {
+        Some(sel) => sel,
+        None => match resolve_endpoint(route_table, rule, hash) {
+            Ok(sel) => sel,
+            Err(resp) => return Ok(*resp),
+        },
     };
 
-    let SelectedEndpoint {
-        endpoint: ep,
-        counter,
-    } = selection;
-    let _active_counter = ActiveConnGuard::new(counter);
+    let target = UpstreamTarget::new(selection);
 
-    drop(route_table_guard);
+    let _budget_request = cluster_rules
+        .retry_budget
+        .as_ref()
+        .map(|budget| budget.start_request());
 
     // subrequest if DEX AUTH enabled
-    if let Some(auth) = cluster_rules.auth.as_deref() {
-        if let Err(resp) = perform_auth_if_needed(
+    if let Some(auth) = cluster_rules.auth.as_deref()
+        && let Err(resp) = perform_auth_if_needed(
             &mut req,
             auth,
-            &state,
+            state,
             &host,
             frontend_is_tls,
             cluster_rules.backend_tls_insecure_skip_verify,
+            info.trace.as_ref(),
         )
         .await
-        {
-            return Ok(resp);
-        }
+    {
+        return Ok(*resp);
     }
 
     // handle req (prepare headers/authority for selected endpoint)
     handle_req_upstream(
         &mut req,
         &host,
-        &ep.address,
-        ep.port as u16,
+        &target.endpoint.address,
+        target.endpoint.port as u16,
         cluster_rules.backend_protocol.clone(),
         frontend_is_tls,
     );
@@ -116,28 +305,29 @@ pub async fn proxy_handler(
         apply_header_rewrites(req.headers_mut(), header_rewrites.as_ref());
     }
 
-    let request_snapshot = RequestSnapshot::capture(&req);
-    let initial_request = req.map(|b| b.boxed());
-
-    // release read lock before IO
-    let addr = format!("{}:{}", ep.address, ep.port);
-    let pool = state.client_pool.load();
-    let timeout = Duration::from_millis(cluster_rules.timeout_ms as u64);
-    let client = if cluster_rules.backend_tls_insecure_skip_verify {
-        &pool.connector_insecure
+    let mut request_snapshot = RequestSnapshot::capture(&req);
+    let initial_request = if request_snapshot.body_is_reusable
+        || cluster_rules.retries <= 1
+        || cluster_rules.retry_policy.max_body_bytes == 0
+    {
+        req.map(|b| b.boxed())
     } else {
-        &pool.connector
+        let limit = cluster_rules.retry_policy.max_body_bytes;
+        match buffer_request_body(req, limit, &mut request_snapshot).await {
+            Ok(req) => req,
+            Err(resp) => return Ok(*resp),
+        }
     };
+    let initial_endpoint = target.endpoint.clone();
 
-    // Retries: at least 1 attempt
-    let retries = cluster_rules.retries.max(1) as usize;
-    let mut resp = match forward_to_upstream(
+    let (mut resp, served) = match forward_to_upstream(
         initial_request,
-        client,
-        timeout,
-        retries,
+        state,
+        info,
+        hash,
+        &cluster_rules,
         &request_snapshot,
-        &addr,
+        target,
     )
     .await
     {
@@ -147,6 +337,14 @@ pub async fn proxy_handler(
 
     remove_hop_headers(resp.headers_mut());
 
+    // re-pin when a retry moved the request away from the pinned endpoint
+    if (!is_pinned || served != initial_endpoint)
+        && let Some(affinity) = cluster_rules.session_affinity.as_deref()
+    {
+        let value = cluster_rules.affinity_cookie(&served);
+        set_affinity_cookie(resp.headers_mut(), affinity, &value, frontend_is_tls);
+    }
+
     Ok(resp.map(|b| b.boxed()))
 }
 
@@ -157,6 +355,7 @@ async fn perform_auth_if_needed(
     host: &str,
     frontend_is_tls: bool,
     backend_tls_insecure_skip_verify: bool,
+    trace: Option<&Context>,
 ) -> ProxyResult<()> {
     let path = req.uri().path();
     if auth.skip_paths.iter().any(|p| path.starts_with(p)) {
@@ -167,17 +366,16 @@ async fn perform_auth_if_needed(
         if let Some(cookies_val) = req.headers().get(header::COOKIE) {
             if let Ok(cookies) = cookies_val.to_str() {
                 let needle = format!("{}=", cookie_name);
-                if !cookies.contains(&needle) {
-                    if let Some(signin) = &auth.signin {
-                        let location =
-                            build_signin_location(signin, host, req.uri(), frontend_is_tls);
-                        return Err(redirect(StatusCode::FOUND, &location));
-                    }
+                if !cookies.contains(&needle)
+                    && let Some(signin) = &auth.signin
+                {
+                    let location = build_signin_location(signin, host, req.uri(), frontend_is_tls);
+                    return Err(Box::new(redirect(StatusCode::FOUND, &location)));
                 }
             }
         } else if let Some(signin) = &auth.signin {
             let location = build_signin_location(signin, host, req.uri(), frontend_is_tls);
-            return Err(redirect(StatusCode::FOUND, &location));
+            return Err(Box::new(redirect(StatusCode::FOUND, &location)));
         }
     }
 
@@ -192,7 +390,7 @@ async fn perform_auth_if_needed(
         Some(url) => url,
         None => {
             let err_text = format!("Authorization URL not found for: {:?}", req.uri());
-            return Err(text(StatusCode::BAD_GATEWAY, err_text));
+            return Err(Box::new(text(StatusCode::BAD_GATEWAY, err_text)));
         }
     };
 
@@ -200,39 +398,58 @@ async fn perform_auth_if_needed(
         Ok(u) => u,
         Err(_) => {
             let err_text = format!("Invalid authorization URL: {}", auth_url);
-            return Err(text(StatusCode::BAD_GATEWAY, err_text));
+            return Err(Box::new(text(StatusCode::BAD_GATEWAY, err_text)));
         }
     };
 
-    let auth_req = build_auth_request(req, uri, host, frontend_is_tls);
+    let mut auth_req =
+        build_auth_request(req, uri, host, frontend_is_tls, &state.request_id_header.0);
+    let span = trace
+        .zip(state.telemetry.as_deref())
+        .map(|(parent, telemetry)| {
+            let cx = telemetry.start_client(
+                parent,
+                "auth".to_string(),
+                auth_req.method(),
+                auth_req.uri(),
+            );
+            telemetry.inject(&cx, auth_req.headers_mut());
+            cx
+        });
     let auth_resp = match client.request(auth_req).await {
         Ok(r) => r,
         Err(e) => {
             let err_text = format!("Authorization subrequest failed: {:?} : {:?}", auth_url, e);
-            return Err(text(StatusCode::BAD_GATEWAY, err_text));
+            if let Some(cx) = &span {
+                telemetry::end_span(cx, None, Some(&err_text));
+            }
+            return Err(Box::new(text(StatusCode::BAD_GATEWAY, err_text)));
         }
     };
+    if let Some(cx) = &span {
+        telemetry::end_span(cx, Some(auth_resp.status()), None);
+    }
 
     let status = auth_resp.status();
     if status.is_success() {
         // Copy configured headers from auth response into the upstream request
         for name in auth.response_headers.iter() {
-            if let Ok(hn) = HeaderName::from_bytes(name.as_bytes()) {
-                if let Some(val) = auth_resp.headers().get(&hn) {
-                    req.headers_mut().insert(hn, val.clone());
-                }
+            if let Ok(hn) = HeaderName::from_bytes(name.as_bytes())
+                && let Some(val) = auth_resp.headers().get(&hn)
+            {
+                req.headers_mut().insert(hn, val.clone());
             }
         }
         Ok(())
     } else if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
         if let Some(signin) = &auth.signin {
             let location = build_signin_location(signin, host, req.uri(), frontend_is_tls);
-            return Err(redirect(StatusCode::FOUND, &location));
+            return Err(Box::new(redirect(StatusCode::FOUND, &location)));
         }
-        Err(text(StatusCode::UNAUTHORIZED, "unauthorized"))
+        Err(Box::new(text(StatusCode::UNAUTHORIZED, "unauthorized")))
     } else {
         let err_text = format!("authorization service returned {}", status);
-        Err(text(StatusCode::BAD_GATEWAY, err_text))
+        Err(Box::new(text(StatusCode::BAD_GATEWAY, err_text)))
     }
 }
 
@@ -241,8 +458,9 @@ fn build_auth_request(
     auth_uri: Uri,
     original_host: &str,
     frontend_is_tls: bool,
+    request_id_header: &HeaderName,
 ) -> Request<BoxBody<Bytes, hyper::Error>> {
-    // Copy Cookie and Authorization from original request if present.
+    // Copy Cookie, Authorization and the request id from original request if present.
     let mut builder = http::Request::builder().method("GET").uri(auth_uri);
 
     let headers = builder.headers_mut().expect("headers_mut");
@@ -250,6 +468,9 @@ fn build_auth_request(
     if let Some(v) = original_req.headers().get(header::COOKIE) {
         headers.insert(header::COOKIE, v.clone());
     }
+    if let Some(v) = original_req.headers().get(request_id_header) {
+        headers.insert(request_id_header.clone(), v.clone());
+    }
     if let Some(v) = original_req.headers().get(header::AUTHORIZATION) {
         headers.insert(header::AUTHORIZATION, v.clone());
     }
@@ -354,13 +575,7 @@ fn handle_req_upstream(
 
     remove_hop_headers(req.headers_mut());
 
-    let default_port = if is_tls { 443 } else { 80 };
-    let auth = if upstream_port == default_port {
-        Authority::from_str(upstream_host)
-    } else {
-        Authority::from_str(&format!("{upstream_host}:{upstream_port}"))
-    };
-    if let Ok(a) = auth {
+    if let Some(a) = upstream_authority(upstream_host, upstream_port, is_tls) {
         parts.authority = Some(a);
     }
 
@@ -379,8 +594,20 @@ fn handle_req_upstream(
     }
 }
 
+fn upstream_authority(upstream_host: &str, upstream_port: u16, is_tls: bool) -> Option<Authority> {
+    let default_port = if is_tls { 443 } else { 80 };
+    let auth = if upstream_port == default_port {
+        Authority::from_str(upstream_host)
+    } else {
+        Authority::from_str(&format!("{upstream_host}:{upstream_port}"))
+    };
+    auth.ok()
+}
+
 fn text(status: StatusCode, s: impl Into<String>) -> http::Response<BoxBody<Bytes, hyper::Error>> {
-    let body: BoxBody<Bytes, hyper::Error> = Full::new(Bytes::from(s.into()))
+    let mut s = s.into();
+    let _ = REQUEST_ID.try_with(|id| s.push_str(&format!(" (request id: {id})")));
+    let body: BoxBody<Bytes, hyper::Error> = Full::new(Bytes::from(s))
         .map_err(|never: Infallible| match never {})
         .boxed();
 
@@ -404,10 +631,10 @@ fn add_forward_headers(h: &mut http::HeaderMap, frontend_is_tls: bool, original_
         HeaderValue::from_static(proto),
     );
 
-    if !h.contains_key(HeaderName::from_static("x-forwarded-host")) {
-        if let Ok(v) = HeaderValue::from_str(original_host) {
-            let _ = h.insert(HeaderName::from_static("x-forwarded-host"), v);
-        }
+    if !h.contains_key(HeaderName::from_static("x-forwarded-host"))
+        && let Ok(v) = HeaderValue::from_str(original_host)
+    {
+        let _ = h.insert(HeaderName::from_static("x-forwarded-host"), v);
     }
 }
 
@@ -467,6 +694,8 @@ struct RequestSnapshot {
     version: Version,
     headers: HeaderMap,
     body_is_reusable: bool,
+    /// buffered request body replayed on retries
+    body: Bytes,
 }
 
 impl RequestSnapshot {
@@ -477,11 +706,72 @@ impl RequestSnapshot {
             version: req.version(),
             headers: req.headers().clone(),
             body_is_reusable: req.body().is_end_stream(),
+            body: Bytes::new(),
+        }
+    }
+}
+
+// read the body ahead up to `limit`: a body that ends within it is kept in the snapshot for
+// retries, a larger one (or one with trailers) is streamed through as is and not retried
+async fn buffer_request_body(
+    req: Request<Incoming>,
+    limit: usize,
+    snapshot: &mut RequestSnapshot,
+) -> ProxyResult<Request<BoxBody<Bytes, hyper::Error>>> {
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > limit as u64) {
+        return Ok(req.map(|b| b.boxed()));
+    }
+
+    let (parts, mut body) = req.into_parts();
+    let mut frames = VecDeque::new();
+    let mut buffered = 0;
+    let mut has_trailers = false;
+    while buffered <= limit {
+        match body.frame().await {
+            Some(Ok(frame)) => {
+                match frame.data_ref() {
+                    Some(data) => buffered += data.len(),
+                    None => has_trailers = true,
+                }
+                frames.push_back(frame);
+            }
+            Some(Err(err)) => {
+                tracing::debug!(%err, "failed to read request body");
+                return Err(Box::new(text(
+                    StatusCode::BAD_REQUEST,
+                    "invalid request body",
+                )));
+            }
+            None if !has_trailers => {
+                let mut data = BytesMut::with_capacity(buffered);
+                for frame in frames {
+                    if let Ok(chunk) = frame.into_data() {
+                        data.extend_from_slice(&chunk);
+                    }
+                }
+                snapshot.body = data.freeze();
+                snapshot.body_is_reusable = true;
+                return Ok(Request::from_parts(
+                    parts,
+                    utils::full(snapshot.body.clone()),
+                ));
+            }
+            None => break,
         }
     }
+
+    Ok(Request::from_parts(
+        parts,
+        PrefixedBody::new(frames, body).boxed(),
+    ))
 }
 
-fn extract_host(req: &Request<Incoming>) -> ProxyResult<String> {
+pub fn extract_host<B>(req: &Request<B>) -> ProxyResult<String> {
     if let Some(h) = req.headers().get(header::HOST) {
         match h.to_str() {
             Ok(s) if !s.is_empty() => match Authority::try_from(s.trim()) {
@@ -490,14 +780,20 @@ fn extract_host(req: &Request<Incoming>) -> ProxyResult<String> {
                     .to_ascii_lowercase()
                     .trim_end_matches('.')
                     .to_string()),
-                Err(_) => Err(text(StatusCode::BAD_REQUEST, "Invalid Host header")),
+                Err(_) => Err(Box::new(text(
+                    StatusCode::BAD_REQUEST,
+                    "Invalid Host header",
+                ))),
             },
-            _ => Err(text(StatusCode::BAD_REQUEST, "Invalid Host header")),
+            _ => Err(Box::new(text(
+                StatusCode::BAD_REQUEST,
+                "Invalid Host header",
+            ))),
         }
     } else if let Some(h) = req.uri().host() {
         Ok(h.to_string())
     } else {
-        Err(text(StatusCode::BAD_REQUEST, "Missing Host"))
+        Err(Box::new(text(StatusCode::BAD_REQUEST, "Missing Host")))
     }
 }
 
@@ -510,7 +806,7 @@ fn resolve_route<'a>(
         Some(rule) => Ok(rule),
         None => {
             tracing::warn!(%host, %path, "route not found");
-            Err(text(StatusCode::NOT_FOUND, "route not found"))
+            Err(Box::new(text(StatusCode::NOT_FOUND, "route not found")))
         }
     }
 }
@@ -520,77 +816,446 @@ fn resolve_cluster(route_table: &RouteTable, rule: &RouteRule) -> ProxyResult<Ar
         Some(cluster) => Ok(cluster),
         None => {
             tracing::error!(cluster=%rule.cluster, "cluster rule not found");
-            Err(text(StatusCode::NOT_FOUND, "cluster rules not found"))
+            Err(Box::new(text(
+                StatusCode::NOT_FOUND,
+                "cluster rules not found",
+            )))
         }
     }
 }
 
-fn resolve_endpoint(route_table: &RouteTable, rule: &RouteRule) -> ProxyResult<SelectedEndpoint> {
-    match route_table.get_endpoint(rule.cluster.as_str()) {
+fn resolve_endpoint(
+    route_table: &RouteTable,
+    rule: &RouteRule,
+    hash: Option<u64>,
+) -> ProxyResult<SelectedEndpoint> {
+    match route_table.get_endpoint(rule.cluster.as_str(), hash) {
         Some(endpoint) => Ok(endpoint),
         None => {
             tracing::error!(cluster=%rule.cluster, "endpoint not found");
-            Err(text(StatusCode::BAD_GATEWAY, "endpoint not found"))
+            Err(Box::new(text(
+                StatusCode::BAD_GATEWAY,
+                "endpoint not found",
+            )))
+        }
+    }
+}
+
+// consistent-hash key of the request; None when the configured source is absent
+pub fn request_hash<B>(req: &Request<B>, policy: &HashPolicyRule) -> Option<u64> {
+    match policy {
+        HashPolicyRule::Header(name) => {
+            let value = req.headers().get(name)?;
+            Some(hash_bytes(value.as_bytes()))
+        }
+        HashPolicyRule::Cookie(name) => {
+            let value = cookie_value(req.headers(), name)?;
+            Some(hash_bytes(value.as_bytes()))
+        }
+        HashPolicyRule::QueryParameter(name) => {
+            let value = req.uri().query()?.split('&').find_map(|pair| {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+                (k == name).then_some(v)
+            })?;
+            Some(hash_bytes(value.as_bytes()))
+        }
+        HashPolicyRule::SourceIp => {
+            let addr = req.extensions().get::<ClientAddr>()?;
+            Some(hash_bytes(addr.0.ip().to_string().as_bytes()))
+        }
+    }
+}
+
+fn set_affinity_cookie(
+    headers: &mut HeaderMap,
+    affinity: &SessionAffinityRule,
+    value: &str,
+    frontend_is_tls: bool,
+) {
+    let mut cookie = format!(
+        "{}={}; Path={}; HttpOnly; SameSite=Lax",
+        affinity.cookie_name, value, affinity.cookie_path
+    );
+    if affinity.max_age_sec > 0 {
+        cookie.push_str(&format!("; Max-Age={}", affinity.max_age_sec));
+    }
+    if frontend_is_tls {
+        cookie.push_str("; Secure");
+    }
+
+    match HeaderValue::from_str(&cookie) {
+        Ok(v) => {
+            headers.append(header::SET_COOKIE, v);
+        }
+        Err(err) => {
+            tracing::warn!(cookie = %affinity.cookie_name, %err, "invalid session affinity cookie");
+        }
+    }
+}
+
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|pair| {
+            let (k, v) = pair.trim().split_once('=')?;
+            (k == name).then_some(v)
+        })
+}
+
+/// Endpoint an upstream attempt goes to, with the runtime state fed by its outcome.
+/// Counts as an active connection of the endpoint while alive.
+struct UpstreamTarget {
+    endpoint: Endpoint,
+    addr: String,
+    stats: Option<Arc<EndpointStats>>,
+    health: Option<Arc<HealthState>>,
+    _active: ActiveConnGuard,
+}
+
+impl UpstreamTarget {
+    fn new(selection: SelectedEndpoint) -> Self {
+        let SelectedEndpoint {
+            endpoint,
+            counter,
+            stats,
+            health,
+        } = selection;
+        Self {
+            addr: format!("{}:{}", endpoint.address, endpoint.port),
+            endpoint,
+            stats,
+            health,
+            _active: ActiveConnGuard::new(counter),
+        }
+    }
+}
+
+type UpstreamClient = Client<HttpsConnector<HttpConnector>, BoxBody<Bytes, hyper::Error>>;
+type AttemptResult = Result<Result<Response<Incoming>, hyper_util::client::legacy::Error>, Elapsed>;
+
+/// What every upstream attempt of one request shares.
+struct Upstream<'a> {
+    client: &'a UpstreamClient,
+    state: &'a AppState,
+    route_cluster: &'a str,
+    hash: Option<u64>,
+    cluster: &'a ClusterRule,
+    snapshot: &'a RequestSnapshot,
+    timeout: Duration,
+    trace: Option<&'a Context>,
+}
+
+/// Circuit breaker and retry budget slots held by a retry or hedged attempt; released on drop.
+struct RetryPermits {
+    _breaker: Option<RetryPermit>,
+    _budget: Option<BudgetPermit>,
+}
+
+impl Upstream<'_> {
+    // one attempt; its outcome feeds the endpoint latency and health
+    async fn send(
+        &self,
+        request: Request<BoxBody<Bytes, hyper::Error>>,
+        target: &UpstreamTarget,
+    ) -> AttemptResult {
+        let mut request = request;
+        let span = self
+            .trace
+            .zip(self.state.telemetry.as_deref())
+            .map(|(parent, telemetry)| {
+                let name = format!("upstream {}", self.cluster.name());
+                let cx = telemetry.start_client(parent, name, request.method(), request.uri());
+                telemetry.inject(&cx, request.headers_mut());
+                cx
+            });
+        let started = Instant::now();
+        let result = self.client.request(request).timeout(self.timeout).await;
+        if let Some(cx) = &span {
+            match &result {
+                Ok(Ok(resp)) => telemetry::end_span(cx, Some(resp.status()), None),
+                Ok(Err(e)) => telemetry::end_span(cx, None, Some(&e.to_string())),
+                Err(_) => telemetry::end_span(cx, None, Some("timeout")),
+            }
+        }
+        match &result {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                let kind = if e.is_connect() { "connect" } else { "reset" };
+                self.state.metrics.upstream_error(self.cluster.name(), kind);
+            }
+            Err(_) => self
+                .state
+                .metrics
+                .upstream_error(self.cluster.name(), "timeout"),
+        }
+        // failed attempts count as a full timeout so P2C steers away from the endpoint
+        if let Some(stats) = target.stats.as_deref() {
+            let latency = if matches!(result, Ok(Ok(_))) {
+                started.elapsed()
+            } else {
+                self.timeout
+            };
+            stats.record_latency(latency);
+        }
+        if let Some(health) = target.health.as_deref() {
+            let failed = match &result {
+                Ok(Ok(resp)) => resp.status().is_server_error(),
+                _ => true,
+            };
+            self.cluster
+                .record_outcome(health, &target.endpoint, failed);
+        }
+        result
+    }
+
+    // attempts past the first need a circuit breaker slot and retry budget
+    fn retry_permits(&self, kind: &str, target: &UpstreamTarget) -> Option<RetryPermits> {
+        let cluster = self.cluster;
+        let breaker = match &cluster.circuit_breaker {
+            Some(breaker) => match breaker.try_retry() {
+                Some(permit) => Some(permit),
+                None => {
+                    tracing::warn!(cluster = %cluster.name(), addr = %target.addr, "{kind} skipped: circuit breaker max retries");
+                    return None;
+                }
+            },
+            None => None,
+        };
+        let budget = match &cluster.retry_budget {
+            Some(budget) => match budget.try_retry() {
+                Some(permit) => Some(permit),
+                None => {
+                    self.state.metrics.retry_budget_exhausted(cluster.name());
+                    tracing::warn!(cluster = %cluster.name(), addr = %target.addr, exhausted = budget.exhausted(), "{kind} skipped: retry budget exhausted");
+                    return None;
+                }
+            },
+            None => None,
+        };
+        Some(RetryPermits {
+            _breaker: breaker,
+            _budget: budget,
+        })
+    }
+
+    // next endpoint per the LB policy, skipping the ones already tried
+    async fn next_target(&self, tried: &[Endpoint]) -> Option<UpstreamTarget> {
+        let route_table = self.state.route_table.read().await.clone();
+        route_table
+            .get_retry_endpoint(self.route_cluster, self.hash, tried)
+            .map(UpstreamTarget::new)
+    }
+
+    fn retry_request(&self, target: &UpstreamTarget) -> Request<BoxBody<Bytes, hyper::Error>> {
+        build_retry_request(
+            self.snapshot,
+            &target.endpoint,
+            &self.cluster.backend_protocol,
+        )
+    }
+
+    // a response the client can get, as opposed to one the retry policy would retry
+    fn is_answer(&self, result: &AttemptResult) -> bool {
+        match result {
+            Ok(Ok(resp)) => !self
+                .cluster
+                .retry_policy
+                .retries_status(resp.status().as_u16()),
+            _ => false,
+        }
+    }
+
+    // hedging: when the first attempt has no response headers after the hedge delay, race a
+    // second attempt on another endpoint; the first answer wins and the other attempt is dropped
+    async fn send_hedged(
+        &self,
+        request: Request<BoxBody<Bytes, hyper::Error>>,
+        target: UpstreamTarget,
+        delay: Duration,
+        tried: &mut Vec<Endpoint>,
+    ) -> (AttemptResult, UpstreamTarget) {
+        let mut hedge: Option<UpstreamTarget> = None;
+        let mut _permits = None;
+        let (result, hedge_won) = {
+            let first = self.send(request, &target);
+            tokio::pin!(first);
+            tokio::select! {
+                result = &mut first => (result, false),
+                _ = tokio::time::sleep(delay) => {
+                    let next = self
+                        .next_target(std::slice::from_ref(&target.endpoint))
+                        .await
+                        .filter(|next| next.endpoint != target.endpoint);
+                    let permits = match &next {
+                        Some(next) => self.retry_permits("hedge", next),
+                        None => None,
+                    };
+                    match (next, permits) {
+                        (Some(next), Some(permits)) => {
+                            _permits = Some(permits);
+                            self.state.metrics.hedge(self.cluster.name());
+                            tracing::debug!(cluster = %self.cluster.name(), addr = %next.addr, "hedging upstream request");
+                            let next = hedge.insert(next);
+                            let second = self.send(self.retry_request(next), next);
+                            tokio::pin!(second);
+                            tokio::select! {
+                                result = &mut first => if self.is_answer(&result) {
+                                    (result, false)
+                                } else {
+                                    (second.await, true)
+                                },
+                                result = &mut second => if self.is_answer(&result) {
+                                    (result, true)
+                                } else {
+                                    (first.await, false)
+                                },
+                            }
+                        }
+                        _ => (first.await, false),
+                    }
+                }
+            }
+        };
+
+        match hedge {
+            Some(hedge) if hedge_won => {
+                tried.push(target.endpoint.clone());
+                (result, hedge)
+            }
+            Some(hedge) => {
+                tried.push(hedge.endpoint.clone());
+                (result, target)
+            }
+            None => (result, target),
         }
     }
 }
 
+// send the request, retrying per the cluster retry policy on another endpoint each time;
+// returns the response with the endpoint that served it
 async fn forward_to_upstream(
     initial_request: Request<BoxBody<Bytes, hyper::Error>>,
-    client: &Client<HttpsConnector<HttpConnector>, BoxBody<Bytes, hyper::Error>>,
-    timeout: Duration,
-    retries: usize,
+    state: &AppState,
+    info: &mut RequestInfo,
+    hash: Option<u64>,
+    cluster: &ClusterRule,
     snapshot: &RequestSnapshot,
-    addr: &str,
-) -> Result<Response<Incoming>, String> {
-    let mut last_err: Option<String> = None;
-    let mut response: Option<Response<Incoming>> = None;
-    let mut first_request = Some(initial_request);
-
-    for attempt in 0..retries {
-        let request = if let Some(req) = first_request.take() {
-            req
-        } else if snapshot.body_is_reusable {
-            build_retry_request(snapshot)
-        } else {
-            break;
+    mut target: UpstreamTarget,
+) -> Result<(Response<Incoming>, Endpoint), String> {
+    let pool = state.client_pool.load();
+    let client = if cluster.backend_tls_insecure_skip_verify {
+        &pool.connector_insecure
+    } else {
+        &pool.connector
+    };
+    let policy = &cluster.retry_policy;
+    let route_cluster = info.labels.cluster.clone();
+    let trace = info.trace.clone();
+    let upstream = Upstream {
+        client,
+        state,
+        route_cluster: &route_cluster,
+        hash,
+        cluster,
+        snapshot,
+        timeout: policy
+            .per_try_timeout
+            .unwrap_or_else(|| Duration::from_millis(cluster.timeout_ms.max(0) as u64)),
+        trace: trace.as_ref(),
+    };
+    // Retries: at least 1 attempt
+    let attempts = cluster.retries.max(1) as usize;
+    let hedge_delay = cluster
+        .hedge_policy
+        .as_deref()
+        .filter(|hedge| snapshot.body_is_reusable && hedge.methods.contains(&snapshot.method))
+        .map(|hedge| hedge.delay);
+
+    let mut request = initial_request;
+    let mut tried: Vec<Endpoint> = Vec::new();
+    let mut _retry_permits = None;
+    let mut attempt = 0;
+
+    loop {
+        let result = match hedge_delay {
+            Some(delay) if attempt == 0 => {
+                let (result, winner) = upstream
+                    .send_hedged(request, target, delay, &mut tried)
+                    .await;
+                target = winner;
+                result
+            }
+            _ => upstream.send(request, &target).await,
         };
+        info.upstream = Some(target.addr.clone());
+        info.retries = attempt as u32;
 
-        match client.request(request).timeout(timeout).await {
-            Ok(Ok(resp)) => {
-                response = Some(resp);
-                break;
-            }
+        let (retryable, outcome) = match result {
+            Ok(Ok(resp)) => (policy.retries_status(resp.status().as_u16()), Ok(resp)),
             Ok(Err(e)) => {
-                last_err = Some(format!("Upstream connector error ({}): {:?}", e, addr));
-                if attempt + 1 == retries {
-                    break;
-                }
+                let retryable = if e.is_connect() {
+                    policy.retry_on_connect_failure
+                } else {
+                    policy.retry_on_reset
+                };
+                let err = format!("Upstream connector error ({}): {:?}", e, target.addr);
+                (retryable, Err(err))
             }
             Err(e) => {
-                last_err = Some(format!("Upstream connector timeout ({}): {:?}", e, addr));
-                if attempt + 1 == retries {
-                    break;
-                }
+                let err = format!("Upstream connector timeout ({}): {:?}", e, target.addr);
+                (policy.retry_on_timeout, Err(err))
+            }
+        };
+
+        attempt += 1;
+        if !retryable || attempt == attempts || !snapshot.body_is_reusable {
+            return outcome.map(|resp| (resp, target.endpoint));
+        }
+
+        // retries hold their slots until the next retry or the end of the request
+        match upstream.retry_permits("retry", &target) {
+            Some(permits) => _retry_permits = Some(permits),
+            None => return outcome.map(|resp| (resp, target.endpoint)),
+        }
+        state.metrics.retry(cluster.name());
+        match &outcome {
+            Ok(resp) => {
+                tracing::debug!(cluster = %cluster.name(), addr = %target.addr, status = resp.status().as_u16(), attempt, "retrying upstream request")
+            }
+            Err(err) => {
+                tracing::debug!(cluster = %cluster.name(), %err, attempt, "retrying upstream request")
             }
         }
-    }
+        drop(outcome);
 
-    match response {
-        Some(resp) => Ok(resp),
-        None => {
-            Err(last_err.unwrap_or_else(|| format!("Upstream connector timed out: {:?}", addr)))
+        tokio::time::sleep(cluster.retry_backoff(attempt as u32)).await;
+
+        tried.push(target.endpoint.clone());
+        if let Some(next) = upstream.next_target(&tried).await {
+            target = next;
         }
+        request = upstream.retry_request(&target);
     }
 }
 
-fn build_retry_request(snapshot: &RequestSnapshot) -> Request<BoxBody<Bytes, hyper::Error>> {
-    let body = Full::new(Bytes::new())
-        .map_err(|never: Infallible| match never {})
-        .boxed();
+fn build_retry_request(
+    snapshot: &RequestSnapshot,
+    endpoint: &Endpoint,
+    proto: &BackendProtocol,
+) -> Request<BoxBody<Bytes, hyper::Error>> {
+    let body = utils::full(snapshot.body.clone());
+    let is_tls = matches!(proto, BackendProtocol::H1Ssl | BackendProtocol::H2Ssl);
+    let mut parts = snapshot.uri.clone().into_parts();
+    if let Some(a) = upstream_authority(&endpoint.address, endpoint.port as u16, is_tls) {
+        parts.authority = Some(a);
+    }
+    let uri = Uri::from_parts(parts).unwrap_or_else(|_| snapshot.uri.clone());
     let builder = Request::builder()
         .method(snapshot.method.clone())
-        .uri(snapshot.uri.clone())
+        .uri(uri)
         .version(snapshot.version);
     let mut retry_req = builder
         .body(body)
@@ -598,3 +1263,510 @@ fn build_retry_request(snapshot: &RequestSnapshot) -> Request<BoxBody<Bytes, hyp
     *retry_req.headers_mut() = snapshot.headers.clone();
     retry_req
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argon_config::{
+        AuthConfig, Cluster, HedgePolicy, RetryBudget, RetryPolicy, Route, Snapshot,
+    };
+    use crate::client_pool::ClientPool;
+    use crate::endpoint_registry::EndpointRegistry;
+    use crate::snapshot::Locality;
+    use arc_swap::ArcSwap;
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+    use hyper::service::service_fn;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+    use tokio::sync::RwLock;
+
+    /// What a stub upstream received.
+    #[derive(Clone, Debug)]
+    struct Received {
+        method: Method,
+        headers: HeaderMap,
+        body: Bytes,
+    }
+
+    /// Local HTTP server answering with its own port after `delay`; records the requests
+    /// it received and counts the ones dropped before it answered.
+    struct Stub {
+        addr: SocketAddr,
+        received: Arc<Mutex<Vec<Received>>>,
+        cancelled: Arc<AtomicUsize>,
+    }
+
+    // counts the handler future as cancelled unless it ran to completion
+    struct CancelGuard(Arc<AtomicUsize>, bool);
+
+    impl Drop for CancelGuard {
+        fn drop(&mut self) {
+            if !self.1 {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    impl Stub {
+        async fn start(delay: Duration) -> Stub {
+            Stub::serve(delay, StatusCode::OK).await
+        }
+
+        async fn failing(status: StatusCode) -> Stub {
+            Stub::serve(Duration::ZERO, status).await
+        }
+
+        async fn serve(delay: Duration, status: StatusCode) -> Stub {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let cancelled = Arc::new(AtomicUsize::new(0));
+            let (log, drops) = (received.clone(), cancelled.clone());
+            tokio::spawn(async move {
+                loop {
+                    let Ok((stream, _)) = listener.accept().await else {
+                        return;
+                    };
+                    let (log, drops) = (log.clone(), drops.clone());
+                    tokio::spawn(async move {
+                        let svc = service_fn(move |req: Request<Incoming>| {
+                            let (log, drops) = (log.clone(), drops.clone());
+                            async move {
+                                let mut guard = CancelGuard(drops, false);
+                                let (parts, body) = req.into_parts();
+                                let body = body.collect().await?.to_bytes();
+                                log.lock().unwrap().push(Received {
+                                    method: parts.method,
+                                    headers: parts.headers,
+                                    body,
+                                });
+                                tokio::time::sleep(delay).await;
+                                guard.1 = true;
+                                let mut resp =
+                                    Response::new(Full::new(Bytes::from(addr.port().to_string())));
+                                *resp.status_mut() = status;
+                                Ok::<_, hyper::Error>(resp)
+                            }
+                        });
+                        let _ = auto::Builder::new(TokioExecutor::new())
+                            .serve_connection(TokioIo::new(stream), svc)
+                            .await;
+                    });
+                }
+            });
+            Stub {
+                addr,
+                received,
+                cancelled,
+            }
+        }
+
+        fn received(&self) -> Vec<Received> {
+            self.received.lock().unwrap().clone()
+        }
+
+        fn endpoint(&self) -> Endpoint {
+            Endpoint {
+                address: self.addr.ip().to_string(),
+                port: self.addr.port() as i32,
+                weight: 1,
+                ..Default::default()
+            }
+        }
+    }
+
+    fn cluster(name: &str, stubs: &[&Stub]) -> Cluster {
+        Cluster {
+            name: name.into(),
+            lb_policy: "RoundRobin".into(),
+            endpoints: stubs.iter().map(|stub| stub.endpoint()).collect(),
+            timeout_ms: 5000,
+            ..Default::default()
+        }
+    }
+
+    fn hedged(mut cluster: Cluster, delay_ms: i32) -> Cluster {
+        cluster.hedge_policy = Some(HedgePolicy {
+            delay_ms,
+            ..Default::default()
+        });
+        cluster
+    }
+
+    /// Proxy listener serving `clusters` for host "app.example"; the first cluster takes "/".
+    async fn proxy(clusters: Vec<Cluster>) -> (SocketAddr, AppState) {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let snapshot = Snapshot {
+            version: "v1".into(),
+            routes: clusters
+                .iter()
+                .enumerate()
+                .map(|(idx, cluster)| Route {
+                    host: "app.example".into(),
+                    path: if idx == 0 {
+                        "/".into()
+                    } else {
+                        format!("/{}", cluster.name)
+                    },
+                    path_type: "Prefix".into(),
+                    cluster: cluster.name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            clusters,
+            ..Default::default()
+        };
+        let table = RouteTable::new(
+            &snapshot,
+            &Locality::default(),
+            &EndpointRegistry::default(),
+            &RouteTable::default(),
+        );
+        let state = AppState {
+            client_pool: Arc::new(ArcSwap::from_pointee(ClientPool::new_http_pool_connector(
+                1,
+            ))),
+            route_table: Arc::new(RwLock::new(Arc::new(table))),
+            ..Default::default()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let state = served.clone();
+                tokio::spawn(async move {
+                    let svc = service_fn(move |req| proxy_handler(req, state.clone()));
+                    let _ = auto::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(stream), svc)
+                        .await;
+                });
+            }
+        });
+        (addr, state)
+    }
+
+    fn request(proxy: SocketAddr, method: Method, path: &str) -> http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(format!("http://{proxy}{path}"))
+            .header(header::HOST, "app.example")
+    }
+
+    async fn call<B>(request: Request<B>) -> (StatusCode, HeaderMap, String)
+    where
+        B: Body<Data = Bytes, Error = Infallible> + Send + Unpin + 'static,
+    {
+        let client = Client::builder(TokioExecutor::new()).build_http::<B>();
+        let resp = client.request(request).await.unwrap();
+        let (parts, body) = resp.into_parts();
+        let body = body.collect().await.unwrap().to_bytes();
+        (
+            parts.status,
+            parts.headers,
+            String::from_utf8_lossy(&body).into_owned(),
+        )
+    }
+
+    async fn get(proxy: SocketAddr) -> (StatusCode, HeaderMap, String) {
+        call(
+            request(proxy, Method::GET, "/")
+                .body(Full::default())
+                .unwrap(),
+        )
+        .await
+    }
+
+    // waits up to a second for `done`, for effects that land after the response
+    async fn eventually(done: impl Fn() -> bool) -> bool {
+        for _ in 0..100 {
+            if done() {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        done()
+    }
+
+    #[tokio::test]
+    async fn fast_first_attempt_is_not_hedged() {
+        let first = Stub::start(Duration::ZERO).await;
+        let second = Stub::start(Duration::ZERO).await;
+        let (proxy, _) = proxy(vec![hedged(cluster("app", &[&first, &second]), 200)]).await;
+
+        let (status, _, body) = get(proxy).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, first.addr.port().to_string());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(second.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn slow_first_attempt_is_hedged_on_another_endpoint_and_cancelled() {
+        let slow = Stub::start(Duration::from_secs(3)).await;
+        let fast = Stub::start(Duration::ZERO).await;
+        let (proxy, state) = proxy(vec![hedged(cluster("app", &[&slow, &fast]), 50)]).await;
+
+        let started = Instant::now();
+        let (status, _, body) = get(proxy).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, fast.addr.port().to_string());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(slow.received().len(), 1);
+        assert_eq!(fast.received().len(), 1);
+        assert!(
+            eventually(|| slow.cancelled.load(Ordering::SeqCst) == 1).await,
+            "the losing attempt is dropped"
+        );
+
+        let table = state.route_table.read().await.clone();
+        assert!(
+            state
+                .metrics
+                .render(&table)
+                .contains("argon_upstream_hedges_total{cluster=\"app\"} 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_methods_are_not_hedged() {
+        let slow = Stub::start(Duration::from_millis(200)).await;
+        let fast = Stub::start(Duration::ZERO).await;
+        let (proxy, _) = proxy(vec![hedged(cluster("app", &[&slow, &fast]), 20)]).await;
+
+        let order = request(proxy, Method::POST, "/").body(Full::from("order"));
+        let (status, _, body) = call(order.unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, slow.addr.port().to_string());
+        assert_eq!(slow.received()[0].method, Method::POST);
+        assert!(fast.received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn hedges_stop_when_the_retry_budget_is_exhausted() {
+        let first = Stub::start(Duration::from_millis(300)).await;
+        let second = Stub::start(Duration::from_millis(300)).await;
+        let mut app = hedged(cluster("app", &[&first, &second]), 50);
+        app.retry_budget = Some(RetryBudget {
+            budget_percent: 1,
+            min_retry_concurrency: 1,
+        });
+        let (proxy, state) = proxy(vec![app]).await;
+
+        let (a, b) = tokio::join!(get(proxy), get(proxy));
+        assert_eq!((a.0, b.0), (StatusCode::OK, StatusCode::OK));
+        // two requests, one hedge: the second found the only retry slot taken
+        assert_eq!(first.received().len() + second.received().len(), 3);
+
+        let table = state.route_table.read().await.clone();
+        let metrics = state.metrics.render(&table);
+        assert!(metrics.contains("argon_upstream_hedges_total{cluster=\"app\"} 1"));
+        assert!(metrics.contains("argon_retry_budget_exhausted_total{cluster=\"app\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn hedges_without_a_retry_budget_get_the_default_one() {
+        let stubs = [
+            Stub::start(Duration::from_millis(300)).await,
+            Stub::start(Duration::from_millis(300)).await,
+        ];
+        let (proxy, state) = proxy(vec![hedged(cluster("app", &[&stubs[0], &stubs[1]]), 50)]).await;
+
+        // the default budget allows three retries in flight at low load
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..5 {
+            requests.spawn(get(proxy));
+        }
+        while let Some(result) = requests.join_next().await {
+            assert_eq!(result.unwrap().0, StatusCode::OK);
+        }
+        let table = state.route_table.read().await.clone();
+        let metrics = state.metrics.render(&table);
+        assert!(metrics.contains("argon_upstream_hedges_total{cluster=\"app\"} 3"));
+        assert!(metrics.contains("argon_retry_budget_exhausted_total{cluster=\"app\"} 2"));
+    }
+
+    // cluster retrying 503s on the other endpoint, buffering bodies up to `max_body_bytes`
+    fn retrying(stubs: &[&Stub], max_body_bytes: i32) -> Cluster {
+        Cluster {
+            retries: 2,
+            retry_policy: Some(RetryPolicy {
+                retry_on: vec!["503".into()],
+                base_backoff_ms: 1,
+                max_body_bytes,
+                ..Default::default()
+            }),
+            ..cluster("app", stubs)
+        }
+    }
+
+    // chunked request body, without content-length
+    fn chunked(chunks: &[&'static str]) -> BoxBody<Bytes, Infallible> {
+        let frames = chunks
+            .iter()
+            .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))));
+        StreamBody::new(tokio_stream::iter(frames.collect::<Vec<_>>())).boxed()
+    }
+
+    fn post(
+        proxy: SocketAddr,
+        body: BoxBody<Bytes, Infallible>,
+    ) -> Request<BoxBody<Bytes, Infallible>> {
+        request(proxy, Method::POST, "/").body(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn body_at_the_limit_is_buffered_and_replayed_identically() {
+        let failing = Stub::failing(StatusCode::SERVICE_UNAVAILABLE).await;
+        let ok = Stub::start(Duration::ZERO).await;
+        let (proxy, _) = proxy(vec![retrying(&[&failing, &ok], 8)]).await;
+
+        let (status, _, body) = call(post(proxy, chunked(&["abcd", "efgh"]))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, ok.addr.port().to_string());
+        assert_eq!(failing.received()[0].body, "abcdefgh");
+        assert_eq!(ok.received()[0].body, "abcdefgh");
+        assert_eq!(ok.received()[0].method, Method::POST);
+    }
+
+    #[tokio::test]
+    async fn body_over_the_limit_is_streamed_whole_and_not_retried() {
+        let sized = Full::from("abcdefghi")
+            .map_err(|never| match never {})
+            .boxed();
+        for (body, sent) in [
+            // read ahead past the limit, then streamed on with the frames already read
+            (chunked(&["abcd", "efgh", "i", "jkl"]), "abcdefghijkl"),
+            // refused by content-length without reading it
+            (sized, "abcdefghi"),
+        ] {
+            let failing = Stub::failing(StatusCode::SERVICE_UNAVAILABLE).await;
+            let ok = Stub::start(Duration::ZERO).await;
+            let (proxy, _) = proxy(vec![retrying(&[&failing, &ok], 8)]).await;
+
+            let (status, _, _) = call(post(proxy, body)).await;
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(failing.received()[0].body, sent);
+            assert!(ok.received().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn empty_bodies_are_retried() {
+        let failing = Stub::failing(StatusCode::SERVICE_UNAVAILABLE).await;
+        let ok = Stub::start(Duration::ZERO).await;
+        let (proxy, _) = proxy(vec![retrying(&[&failing, &ok], 8)]).await;
+
+        let (status, _, _) = call(post(proxy, chunked(&[]))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = get(proxy).await;
+        assert_eq!(status, StatusCode::OK);
+        let bodies: Vec<Bytes> = ok.received().into_iter().map(|r| r.body).collect();
+        assert_eq!(bodies, [Bytes::new(), Bytes::new()]);
+    }
+
+    fn request_id(headers: &HeaderMap) -> &str {
+        headers.get("x-request-id").unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn request_id_from_the_client_is_kept_up_to_the_upstream_and_the_response() {
+        let upstream = Stub::start(Duration::ZERO).await;
+        let (proxy, _) = proxy(vec![cluster("app", &[&upstream])]).await;
+
+        let req = request(proxy, Method::GET, "/").header("x-request-id", "abc-123");
+        let (status, headers, _) = call(req.body(Full::<Bytes>::default()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(request_id(&headers), "abc-123");
+        assert_eq!(request_id(&upstream.received()[0].headers), "abc-123");
+    }
+
+    #[tokio::test]
+    async fn request_id_is_generated_when_missing_or_unusable() {
+        let upstream = Stub::start(Duration::ZERO).await;
+        let (proxy, _) = proxy(vec![cluster("app", &[&upstream])]).await;
+
+        let too_long = HeaderValue::from_str(&"x".repeat(MAX_REQUEST_ID_LEN + 1)).unwrap();
+        let not_text = HeaderValue::from_bytes(b"id-\xff").unwrap();
+        let sent = [
+            None,
+            Some(HeaderValue::from_static("  ")),
+            Some(too_long),
+            Some(not_text),
+        ];
+        for (idx, value) in sent.into_iter().enumerate() {
+            let mut req = request(proxy, Method::GET, "/");
+            if let Some(value) = value {
+                req = req.header("x-request-id", value);
+            }
+            let (_, headers, _) = call(req.body(Full::<Bytes>::default()).unwrap()).await;
+            let id = request_id(&headers);
+            assert!(Uuid::parse_str(id).is_ok(), "{id}");
+            let received = &upstream.received()[idx];
+            assert_eq!(received.headers.get_all("x-request-id").iter().count(), 1);
+            assert_eq!(request_id(&received.headers), id);
+        }
+    }
+
+    #[tokio::test]
+    async fn request_id_reaches_the_auth_subrequest() {
+        let auth = Stub::start(Duration::ZERO).await;
+        let upstream = Stub::start(Duration::ZERO).await;
+        let mut app = cluster("app", &[&upstream]);
+        app.auth = Some(AuthConfig {
+            url: format!("http://{}/oauth2/auth", auth.addr),
+            ..Default::default()
+        });
+        let (proxy, _) = proxy(vec![app]).await;
+
+        let req = request(proxy, Method::GET, "/").header("x-request-id", "abc-123");
+        let (status, _, _) = call(req.body(Full::<Bytes>::default()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(request_id(&auth.received()[0].headers), "abc-123");
+        assert_eq!(request_id(&upstream.received()[0].headers), "abc-123");
+
+        // a generated id is the same in the subrequest and the proxied request
+        let (_, headers, _) = get(proxy).await;
+        let id = request_id(&headers);
+        assert_eq!(request_id(&auth.received()[1].headers), id);
+        assert_eq!(request_id(&upstream.received()[1].headers), id);
+    }
+
+    #[tokio::test]
+    async fn request_id_is_in_error_bodies() {
+        // nothing listens on a port that was just released
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+        let mut app = cluster("app", &[]);
+        app.endpoints.push(Endpoint {
+            address: closed_addr.ip().to_string(),
+            port: closed_addr.port() as i32,
+            weight: 1,
+            ..Default::default()
+        });
+        let (proxy, _) = proxy(vec![app]).await;
+
+        let req = request(proxy, Method::GET, "/").header("x-request-id", "abc-123");
+        let (status, headers, body) = call(req.body(Full::<Bytes>::default()).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(request_id(&headers), "abc-123");
+        assert!(body.ends_with("(request id: abc-123)"), "{body}");
+
+        let unknown = Request::get(format!("http://{proxy}/"))
+            .header(header::HOST, "unknown.example")
+            .body(Full::<Bytes>::default())
+            .unwrap();
+        let (status, headers, body) = call(unknown).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let id = request_id(&headers);
+        assert!(body.ends_with(&format!("(request id: {id})")), "{body}");
+    }
+}
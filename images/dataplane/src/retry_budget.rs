@@ -0,0 +1,122 @@
+use crate::snapshot::RetryBudgetRule;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Per-cluster cap on retries in flight, relative to the requests in flight, so a degraded
+/// backend is not hit with `retries` times its normal load.
+#[derive(Debug)]
+pub struct RetryBudget {
+    rule: RetryBudgetRule,
+    active: Arc<AtomicUsize>,
+    retries: Arc<AtomicUsize>,
+    exhausted: AtomicU64,
+}
+
+/// A request in flight to the cluster; released on drop.
+#[derive(Debug)]
+pub struct ActiveRequest {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A retry in flight within the budget; released on drop.
+#[derive(Debug)]
+pub struct BudgetPermit {
+    retries: Arc<AtomicUsize>,
+}
+
+impl Drop for BudgetPermit {
+    fn drop(&mut self) {
+        self.retries.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RetryBudget {
+    pub fn new(rule: RetryBudgetRule) -> Self {
+        Self {
+            rule,
+            active: Arc::new(AtomicUsize::new(0)),
+            retries: Arc::new(AtomicUsize::new(0)),
+            exhausted: AtomicU64::new(0),
+        }
+    }
+
+    pub fn start_request(&self) -> ActiveRequest {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        ActiveRequest {
+            active: self.active.clone(),
+        }
+    }
+
+    // None when the retries in flight already use up budget_percent of the active requests
+    // (but never fewer than min_retry_concurrency)
+    pub fn try_retry(&self) -> Option<BudgetPermit> {
+        let allowed = (self.active.load(Ordering::Relaxed) * self.rule.budget_percent / 100)
+            .max(self.rule.min_retry_concurrency);
+        let retries = self.retries.fetch_add(1, Ordering::Relaxed) + 1;
+        let permit = BudgetPermit {
+            retries: self.retries.clone(),
+        };
+        if retries > allowed {
+            self.exhausted.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(permit)
+    }
+
+    // retries skipped because the budget was exhausted
+    pub fn exhausted(&self) -> u64 {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(budget_percent: usize, min_retry_concurrency: usize) -> RetryBudget {
+        RetryBudget::new(RetryBudgetRule {
+            budget_percent,
+            min_retry_concurrency,
+        })
+    }
+
+    #[test]
+    fn min_retry_concurrency_applies_without_load() {
+        let budget = budget(20, 3);
+        let permits: Vec<_> = (0..3).map(|_| budget.try_retry().unwrap()).collect();
+        assert!(budget.try_retry().is_none());
+        assert_eq!(budget.exhausted(), 1);
+        drop(permits);
+        assert!(budget.try_retry().is_some());
+    }
+
+    #[test]
+    fn budget_is_a_percent_of_active_requests() {
+        let budget = budget(20, 3);
+        let requests: Vec<_> = (0..50).map(|_| budget.start_request()).collect();
+        let permits: Vec<_> = (0..10).map(|_| budget.try_retry().unwrap()).collect();
+        assert!(budget.try_retry().is_none());
+
+        drop(requests);
+        drop(permits);
+        assert_eq!(budget.active.load(Ordering::Relaxed), 0);
+        assert_eq!(budget.retries.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn rejected_retry_does_not_hold_a_slot() {
+        let budget = budget(20, 1);
+        let _permit = budget.try_retry().unwrap();
+        for _ in 0..5 {
+            assert!(budget.try_retry().is_none());
+        }
+        assert_eq!(budget.retries.load(Ordering::Relaxed), 1);
+        assert_eq!(budget.exhausted(), 5);
+    }
+}
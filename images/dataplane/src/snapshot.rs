@@ -1,32 +1,148 @@
-use dashmap::DashMap;
-use http::HeaderName;
+use http::{HeaderName, Method};
 use std::cmp::PartialEq;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
 
-use crate::argon_config::{AuthConfig, Endpoint, HeaderRewrite, Snapshot};
+use ring::hmac;
+use ring::rand::SystemRandom;
+
+use crate::argon_config::{
+    AuthConfig, CircuitBreaker as CircuitBreakerPb, Cluster as ClusterPb, Endpoint, HashPolicy,
+    HeaderRewrite, HealthCheck, HedgePolicy, OutlierDetection, RetryBudget as RetryBudgetPb,
+    RetryPolicy, SessionAffinity, Snapshot,
+};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::consistent_hash::{HashTable, Maglev, RingHash, hash_bytes};
+use crate::endpoint_registry::{EndpointId, EndpointRegistry, EndpointState};
+use crate::health::HealthState;
+use crate::retry_budget::RetryBudget;
+use crate::utils::splitmix64;
+use std::borrow::Cow;
 use tracing::warn;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct RouteRule {
+    /// host the rule was configured for; empty for the default host
+    host: String,
     path: String,
     path_type: PathType,
     pub cluster: String,
     priority: i32,
+    access_log: RouteAccessLog,
+}
+
+/// Access logging of a route.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum RouteAccessLog {
+    Off,
+    /// dataplane-wide sampling
+    #[default]
+    Default,
+    /// percent of requests logged
+    Sampled(u32),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct EndpointKey {
-    address: String,
-    port: i32,
-    index: usize,
+impl RouteRule {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn path_type(&self) -> &'static str {
+        self.path_type.as_str()
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        match self.path_type {
+            PathType::Exact => self.path == path,
+            PathType::Prefix => path.starts_with(self.path.as_str()),
+        }
+    }
+
+    // why `path` does not match this rule
+    fn miss_reason(&self) -> &'static str {
+        match self.path_type {
+            PathType::Exact => "path is not equal to the exact path",
+            PathType::Prefix => "path does not start with the prefix",
+        }
+    }
+
+    pub fn access_log(&self) -> RouteAccessLog {
+        self.access_log
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct SelectedEndpoint {
     pub endpoint: Endpoint,
     pub counter: Option<Arc<AtomicUsize>>,
+    pub stats: Option<Arc<EndpointStats>>,
+    pub health: Option<Arc<HealthState>>,
+}
+
+/// Outcome of matching a host and path against the route table, for the admin route test.
+#[derive(Debug)]
+pub struct RouteExplanation<'a> {
+    /// the table has routes for the exact host; otherwise only the default host is searched
+    pub host_bucket: bool,
+    pub matched: Option<&'a RouteRule>,
+    /// rules tried before the match, in match order, with the reason they were passed over
+    pub skipped: Vec<(&'a RouteRule, &'static str)>,
+    /// rules of other hosts whose path matches; never tried because the host differs.
+    /// Routes match on host and path only, so method and headers never skip a rule.
+    pub other_hosts: Vec<&'a RouteRule>,
+}
+
+/// What the dataplane currently believes about one cluster endpoint.
+#[derive(Clone, Debug)]
+pub struct EndpointStatus<'a> {
+    pub endpoint: &'a Endpoint,
+    /// locality level: 0 same zone, then same region, then everything else
+    pub priority: usize,
+    pub active: usize,
+    pub latency_ewma_us: f64,
+    /// None without health checks and outlier detection
+    pub health: Option<&'static str>,
+}
+
+/// weight of the newest latency sample in the moving average
+const EWMA_ALPHA: f64 = 0.3;
+
+/// Upstream latency of one endpoint as an exponentially-weighted moving average.
+#[derive(Debug, Default)]
+pub struct EndpointStats {
+    // f64 bits, microseconds; 0 until the first sample
+    ewma_us: AtomicU64,
+}
+
+impl EndpointStats {
+    pub fn record_latency(&self, latency: Duration) {
+        let sample = latency.as_micros() as f64;
+        let _ = self
+            .ewma_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                let current = f64::from_bits(bits);
+                let next = if current == 0.0 {
+                    sample
+                } else {
+                    current + EWMA_ALPHA * (sample - current)
+                };
+                Some(next.to_bits())
+            });
+    }
+
+    pub fn latency_ewma_us(&self) -> f64 {
+        f64::from_bits(self.ewma_us.load(Ordering::Relaxed))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -88,7 +204,7 @@ impl AuthConfigDex {
 }
 
 fn build_auth_runtime(auth: Option<&AuthConfig>) -> Option<Arc<AuthConfigDex>> {
-    let Some(pb) = auth else { return None };
+    let pb = auth?;
     // If everything is empty, don't attach auth
     let has_any = !pb.url.trim().is_empty()
         || !pb.signin.trim().is_empty()
@@ -101,6 +217,268 @@ fn build_auth_runtime(auth: Option<&AuthConfig>) -> Option<Arc<AuthConfigDex>> {
     Some(Arc::new(AuthConfigDex::from_pb(pb)))
 }
 
+#[derive(Clone, Debug)]
+pub struct SessionAffinityRule {
+    pub cookie_name: String,
+    pub cookie_path: String,
+    pub max_age_sec: i64,
+}
+
+impl SessionAffinityRule {
+    fn from_pb(pb: &SessionAffinity) -> Option<Self> {
+        let cookie_name = pb.cookie_name.trim();
+        if cookie_name.is_empty() {
+            return None;
+        }
+        let cookie_path = if pb.cookie_path.trim().is_empty() {
+            "/".to_string()
+        } else {
+            pb.cookie_path.trim().to_string()
+        };
+        Some(SessionAffinityRule {
+            cookie_name: cookie_name.to_string(),
+            cookie_path,
+            max_age_sec: pb.max_age_sec.max(0),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheckRule {
+    pub path: String,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub healthy_threshold: u32,
+    pub unhealthy_threshold: u32,
+    pub expected_statuses: Vec<u16>,
+}
+
+impl HealthCheckRule {
+    fn from_pb(pb: &HealthCheck) -> Option<Self> {
+        let path = pb.path.trim();
+        if path.is_empty() {
+            return None;
+        }
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{path}")
+        };
+        let millis =
+            |v: i32, default: u64| Duration::from_millis(if v > 0 { v as u64 } else { default });
+        let threshold = |v: i32, default: u32| if v > 0 { v as u32 } else { default };
+        Some(HealthCheckRule {
+            path,
+            interval: millis(pb.interval_ms, 5000),
+            timeout: millis(pb.timeout_ms, 1000),
+            healthy_threshold: threshold(pb.healthy_threshold, 2),
+            unhealthy_threshold: threshold(pb.unhealthy_threshold, 3),
+            expected_statuses: pb
+                .expected_statuses
+                .iter()
+                .filter_map(|s| u16::try_from(*s).ok())
+                .collect(),
+        })
+    }
+
+    pub fn accepts(&self, status: u16) -> bool {
+        if self.expected_statuses.is_empty() {
+            (200..400).contains(&status)
+        } else {
+            self.expected_statuses.contains(&status)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlierDetectionRule {
+    pub consecutive_errors: u32,
+    pub base_ejection: Duration,
+    pub max_ejection: Duration,
+    pub max_ejection_percent: u32,
+}
+
+impl OutlierDetectionRule {
+    fn from_pb(pb: &OutlierDetection) -> Option<Self> {
+        if pb.consecutive_errors <= 0 {
+            return None;
+        }
+        let millis =
+            |v: i32, default: u64| Duration::from_millis(if v > 0 { v as u64 } else { default });
+        Some(OutlierDetectionRule {
+            consecutive_errors: pb.consecutive_errors as u32,
+            base_ejection: millis(pb.base_ejection_ms, 30_000),
+            max_ejection: millis(pb.max_ejection_ms, 300_000),
+            max_ejection_percent: if pb.max_ejection_percent > 0 {
+                pb.max_ejection_percent.min(100) as u32
+            } else {
+                10
+            },
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerRule {
+    pub max_requests: usize,
+    pub max_pending: usize,
+    pub max_retries: usize,
+    pub pending_timeout: Duration,
+}
+
+impl CircuitBreakerRule {
+    fn from_pb(pb: &CircuitBreakerPb) -> Option<Self> {
+        let rule = CircuitBreakerRule {
+            max_requests: pb.max_requests.max(0) as usize,
+            max_pending: pb.max_pending_requests.max(0) as usize,
+            max_retries: pb.max_retries.max(0) as usize,
+            pending_timeout: Duration::from_millis(if pb.pending_timeout_ms > 0 {
+                pb.pending_timeout_ms as u64
+            } else {
+                1000
+            }),
+        };
+        if rule.max_requests == 0 && rule.max_retries == 0 {
+            return None;
+        }
+        Some(rule)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryBudgetRule {
+    pub budget_percent: usize,
+    pub min_retry_concurrency: usize,
+}
+
+impl Default for RetryBudgetRule {
+    fn default() -> Self {
+        Self::from_pb(&RetryBudgetPb::default())
+    }
+}
+
+impl RetryBudgetRule {
+    fn from_pb(pb: &RetryBudgetPb) -> Self {
+        RetryBudgetRule {
+            budget_percent: if pb.budget_percent > 0 {
+                pb.budget_percent as usize
+            } else {
+                20
+            },
+            min_retry_concurrency: if pb.min_retry_concurrency > 0 {
+                pb.min_retry_concurrency as usize
+            } else {
+                3
+            },
+        }
+    }
+}
+
+/// When and how a failed attempt is retried. Without `retry_on`, only connect failures are
+/// retried: the request never reached the upstream, so replaying it is safe for any method.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicyRule {
+    pub retry_on_statuses: Vec<u16>,
+    pub retry_on_reset: bool,
+    pub retry_on_connect_failure: bool,
+    pub retry_on_timeout: bool,
+    pub per_try_timeout: Option<Duration>,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    /// request bodies up to this size are buffered for replay; 0 = bodies are never retried
+    pub max_body_bytes: usize,
+}
+
+impl Default for RetryPolicyRule {
+    fn default() -> Self {
+        Self {
+            retry_on_statuses: Vec::new(),
+            retry_on_reset: false,
+            retry_on_connect_failure: true,
+            retry_on_timeout: false,
+            per_try_timeout: None,
+            base_backoff: Duration::from_millis(25),
+            max_backoff: Duration::from_millis(250),
+            max_body_bytes: 0,
+        }
+    }
+}
+
+impl RetryPolicyRule {
+    fn from_pb(cluster: &str, pb: &RetryPolicy) -> Self {
+        let mut rule = RetryPolicyRule::default();
+        if !pb.retry_on.is_empty() {
+            rule.retry_on_connect_failure = false;
+        }
+        for condition in &pb.retry_on {
+            match condition.trim() {
+                "reset" => rule.retry_on_reset = true,
+                "connect-failure" => rule.retry_on_connect_failure = true,
+                "timeout" => rule.retry_on_timeout = true,
+                "gateway-error" => rule.retry_on_statuses.extend([502, 503, 504]),
+                other => match other.parse::<u16>() {
+                    Ok(status) if (500..600).contains(&status) => {
+                        rule.retry_on_statuses.push(status)
+                    }
+                    _ => warn!(%cluster, retry_on = %other, "ignoring unsupported retry condition"),
+                },
+            }
+        }
+        rule.retry_on_statuses.sort_unstable();
+        rule.retry_on_statuses.dedup();
+
+        if pb.per_try_timeout_ms > 0 {
+            rule.per_try_timeout = Some(Duration::from_millis(pb.per_try_timeout_ms as u64));
+        }
+        if pb.base_backoff_ms > 0 {
+            rule.base_backoff = Duration::from_millis(pb.base_backoff_ms as u64);
+            rule.max_backoff = rule.base_backoff * 10;
+        }
+        if pb.max_backoff_ms > 0 {
+            rule.max_backoff =
+                Duration::from_millis(pb.max_backoff_ms as u64).max(rule.base_backoff);
+        }
+        rule.max_body_bytes = pb.max_body_bytes.max(0) as usize;
+        rule
+    }
+
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.retry_on_statuses.contains(&status)
+    }
+}
+
+/// Race a second attempt against a slow first one; idempotent methods only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HedgePolicyRule {
+    pub delay: Duration,
+    pub methods: Vec<Method>,
+}
+
+impl HedgePolicyRule {
+    fn from_pb(cluster: &str, pb: &HedgePolicy) -> Option<Self> {
+        if pb.delay_ms <= 0 {
+            return None;
+        }
+        let mut methods: Vec<Method> = Vec::new();
+        for name in &pb.methods {
+            match Method::from_bytes(name.trim().to_ascii_uppercase().as_bytes()) {
+                Ok(method) if method.is_idempotent() => methods.push(method),
+                _ => warn!(%cluster, method = %name, "ignoring non-idempotent hedge method"),
+            }
+        }
+        if pb.methods.is_empty() {
+            methods = vec![Method::GET, Method::HEAD];
+        }
+        if methods.is_empty() {
+            return None;
+        }
+        Some(HedgePolicyRule {
+            delay: Duration::from_millis(pb.delay_ms as u64),
+            methods,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ClusterRule {
     name: String,
@@ -113,8 +491,111 @@ pub struct ClusterRule {
     pub request_headers: Arc<Vec<HeaderRewriteRule>>,
     pub backend_tls_insecure_skip_verify: bool,
     rr_cursor: Arc<AtomicUsize>,
-    least_conn_cursor: Arc<DashMap<EndpointKey, Arc<AtomicUsize>>>,
+    /// current weights for smooth weighted round-robin, indexed like `endpoints`
+    wrr_current: Arc<Mutex<Vec<i64>>>,
+    /// in-flight requests per endpoint, indexed like `endpoints`
+    active: Arc<Vec<Arc<AtomicUsize>>>,
+    /// latency per endpoint, indexed like `endpoints`
+    stats: Arc<Vec<Arc<EndpointStats>>>,
+    /// splitmix64 state for P2C sampling and retry jitter
+    p2c_seed: Arc<AtomicU64>,
+    /// endpoint indexes grouped by locality: same zone, same region, everything else
+    priorities: Arc<Vec<Vec<usize>>>,
+    priority_cursor: Arc<AtomicUsize>,
+    /// RingHash/Maglev lookup table per priority level
+    hash_tables: Arc<Vec<HashTable>>,
+    pub hash_policy: Option<HashPolicyRule>,
+    pub session_affinity: Option<Arc<SessionAffinityRule>>,
+    /// endpoint id of affinity cookies -> endpoint index
+    sticky_endpoints: Arc<HashMap<String, usize>>,
+    pub health_check: Option<Arc<HealthCheckRule>>,
+    pub outlier_detection: Option<Arc<OutlierDetectionRule>>,
+    /// health per endpoint, indexed like `endpoints`; empty without health checks
+    /// and outlier detection
+    health: Arc<Vec<Arc<HealthState>>>,
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    pub retry_policy: Arc<RetryPolicyRule>,
+    pub retry_budget: Option<Arc<RetryBudget>>,
+    pub hedge_policy: Option<Arc<HedgePolicyRule>>,
     pub auth: Option<Arc<AuthConfigDex>>,
+    /// the config this rule was built from, to reuse the rule while it does not change
+    source: Arc<ClusterPb>,
+    /// parts of `source` that were rejected while building the rule
+    rejected: Vec<Rejection>,
+}
+
+/// Where the consistent-hash key is taken from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HashPolicyRule {
+    Header(HeaderName),
+    Cookie(String),
+    QueryParameter(String),
+    SourceIp,
+}
+
+impl HashPolicyRule {
+    fn from_pb(pb: &HashPolicy) -> Option<Self> {
+        let name = pb.name.trim();
+        match pb.source.as_str() {
+            "Header" => HeaderName::from_bytes(name.as_bytes())
+                .ok()
+                .map(HashPolicyRule::Header),
+            "Cookie" if !name.is_empty() => Some(HashPolicyRule::Cookie(name.to_string())),
+            "QueryParameter" if !name.is_empty() => {
+                Some(HashPolicyRule::QueryParameter(name.to_string()))
+            }
+            "SourceIP" => Some(HashPolicyRule::SourceIp),
+            _ => None,
+        }
+    }
+}
+
+/// Share of traffic a partially available priority level still receives (Envoy default 1.4).
+const OVERPROVISIONING_FACTOR: usize = 140;
+
+/// Zone and region of the node this dataplane runs on.
+#[derive(Clone, Debug, Default)]
+pub struct Locality {
+    pub zone: String,
+    pub region: String,
+}
+
+impl Locality {
+    pub fn from_env() -> Self {
+        let read = |name: &str| {
+            std::env::var(name)
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+        Self {
+            zone: read("NODE_ZONE"),
+            region: read("NODE_REGION"),
+        }
+    }
+
+    // 0 - same zone, 1 - same region, 2 - remote; everything is local when locality is unknown
+    fn priority(&self, endpoint: &Endpoint) -> usize {
+        let same_region =
+            self.region.is_empty() || endpoint.region.is_empty() || self.region == endpoint.region;
+
+        let unknown = self.zone.is_empty() && self.region.is_empty();
+        if unknown || (!self.zone.is_empty() && self.zone == endpoint.zone && same_region) {
+            0
+        } else if !self.region.is_empty() && self.region == endpoint.region {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn group(&self, endpoints: &[Endpoint]) -> Vec<Vec<usize>> {
+        let mut levels: Vec<Vec<usize>> = vec![Vec::new(); 3];
+        for (idx, endpoint) in endpoints.iter().enumerate() {
+            levels[self.priority(endpoint)].push(idx);
+        }
+        levels.retain(|level| !level.is_empty());
+        levels
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
@@ -130,22 +611,48 @@ impl PathType {
             _ => None,
         }
     }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            PathType::Prefix => "Prefix",
+            PathType::Exact => "Exact",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 enum LBPolicy {
     RoundRobin,
+    WeightedRoundRobin,
     LeastConn,
+    RingHash,
+    Maglev,
+    PowerOfTwoChoices,
 }
 
 impl LBPolicy {
     fn parse(s: &str) -> Option<Self> {
         match s {
             "RoundRobin" => Some(LBPolicy::RoundRobin),
+            "WeightedRoundRobin" => Some(LBPolicy::WeightedRoundRobin),
             "LeastConn" => Some(LBPolicy::LeastConn),
+            "RingHash" => Some(LBPolicy::RingHash),
+            "Maglev" => Some(LBPolicy::Maglev),
+            "P2C" => Some(LBPolicy::PowerOfTwoChoices),
             _ => None,
         }
     }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LBPolicy::RoundRobin => "RoundRobin",
+            LBPolicy::WeightedRoundRobin => "WeightedRoundRobin",
+            LBPolicy::LeastConn => "LeastConn",
+            LBPolicy::RingHash => "RingHash",
+            LBPolicy::Maglev => "Maglev",
+            LBPolicy::PowerOfTwoChoices => "P2C",
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -193,67 +700,280 @@ pub struct HeaderRewriteRule {
     pub mode: HeaderRewriteMode,
 }
 
+/// A part of a snapshot the dataplane could not apply, reported back to the controller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    /// "Route", "Cluster" or "ServerTlsBundle"
+    pub kind: &'static str,
+    pub name: String,
+    pub message: String,
+}
+
+impl Rejection {
+    pub fn new(kind: &'static str, name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// What to do with a snapshot that has rejected parts.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ValidationMode {
+    /// apply everything else and NACK the rejected parts
+    #[default]
+    Permissive,
+    /// NACK the whole snapshot and keep serving the last one that validated
+    Strict,
+}
+
+impl ValidationMode {
+    // SNAPSHOT_VALIDATION: "permissive" (default) or "strict"
+    pub fn from_env() -> Self {
+        let raw = std::env::var("SNAPSHOT_VALIDATION").unwrap_or_default();
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "permissive" => ValidationMode::Permissive,
+            "strict" => ValidationMode::Strict,
+            other => {
+                warn!(value = %other, "unknown SNAPSHOT_VALIDATION, using permissive");
+                ValidationMode::Permissive
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct RouteTable {
     version: String,
+    /// when the snapshot was applied; None before the first one
+    updated_at: Option<SystemTime>,
     routes_by_host: HashMap<String, Arc<Vec<RouteRule>>>, // host name -> route_rule
     clusters: HashMap<String, Arc<ClusterRule>>,          // cluster name -> cluster_rule
+    /// parts of the snapshot left out of the table or applied with a fallback
+    rejected: Vec<Rejection>,
 }
 
 impl Default for RouteTable {
     fn default() -> Self {
         RouteTable {
             version: "".to_string(),
+            updated_at: None,
             routes_by_host: Default::default(),
             clusters: Default::default(),
+            rejected: Vec::new(),
         }
     }
 }
 
 impl RouteTable {
-    // new create sorted hasMap route table for fast routing
-    pub fn new(snapshot: &Snapshot) -> Self {
+    // new create sorted hasMap route table for fast routing; clusters unchanged since
+    // `previous` keep their rule, changed ones still take per-endpoint state from `registry`
+    pub fn new(
+        snapshot: &Snapshot,
+        locality: &Locality,
+        registry: &EndpointRegistry,
+        previous: &RouteTable,
+    ) -> Self {
         // create hashMap clusters
         let mut clusters: HashMap<String, Arc<ClusterRule>> = HashMap::new();
+        let mut rejected = Vec::new();
         for cluster in &snapshot.clusters {
-            let bp = BackendProtocol::parse(&cluster.backend_protocol)
-                .unwrap_or_else(|| BackendProtocol::H1);
+            if let Some(prev) = previous.clusters.get(&cluster.name.to_ascii_lowercase())
+                && *prev.source == *cluster
+            {
+                rejected.extend(prev.rejected.iter().cloned());
+                clusters.insert(cluster.name.to_ascii_lowercase(), prev.clone());
+                continue;
+            }
+            let first_rejected = rejected.len();
+            let bp = BackendProtocol::parse(&cluster.backend_protocol).unwrap_or_else(|| {
+                if !cluster.backend_protocol.is_empty() {
+                    rejected.push(Rejection::new(
+                        "Cluster",
+                        &cluster.name,
+                        format!(
+                            "unsupported backend_protocol {:?}, using h1",
+                            cluster.backend_protocol
+                        ),
+                    ));
+                }
+                BackendProtocol::H1
+            });
 
             if let Some(lb) = LBPolicy::parse(&cluster.lb_policy) {
-                let counters = EndpointKey::build_map(&cluster.endpoints);
-                let request_headers = build_header_rewrites(&cluster.request_headers);
+                if cluster.endpoints.is_empty() {
+                    rejected.push(Rejection::new("Cluster", &cluster.name, "no endpoints"));
+                }
+                let states: Vec<EndpointState> = cluster
+                    .endpoints
+                    .iter()
+                    .map(|ep| {
+                        registry.state(EndpointId {
+                            cluster: cluster.name.clone(),
+                            address: ep.address.clone(),
+                            port: ep.port,
+                        })
+                    })
+                    .collect();
+                // keep rotating from where the previous table was, instead of every
+                // dataplane restarting at the first endpoint after an update
+                let rr_cursor = previous
+                    .clusters
+                    .get(&cluster.name.to_ascii_lowercase())
+                    .map_or_else(Arc::default, |prev| prev.rr_cursor.clone());
+                let request_headers =
+                    build_header_rewrites(&cluster.name, &cluster.request_headers, &mut rejected);
                 let auth = build_auth_runtime(cluster.auth.as_ref());
+                let priorities = locality.group(&cluster.endpoints);
+                let hash_tables = build_hash_tables(lb, &cluster.endpoints, &priorities);
+                let session_affinity = cluster
+                    .session_affinity
+                    .as_ref()
+                    .and_then(SessionAffinityRule::from_pb)
+                    .map(Arc::new);
+                let sticky_endpoints = if session_affinity.is_some() {
+                    // load the key now, so a missing SESSION_AFFINITY_KEY is reported when
+                    // the first affinity cluster is configured rather than on its first request
+                    affinity_key();
+                    cluster
+                        .endpoints
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, ep)| (affinity_endpoint_id(&cluster.name, ep), idx))
+                        .collect()
+                } else {
+                    HashMap::new()
+                };
+                let health_check = cluster
+                    .health_check
+                    .as_ref()
+                    .and_then(HealthCheckRule::from_pb)
+                    .map(Arc::new);
+                let outlier_detection = cluster
+                    .outlier_detection
+                    .as_ref()
+                    .and_then(OutlierDetectionRule::from_pb)
+                    .map(Arc::new);
+                let circuit_breaker = cluster
+                    .circuit_breaker
+                    .as_ref()
+                    .and_then(CircuitBreakerRule::from_pb)
+                    .map(|rule| Arc::new(CircuitBreaker::new(rule)));
+                let retry_policy = cluster
+                    .retry_policy
+                    .as_ref()
+                    .map(|pb| RetryPolicyRule::from_pb(&cluster.name, pb))
+                    .unwrap_or_default();
+                let hedge_policy = cluster
+                    .hedge_policy
+                    .as_ref()
+                    .and_then(|pb| HedgePolicyRule::from_pb(&cluster.name, pb))
+                    .map(Arc::new);
+                // hedging doubles the load on a slow cluster, so it is never left unbounded:
+                // a hedged cluster without a budget gets the default one
+                let retry_budget = match &cluster.retry_budget {
+                    Some(pb) => Some(RetryBudgetRule::from_pb(pb)),
+                    None => hedge_policy.as_ref().map(|_| RetryBudgetRule::default()),
+                }
+                .map(|rule| Arc::new(RetryBudget::new(rule)));
+                let health_states = if health_check.is_some() || outlier_detection.is_some() {
+                    states.iter().map(|state| state.health.clone()).collect()
+                } else {
+                    Vec::new()
+                };
+                let hash_policy = cluster.hash_policy.as_ref().and_then(|pb| {
+                    let rule = HashPolicyRule::from_pb(pb);
+                    if rule.is_none() {
+                        warn!(cluster = %cluster.name, source = %pb.source, "ignoring unsupported hash policy");
+                        rejected.push(Rejection::new(
+                            "Cluster",
+                            &cluster.name,
+                            format!("unsupported hash_policy source {:?}", pb.source),
+                        ));
+                    }
+                    rule
+                });
                 clusters
                     .entry(cluster.name.to_ascii_lowercase())
                     .insert_entry(Arc::from(ClusterRule {
-                        name: "".to_string(), // maybe remove ?
+                        name: cluster.name.clone(),
                         lb_policy: lb,
                         endpoints: cluster.endpoints.clone(),
                         timeout_ms: cluster.timeout_ms,
                         retries: cluster.retries,
-                        rr_cursor: Arc::new(AtomicUsize::new(0)),
-                        least_conn_cursor: counters,
+                        rr_cursor,
+                        wrr_current: Arc::new(Mutex::new(vec![0; cluster.endpoints.len()])),
+                        active: Arc::new(states.iter().map(|state| state.active.clone()).collect()),
+                        stats: Arc::new(states.iter().map(|state| state.stats.clone()).collect()),
+                        p2c_seed: Arc::new(AtomicU64::new(hash_bytes(cluster.name.as_bytes()))),
+                        priorities: Arc::new(priorities),
+                        priority_cursor: Arc::new(AtomicUsize::new(0)),
+                        hash_tables: Arc::new(hash_tables),
+                        hash_policy,
+                        session_affinity,
+                        sticky_endpoints: Arc::new(sticky_endpoints),
+                        health_check,
+                        outlier_detection,
+                        health: Arc::new(health_states),
+                        circuit_breaker,
+                        retry_policy: Arc::new(retry_policy),
+                        retry_budget,
+                        hedge_policy,
                         backend_protocol: bp,
                         request_headers,
                         backend_tls_insecure_skip_verify: cluster.backend_tls_insecure_skip_verify,
                         auth,
+                        source: Arc::new(cluster.clone()),
+                        rejected: rejected[first_rejected..].to_vec(),
                     }));
+            } else {
+                rejected.push(Rejection::new(
+                    "Cluster",
+                    &cluster.name,
+                    format!("unsupported lb_policy {:?}", cluster.lb_policy),
+                ));
             }
         }
 
         // create hasMap routes
         let mut buckets: HashMap<String, Vec<RouteRule>> = HashMap::new();
         for r in &snapshot.routes {
+            let name = format!("{}{}", r.host, r.path);
+            // cluster keys are lowercase, and so are the route references looked up in them
+            let cluster = r.cluster.to_ascii_lowercase();
+            if !clusters.contains_key(&cluster) {
+                rejected.push(Rejection::new(
+                    "Route",
+                    &name,
+                    format!("unknown cluster {:?}", r.cluster),
+                ));
+            }
             if let Some(pt) = PathType::parse(&r.path_type) {
                 buckets
                     .entry(r.host.to_ascii_lowercase())
                     .or_default()
                     .push(RouteRule {
+                        host: r.host.to_ascii_lowercase(),
                         path: r.path.clone(),
                         path_type: pt,
-                        cluster: r.cluster.clone(),
+                        cluster,
                         priority: r.priority,
+                        access_log: if r.access_log_disabled {
+                            RouteAccessLog::Off
+                        } else if r.access_log_sample_percent > 0 {
+                            RouteAccessLog::Sampled(r.access_log_sample_percent.min(100) as u32)
+                        } else {
+                            RouteAccessLog::Default
+                        },
                     });
+            } else {
+                rejected.push(Rejection::new(
+                    "Route",
+                    name,
+                    format!("unsupported path_type {:?}", r.path_type),
+                ));
             }
         }
 
@@ -276,23 +996,25 @@ impl RouteTable {
 
         RouteTable {
             version: snapshot.version.clone(),
-            routes_by_host: routes_by_host,
-            clusters: clusters,
+            updated_at: Some(SystemTime::now()),
+            routes_by_host,
+            clusters,
+            rejected,
         }
     }
 
     // get rule for host
     pub fn choose_route<'a>(&'a self, host: &str, path: &str) -> Option<&'a RouteRule> {
-        if let Some(route_rule) = self.routes_by_host.get(host) {
-            if let Some(rule) = Self::match_in_bucket(route_rule, path) {
-                return Some(rule);
-            }
+        if let Some(route_rule) = self.routes_by_host.get(host)
+            && let Some(rule) = Self::match_in_bucket(route_rule, path)
+        {
+            return Some(rule);
         }
 
-        if let Some(route_rule) = self.routes_by_host.get("") {
-            if let Some(rule) = Self::match_in_bucket(route_rule, path) {
-                return Some(rule);
-            }
+        if let Some(route_rule) = self.routes_by_host.get("")
+            && let Some(rule) = Self::match_in_bucket(route_rule, path)
+        {
+            return Some(rule);
         }
 
         // println!("For host {} found in path {:?}", host, self.routes_by_host);
@@ -303,19 +1025,187 @@ impl RouteTable {
     // get path if match
     // todo make Prefix in more precision and add Implemented
     fn match_in_bucket<'a>(rules: &'a [RouteRule], path: &str) -> Option<&'a RouteRule> {
-        rules.iter().find(|r| match r.path_type {
-            PathType::Exact => r.path == path,
-            PathType::Prefix => path.starts_with(r.path.as_str()),
-        })
+        rules.iter().find(|r| r.matches(path))
+    }
+
+    // choose_route step by step: the rule that matched and every rule tried before it
+    pub fn explain_route<'a>(&'a self, host: &str, path: &str) -> RouteExplanation<'a> {
+        let mut explanation = RouteExplanation {
+            host_bucket: self.routes_by_host.contains_key(host),
+            matched: None,
+            skipped: Vec::new(),
+            other_hosts: Vec::new(),
+        };
+        explanation.other_hosts = self
+            .routes_by_host
+            .iter()
+            .filter(|(bucket, _)| !bucket.is_empty() && bucket.as_str() != host)
+            .flat_map(|(_, rules)| rules.iter().filter(|rule| rule.matches(path)))
+            .collect();
+        explanation
+            .other_hosts
+            .sort_by(|a, b| a.host.cmp(&b.host).then(b.priority.cmp(&a.priority)));
+        let buckets = [host, ""];
+        let buckets = if host.is_empty() {
+            &buckets[1..]
+        } else {
+            &buckets[..]
+        };
+        for bucket in buckets {
+            let Some(rules) = self.routes_by_host.get(*bucket) else {
+                continue;
+            };
+            for rule in rules.iter() {
+                if rule.matches(path) {
+                    explanation.matched = Some(rule);
+                    return explanation;
+                }
+                explanation.skipped.push((rule, rule.miss_reason()));
+            }
+        }
+        explanation
+    }
+
+    // get endpoint by balance algorithm; `hash` is the request key for RingHash/Maglev
+    pub fn get_endpoint(&self, cluster_name: &str, hash: Option<u64>) -> Option<SelectedEndpoint> {
+        let cluster = self.clusters.get(cluster_name)?;
+        let level = cluster.choose_priority()?;
+        let candidates = cluster.healthy_candidates(level);
+        self.balance(cluster, level, &candidates, hash)
+    }
+
+    // the endpoint get_endpoint would pick now, without advancing round-robin, WRR or locality
+    // state; P2C samples at random, so its peek is the candidate with the lowest load score
+    pub fn peek_endpoint(&self, cluster_name: &str, hash: Option<u64>) -> Option<SelectedEndpoint> {
+        let cluster = self.clusters.get(cluster_name)?;
+        let level = cluster.pick_priority(|| cluster.priority_cursor.load(Ordering::Relaxed))?;
+        let candidates = cluster.healthy_candidates(level);
+        let next_round_robin = || {
+            let cursor = cluster.rr_cursor.load(Ordering::Relaxed);
+            candidates.get(cursor % candidates.len().max(1)).copied()
+        };
+
+        let idx = match cluster.lb_policy {
+            LBPolicy::RoundRobin => next_round_robin()?,
+            LBPolicy::WeightedRoundRobin => {
+                let current = cluster
+                    .wrr_current
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                let mut best: Option<(usize, i64)> = None;
+                for &idx in candidates.iter() {
+                    let weight = i64::from(cluster.endpoints[idx].weight.max(0));
+                    if weight > 0 && best.is_none_or(|(_, b)| current[idx] + weight > b) {
+                        best = Some((idx, current[idx] + weight));
+                    }
+                }
+                best?.0
+            }
+            LBPolicy::LeastConn => candidates.iter().copied().min_by_key(|&idx| {
+                cluster
+                    .counter_for_index(idx)
+                    .map_or(0, |counter| counter.load(Ordering::Relaxed))
+            })?,
+            LBPolicy::PowerOfTwoChoices if candidates.len() >= 2 => candidates
+                .iter()
+                .copied()
+                .min_by(|&a, &b| cluster.load_score(a).total_cmp(&cluster.load_score(b)))?,
+            LBPolicy::PowerOfTwoChoices => next_round_robin()?,
+            LBPolicy::RingHash | LBPolicy::Maglev => hash
+                .and_then(|h| cluster.hash_pick(level, &candidates, h))
+                .or_else(next_round_robin)?,
+        };
+        cluster.selected(idx)
     }
 
-    // get endpoint by balance algorithm
-    pub fn get_endpoint(&self, cluster_name: &str) -> Option<SelectedEndpoint> {
+    // endpoint for a retry: the same LB policy over the endpoints not tried yet, spilling over
+    // to other localities before going back to an endpoint that already failed
+    pub fn get_retry_endpoint(
+        &self,
+        cluster_name: &str,
+        hash: Option<u64>,
+        tried: &[Endpoint],
+    ) -> Option<SelectedEndpoint> {
         let cluster = self.clusters.get(cluster_name)?;
+        let level = cluster.choose_priority()?;
+        let untried = |level: usize| -> Vec<usize> {
+            cluster
+                .healthy_candidates(level)
+                .iter()
+                .copied()
+                .filter(|&idx| {
+                    let ep = &cluster.endpoints[idx];
+                    !tried
+                        .iter()
+                        .any(|t| t.address == ep.address && t.port == ep.port)
+                })
+                .collect()
+        };
+
+        let picked = std::iter::once(level)
+            .chain((0..cluster.priorities.len()).filter(|&l| l != level))
+            .map(|l| (l, untried(l)))
+            .find(|(_, candidates)| !candidates.is_empty())
+            .and_then(|(l, candidates)| self.balance(cluster, l, &candidates, hash));
+        picked.or_else(|| self.get_endpoint(cluster_name, hash))
+    }
+
+    fn balance(
+        &self,
+        cluster: &ClusterRule,
+        level: usize,
+        candidates: &[usize],
+        hash: Option<u64>,
+    ) -> Option<SelectedEndpoint> {
         match cluster.lb_policy {
-            LBPolicy::RoundRobin => self.round_robin(cluster),
-            LBPolicy::LeastConn => self.least_conn(cluster),
+            LBPolicy::RoundRobin => self.round_robin(cluster, candidates),
+            LBPolicy::WeightedRoundRobin => self.weighted_round_robin(cluster, candidates),
+            LBPolicy::LeastConn => self.least_conn(cluster, candidates),
+            LBPolicy::PowerOfTwoChoices => self.power_of_two_choices(cluster, candidates),
+            LBPolicy::RingHash | LBPolicy::Maglev => {
+                self.consistent_hash(cluster, level, candidates, hash)
+            }
+        }
+    }
+
+    // endpoint pinned by a session affinity cookie; None if the cookie does not verify or the
+    // endpoint is gone, unhealthy, ejected or drained, so the request goes through the LB
+    pub fn get_pinned_endpoint(
+        &self,
+        cluster_name: &str,
+        cookie: &str,
+    ) -> Option<SelectedEndpoint> {
+        let cluster = self.clusters.get(cluster_name)?;
+        let (id, mac) = cookie.split_once('.')?;
+        let idx = *cluster.sticky_endpoints.get(id)?;
+        let endpoint = cluster.endpoints.get(idx)?;
+        if !verify_affinity_mac(&cluster.name, endpoint, mac) || !cluster.is_available(idx) {
+            return None;
         }
+        cluster.selected(idx)
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn updated_at(&self) -> Option<SystemTime> {
+        self.updated_at
+    }
+
+    pub fn rejected(&self) -> &[Rejection] {
+        &self.rejected
+    }
+
+    pub fn clusters(&self) -> impl Iterator<Item = &Arc<ClusterRule>> {
+        self.clusters.values()
+    }
+
+    // route buckets per host ("" is the default host), each in match order
+    pub fn routes(&self) -> impl Iterator<Item = (&str, &[RouteRule])> {
+        self.routes_by_host
+            .iter()
+            .map(|(host, rules)| (host.as_str(), rules.as_slice()))
     }
 
     // get cluster rule timeouts, retries etc...
@@ -325,82 +1215,426 @@ impl RouteTable {
     }
 
     // RoundRobin algorithm
-    fn round_robin(&self, cluster: &ClusterRule) -> Option<SelectedEndpoint> {
-        let len = cluster.endpoints.len();
+    fn round_robin(&self, cluster: &ClusterRule, candidates: &[usize]) -> Option<SelectedEndpoint> {
+        let len = candidates.len();
         if len == 0 {
             return None;
         }
-        let idx = cluster.rr_cursor.fetch_add(1, Ordering::Relaxed) % len;
-        let endpoint = cluster.endpoints[idx].clone();
-        let counter = cluster.counter_for_index(idx);
-        Some(SelectedEndpoint { endpoint, counter })
+        let idx = candidates[cluster.rr_cursor.fetch_add(1, Ordering::Relaxed) % len];
+        cluster.selected(idx)
+    }
+
+    // Smooth WeightedRoundRobin algorithm (nginx): every pick adds each endpoint's weight to its
+    // current weight, takes the largest and subtracts the total from it. Weight 0 means drained.
+    fn weighted_round_robin(
+        &self,
+        cluster: &ClusterRule,
+        candidates: &[usize],
+    ) -> Option<SelectedEndpoint> {
+        let idx = {
+            let mut current = cluster
+                .wrr_current
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            let mut total: i64 = 0;
+            let mut best: Option<usize> = None;
+            for &idx in candidates {
+                let weight = i64::from(cluster.endpoints[idx].weight.max(0));
+                if weight == 0 {
+                    continue;
+                }
+                current[idx] += weight;
+                total += weight;
+                if best.is_none_or(|b| current[idx] > current[b]) {
+                    best = Some(idx);
+                }
+            }
+
+            let idx = best?;
+            current[idx] -= total;
+            idx
+        };
+
+        cluster.selected(idx)
+    }
+
+    // RingHash/Maglev: same key -> same endpoint; requests without a key are spread round-robin
+    fn consistent_hash(
+        &self,
+        cluster: &ClusterRule,
+        level: usize,
+        candidates: &[usize],
+        hash: Option<u64>,
+    ) -> Option<SelectedEndpoint> {
+        let picked = hash.and_then(|h| cluster.hash_pick(level, candidates, h));
+        let Some(idx) = picked else {
+            return self.round_robin(cluster, candidates);
+        };
+
+        cluster.selected(idx)
+    }
+
+    // P2C: sample two distinct endpoints, take the one with lower latency EWMA x in-flight
+    fn power_of_two_choices(
+        &self,
+        cluster: &ClusterRule,
+        candidates: &[usize],
+    ) -> Option<SelectedEndpoint> {
+        let len = candidates.len();
+        if len < 2 {
+            return self.round_robin(cluster, candidates);
+        }
+
+        let r = cluster.next_random();
+        let a = (r % len as u64) as usize;
+        let b = (a + 1 + ((r >> 32) % (len as u64 - 1)) as usize) % len;
+        let (a, b) = (candidates[a], candidates[b]);
+
+        let idx = if cluster.load_score(b) < cluster.load_score(a) {
+            b
+        } else {
+            a
+        };
+        cluster.selected(idx)
     }
 
     // LeastConn algorithm
-    fn least_conn(&self, cluster: &ClusterRule) -> Option<SelectedEndpoint> {
-        let len = cluster.endpoints.len();
-        if len == 0 {
+    fn least_conn(&self, cluster: &ClusterRule, candidates: &[usize]) -> Option<SelectedEndpoint> {
+        if candidates.is_empty() {
             return None;
         }
 
-        let min = cluster
-            .least_conn_cursor
-            .as_ref()
+        let min = candidates
             .iter()
-            .min_by_key(|entry| entry.value().load(Ordering::Relaxed));
+            .filter_map(|&idx| cluster.counter_for_index(idx).map(|counter| (idx, counter)))
+            .min_by_key(|(_, counter)| counter.load(Ordering::Relaxed));
 
-        if let Some(entry) = min {
-            let idx = entry.key().index;
-            if let Some(endpoint) = cluster.endpoints.get(idx).cloned() {
-                let counter = Some(entry.value().clone());
-                return Some(SelectedEndpoint { endpoint, counter });
-            }
+        if let Some((idx, counter)) = min
+            && let Some(endpoint) = cluster.endpoints.get(idx).cloned()
+        {
+            return Some(SelectedEndpoint {
+                endpoint,
+                counter: Some(counter),
+                stats: cluster.stats.get(idx).cloned(),
+                health: cluster.health.get(idx).cloned(),
+            });
         }
 
-        self.round_robin(cluster)
+        self.round_robin(cluster, candidates)
     }
 }
 
 impl ClusterRule {
-    fn counter_for_index(&self, idx: usize) -> Option<Arc<AtomicUsize>> {
-        let endpoint = self.endpoints.get(idx)?;
-        let key = EndpointKey::from_endpoint(idx, endpoint);
-        self.least_conn_cursor
-            .get(&key)
-            .map(|entry| entry.value().clone())
+    // pick a locality level: each level takes traffic in proportion to its available endpoints
+    // (scaled by OVERPROVISIONING_FACTOR), the rest spills over to the next level. RingHash and
+    // Maglev take the first level with capacity, so a key keeps its endpoint between requests.
+    fn choose_priority(&self) -> Option<usize> {
+        self.pick_priority(|| self.priority_cursor.fetch_add(1, Ordering::Relaxed))
     }
-}
 
-impl EndpointKey {
-    fn from_endpoint(index: usize, endpoint: &Endpoint) -> Self {
-        Self {
-            address: endpoint.address.clone(),
-            port: endpoint.port,
-            index,
+    // choose_priority with the spill-over roll taken from `cursor`
+    fn pick_priority(&self, cursor: impl FnOnce() -> usize) -> Option<usize> {
+        if self.priorities.len() <= 1 {
+            return (!self.priorities.is_empty()).then_some(0);
+        }
+
+        let mut loads = Vec::with_capacity(self.priorities.len());
+        let mut remaining: usize = 100;
+        for level in self.priorities.iter() {
+            let available = level.iter().filter(|&&idx| self.is_available(idx)).count();
+            let health = (available * OVERPROVISIONING_FACTOR / level.len()).min(100);
+            let load = health.min(remaining);
+            remaining -= load;
+            loads.push(load);
+        }
+
+        if matches!(self.lb_policy, LBPolicy::RingHash | LBPolicy::Maglev) {
+            return Some(loads.iter().position(|&load| load > 0).unwrap_or(0));
+        }
+
+        let total: usize = loads.iter().sum();
+        if total == 0 {
+            // nothing is available anywhere, let the LB policy pick from the local level
+            return Some(0);
+        }
+
+        let mut roll = cursor() % total;
+        for (level, load) in loads.into_iter().enumerate() {
+            if roll < load {
+                return Some(level);
+            }
+            roll -= load;
+        }
+        Some(0)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    // requests in flight per endpoint, as counted by the LB
+    pub fn active_requests(&self) -> impl Iterator<Item = (&Endpoint, usize)> {
+        self.endpoints.iter().enumerate().map(|(idx, endpoint)| {
+            let active = self
+                .counter_for_index(idx)
+                .map_or(0, |c| c.load(Ordering::Relaxed));
+            (endpoint, active)
+        })
+    }
+
+    pub fn lb_policy(&self) -> &'static str {
+        self.lb_policy.as_str()
+    }
+
+    // runtime state of every endpoint, in snapshot order
+    pub fn endpoint_status(&self) -> impl Iterator<Item = EndpointStatus<'_>> {
+        self.endpoints.iter().enumerate().map(|(idx, endpoint)| {
+            let priority = self
+                .priorities
+                .iter()
+                .position(|level| level.contains(&idx))
+                .unwrap_or(0);
+            EndpointStatus {
+                endpoint,
+                priority,
+                active: self
+                    .counter_for_index(idx)
+                    .map_or(0, |c| c.load(Ordering::Relaxed)),
+                latency_ewma_us: self.stats.get(idx).map_or(0.0, |s| s.latency_ewma_us()),
+                health: self.health.get(idx).map(|h| h.status()),
+            }
+        })
+    }
+
+    fn is_healthy(&self, idx: usize) -> bool {
+        self.health.get(idx).is_none_or(|h| h.is_healthy())
+    }
+
+    // endpoints of a priority level that pass health checks; the whole level when none do,
+    // so a failing health check degrades to "try anyway" instead of a blackhole
+    fn healthy_candidates(&self, level: usize) -> Cow<'_, [usize]> {
+        let all = self.priorities[level].as_slice();
+        if self.health.is_empty() {
+            return Cow::Borrowed(all);
+        }
+        let healthy: Vec<usize> = all
+            .iter()
+            .copied()
+            .filter(|&idx| self.is_healthy(idx))
+            .collect();
+        if healthy.is_empty() {
+            Cow::Borrowed(all)
+        } else {
+            Cow::Owned(healthy)
+        }
+    }
+
+    // unhealthy and drained (weight 0, for weight-aware policies) endpoints are not capacity
+    fn is_available(&self, idx: usize) -> bool {
+        let Some(endpoint) = self.endpoints.get(idx) else {
+            return false;
+        };
+        if !self.is_healthy(idx) {
+            return false;
+        }
+        matches!(
+            self.lb_policy,
+            LBPolicy::RoundRobin | LBPolicy::LeastConn | LBPolicy::PowerOfTwoChoices
+        ) || endpoint.weight > 0
+    }
+
+    // endpoint owning `hash` on the level's table, among the candidates only
+    fn hash_pick(&self, level: usize, candidates: &[usize], hash: u64) -> Option<usize> {
+        let mut accepted = vec![false; self.endpoints.len()];
+        for &idx in candidates {
+            accepted[idx] = true;
+        }
+        self.hash_tables.get(level)?.pick(hash, |idx| accepted[idx])
+    }
+
+    fn selected(&self, idx: usize) -> Option<SelectedEndpoint> {
+        let endpoint = self.endpoints.get(idx)?.clone();
+        let counter = self.counter_for_index(idx);
+        let stats = self.stats.get(idx).cloned();
+        let health = self.health.get(idx).cloned();
+        Some(SelectedEndpoint {
+            endpoint,
+            counter,
+            stats,
+            health,
+        })
+    }
+
+    // passive outlier detection: feed the outcome of a real request to `health`
+    pub fn record_outcome(&self, health: &HealthState, endpoint: &Endpoint, failed: bool) {
+        let Some(rule) = self.outlier_detection.as_deref() else {
+            return;
+        };
+        if !health.record_outcome(failed, rule) {
+            return;
+        }
+
+        let total = self.health.len();
+        let ejected = self.health.iter().filter(|h| h.is_ejected()).count();
+        let allowed = (total * rule.max_ejection_percent as usize / 100)
+            .max(1)
+            .min(total.saturating_sub(1));
+        if ejected >= allowed {
+            warn!(
+                cluster = %self.name, address = %endpoint.address, port = endpoint.port,
+                ejected, "outlier not ejected: max ejection percent reached"
+            );
+            return;
+        }
+
+        let duration = health.eject(rule);
+        warn!(
+            cluster = %self.name, address = %endpoint.address, port = endpoint.port,
+            ejection_ms = duration.as_millis() as u64, "outlier endpoint ejected"
+        );
+    }
+
+    // (latency + 1us) * (in-flight + 1): endpoints without samples yet look fast
+    fn load_score(&self, idx: usize) -> f64 {
+        let latency = self.stats.get(idx).map_or(0.0, |s| s.latency_ewma_us());
+        let in_flight = self
+            .counter_for_index(idx)
+            .map_or(0, |c| c.load(Ordering::Relaxed));
+        (latency + 1.0) * (in_flight as f64 + 1.0)
+    }
+
+    fn next_random(&self) -> u64 {
+        splitmix64(&self.p2c_seed)
+    }
+
+    // delay before retry number `retry` (1-based): exponential backoff with full jitter
+    pub fn retry_backoff(&self, retry: u32) -> Duration {
+        let policy = &self.retry_policy;
+        let ceiling = policy
+            .base_backoff
+            .saturating_mul(1 << retry.saturating_sub(1).min(16))
+            .min(policy.max_backoff);
+        let ceiling_us = ceiling.as_micros() as u64;
+        if ceiling_us == 0 {
+            return Duration::ZERO;
         }
+        Duration::from_micros(self.next_random() % (ceiling_us + 1))
     }
 
-    fn build_map(endpoints: &[Endpoint]) -> Arc<DashMap<EndpointKey, Arc<AtomicUsize>>> {
-        let counters = DashMap::with_capacity(endpoints.len());
-        for (index, endpoint) in endpoints.iter().enumerate() {
-            let key = EndpointKey::from_endpoint(index, endpoint);
-            counters.insert(key, Arc::new(AtomicUsize::new(0)));
+    // value of the affinity cookie that pins a client to `endpoint`
+    pub fn affinity_cookie(&self, endpoint: &Endpoint) -> String {
+        affinity_cookie_value(&self.name, endpoint)
+    }
+
+    fn counter_for_index(&self, idx: usize) -> Option<Arc<AtomicUsize>> {
+        self.active.get(idx).cloned()
+    }
+}
+
+// key of the affinity cookie MAC: SESSION_AFFINITY_KEY when set, so replicas accept each
+// other's cookies; a random per-process key otherwise
+fn affinity_key() -> &'static hmac::Key {
+    static KEY: OnceLock<hmac::Key> = OnceLock::new();
+    KEY.get_or_init(|| match std::env::var("SESSION_AFFINITY_KEY") {
+        Ok(key) if !key.trim().is_empty() => {
+            hmac::Key::new(hmac::HMAC_SHA256, key.trim().as_bytes())
+        }
+        _ => {
+            warn!(
+                "SESSION_AFFINITY_KEY is not set: affinity cookies are signed with a random \
+                 per-process key, so other replicas and restarted dataplanes will not honour them"
+            );
+            hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .expect("system random generator")
         }
-        Arc::new(counters)
+    })
+}
+
+fn affinity_message(cluster_name: &str, endpoint: &Endpoint) -> String {
+    format!("{}|{}:{}", cluster_name, endpoint.address, endpoint.port)
+}
+
+// opaque per-cluster id, so pod addresses are not exposed to clients
+fn affinity_endpoint_id(cluster_name: &str, endpoint: &Endpoint) -> String {
+    format!(
+        "{:016x}",
+        hash_bytes(affinity_message(cluster_name, endpoint).as_bytes())
+    )
+}
+
+// "<endpoint id>.<HMAC-SHA256>": the MAC keeps clients from pinning themselves to an
+// endpoint of their choice
+fn affinity_cookie_value(cluster_name: &str, endpoint: &Endpoint) -> String {
+    let message = affinity_message(cluster_name, endpoint);
+    let tag = hmac::sign(affinity_key(), message.as_bytes());
+    let mac: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    format!("{}.{}", affinity_endpoint_id(cluster_name, endpoint), mac)
+}
+
+// constant-time check of the MAC part of an affinity cookie
+fn verify_affinity_mac(cluster_name: &str, endpoint: &Endpoint, mac: &str) -> bool {
+    if !mac.len().is_multiple_of(2) || !mac.is_ascii() {
+        return false;
     }
+    let tag: Option<Vec<u8>> = (0..mac.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&mac[i..i + 2], 16).ok())
+        .collect();
+    let Some(tag) = tag else {
+        return false;
+    };
+    let message = affinity_message(cluster_name, endpoint);
+    hmac::verify(affinity_key(), message.as_bytes(), &tag).is_ok()
 }
 
-fn build_header_rewrites(items: &[HeaderRewrite]) -> Arc<Vec<HeaderRewriteRule>> {
+fn build_hash_tables(
+    lb_policy: LBPolicy,
+    endpoints: &[Endpoint],
+    priorities: &[Vec<usize>],
+) -> Vec<HashTable> {
+    priorities
+        .iter()
+        .filter_map(|level| match lb_policy {
+            LBPolicy::RingHash => Some(HashTable::Ring(RingHash::build(endpoints, level))),
+            LBPolicy::Maglev => Some(HashTable::Maglev(Maglev::build(endpoints, level))),
+            _ => None,
+        })
+        .collect()
+}
+
+fn build_header_rewrites(
+    cluster: &str,
+    items: &[HeaderRewrite],
+    rejected: &mut Vec<Rejection>,
+) -> Arc<Vec<HeaderRewriteRule>> {
     let mut rewrites = Vec::with_capacity(items.len());
     for item in items {
         let name = item.name.trim();
         if name.is_empty() {
             warn!("ignoring request header rewrite with empty name");
+            rejected.push(Rejection::new(
+                "Cluster",
+                cluster,
+                "request header rewrite with empty name",
+            ));
             continue;
         }
 
         let Some(mode) = HeaderRewriteMode::parse(item.mode.as_str()) else {
             warn!(mode = %item.mode, header = %item.name, "ignoring unsupported header rewrite mode");
+            rejected.push(Rejection::new(
+                "Cluster",
+                cluster,
+                format!(
+                    "unsupported mode {:?} of header rewrite {}",
+                    item.mode, item.name
+                ),
+            ));
             continue;
         };
 
@@ -412,6 +1646,11 @@ fn build_header_rewrites(items: &[HeaderRewrite]) -> Arc<Vec<HeaderRewriteRule>>
 
         let Ok(header_name) = HeaderName::from_bytes(name.as_bytes()) else {
             warn!(header = %item.name, "ignoring header rewrite with invalid name");
+            rejected.push(Rejection::new(
+                "Cluster",
+                cluster,
+                format!("invalid header rewrite name {:?}", item.name),
+            ));
             continue;
         };
 
@@ -423,3 +1662,520 @@ fn build_header_rewrites(items: &[HeaderRewrite]) -> Arc<Vec<HeaderRewriteRule>>
     }
     Arc::new(rewrites)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::argon_config::{Cluster, Route, SessionAffinity};
+
+    fn endpoint(address: &str, weight: i32) -> Endpoint {
+        Endpoint {
+            address: address.into(),
+            port: 8080,
+            weight,
+            ..Default::default()
+        }
+    }
+
+    fn cluster(name: &str, lb_policy: &str, endpoints: Vec<Endpoint>) -> Cluster {
+        Cluster {
+            name: name.into(),
+            lb_policy: lb_policy.into(),
+            endpoints,
+            ..Default::default()
+        }
+    }
+
+    fn route(host: &str, path: &str, cluster: &str) -> Route {
+        Route {
+            host: host.into(),
+            path: path.into(),
+            path_type: "Prefix".into(),
+            cluster: cluster.into(),
+            ..Default::default()
+        }
+    }
+
+    fn table(snapshot: &Snapshot) -> RouteTable {
+        RouteTable::new(
+            snapshot,
+            &Locality::default(),
+            &EndpointRegistry::default(),
+            &RouteTable::default(),
+        )
+    }
+
+    fn rejection_messages(table: &RouteTable) -> Vec<(&'static str, String, String)> {
+        table
+            .rejected()
+            .iter()
+            .map(|r| (r.kind, r.name.clone(), r.message.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn rejects_invalid_parts_and_keeps_the_rest() {
+        let snapshot = Snapshot {
+            clusters: vec![
+                cluster("good", "RoundRobin", vec![endpoint("10.0.0.1", 1)]),
+                cluster("bad-lb", "Fastest", vec![endpoint("10.0.0.2", 1)]),
+                cluster("empty", "RoundRobin", vec![]),
+            ],
+            routes: vec![
+                route("a.example", "/", "good"),
+                Route {
+                    path_type: "Regex".into(),
+                    ..route("a.example", "/re", "good")
+                },
+                route("a.example", "/missing", "nope"),
+            ],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+
+        let rejected = rejection_messages(&table);
+        assert!(
+            rejected
+                .iter()
+                .any(|(kind, name, message)| *kind == "Cluster"
+                    && name == "bad-lb"
+                    && message.contains("lb_policy"))
+        );
+        assert!(
+            rejected
+                .iter()
+                .any(|(kind, name, message)| *kind == "Cluster"
+                    && name == "empty"
+                    && message == "no endpoints")
+        );
+        assert!(rejected.iter().any(|(kind, name, message)| *kind == "Route"
+            && name == "a.example/re"
+            && message.contains("path_type")));
+        assert!(rejected.iter().any(|(kind, name, message)| *kind == "Route"
+            && name == "a.example/missing"
+            && message.contains("unknown cluster")));
+        assert_eq!(rejected.len(), 4);
+
+        let rule = table
+            .choose_route("a.example", "/")
+            .expect("valid route kept");
+        assert!(table.get_endpoint(&rule.cluster, None).is_some());
+        assert!(table.get_cluster_rules("bad-lb").is_none());
+    }
+
+    #[test]
+    fn route_to_mixed_case_cluster_name_resolves() {
+        let snapshot = Snapshot {
+            clusters: vec![cluster(
+                "Default-Web-80",
+                "RoundRobin",
+                vec![endpoint("10.0.0.1", 1)],
+            )],
+            routes: vec![route("a.example", "/", "Default-Web-80")],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+
+        assert!(table.rejected().is_empty(), "{:?}", table.rejected());
+        let rule = table.choose_route("a.example", "/x").expect("route");
+        let selected = table.get_endpoint(&rule.cluster, None).expect("endpoint");
+        assert_eq!(selected.endpoint.address, "10.0.0.1");
+    }
+
+    #[test]
+    fn unchanged_clusters_keep_their_rule() {
+        let mut snapshot = Snapshot {
+            clusters: vec![
+                cluster("same", "RoundRobin", vec![endpoint("10.0.0.1", 1)]),
+                cluster("changed", "RoundRobin", vec![endpoint("10.0.0.2", 1)]),
+            ],
+            ..Default::default()
+        };
+        let registry = EndpointRegistry::default();
+        let first = RouteTable::new(
+            &snapshot,
+            &Locality::default(),
+            &registry,
+            &RouteTable::default(),
+        );
+
+        snapshot.clusters[1].endpoints.push(endpoint("10.0.0.3", 1));
+        let second = RouteTable::new(&snapshot, &Locality::default(), &registry, &first);
+
+        let rule = |table: &RouteTable, name: &str| table.get_cluster_rules(name).expect("cluster");
+        assert!(Arc::ptr_eq(&rule(&first, "same"), &rule(&second, "same")));
+        assert!(!Arc::ptr_eq(
+            &rule(&first, "changed"),
+            &rule(&second, "changed")
+        ));
+        assert_eq!(rule(&second, "changed").source.endpoints.len(), 2);
+    }
+
+    #[test]
+    fn hash_key_keeps_its_endpoint_when_traffic_spills_over() {
+        let mut local = endpoint("10.0.0.1", 1);
+        local.zone = "a".into();
+        let mut drained = endpoint("10.0.0.2", 0);
+        drained.zone = "a".into();
+        let mut remote: Vec<Endpoint> = (3..6)
+            .map(|i| endpoint(&format!("10.0.0.{i}"), 1))
+            .collect();
+        for ep in &mut remote {
+            ep.zone = "b".into();
+        }
+        let mut endpoints = vec![local, drained];
+        endpoints.extend(remote);
+        let snapshot = Snapshot {
+            clusters: vec![cluster("hashed", "Maglev", endpoints)],
+            ..Default::default()
+        };
+        let locality = Locality {
+            zone: "a".into(),
+            region: String::new(),
+        };
+        let table = RouteTable::new(
+            &snapshot,
+            &locality,
+            &EndpointRegistry::default(),
+            &RouteTable::default(),
+        );
+
+        for key in 0..20u64 {
+            let first = table.get_endpoint("hashed", Some(key)).expect("endpoint");
+            for _ in 0..10 {
+                let again = table.get_endpoint("hashed", Some(key)).expect("endpoint");
+                assert_eq!(again.endpoint.address, first.endpoint.address);
+            }
+        }
+    }
+
+    #[test]
+    fn affinity_cookie_pins_only_with_a_valid_mac_and_an_available_endpoint() {
+        let mut sticky = cluster(
+            "sticky",
+            "WeightedRoundRobin",
+            vec![endpoint("10.0.0.1", 1), endpoint("10.0.0.2", 0)],
+        );
+        sticky.session_affinity = Some(SessionAffinity {
+            cookie_name: "argon".into(),
+            ..Default::default()
+        });
+        let snapshot = Snapshot {
+            clusters: vec![sticky],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let rule = table.get_cluster_rules("sticky").expect("cluster");
+
+        let live = rule.affinity_cookie(&rule.endpoints()[0]);
+        let pinned = table.get_pinned_endpoint("sticky", &live).expect("pinned");
+        assert_eq!(pinned.endpoint.address, "10.0.0.1");
+
+        // same endpoint id, MAC of another endpoint or garbage
+        let (id, _) = live.split_once('.').unwrap();
+        let drained = rule.affinity_cookie(&rule.endpoints()[1]);
+        let (_, other_mac) = drained.split_once('.').unwrap();
+        assert!(
+            table
+                .get_pinned_endpoint("sticky", &format!("{id}.{other_mac}"))
+                .is_none()
+        );
+        assert!(table.get_pinned_endpoint("sticky", id).is_none());
+        assert!(
+            table
+                .get_pinned_endpoint("sticky", &format!("{id}.zz"))
+                .is_none()
+        );
+
+        // weight 0 drains the endpoint, so its cookie goes back to the LB
+        assert!(table.get_pinned_endpoint("sticky", &drained).is_none());
+    }
+
+    #[test]
+    fn peek_endpoint_is_the_next_pick_and_leaves_lb_state_alone() {
+        for lb in ["RoundRobin", "WeightedRoundRobin"] {
+            let snapshot = Snapshot {
+                clusters: vec![cluster(
+                    "svc",
+                    lb,
+                    vec![
+                        endpoint("10.0.0.1", 3),
+                        endpoint("10.0.0.2", 1),
+                        endpoint("10.0.0.3", 0),
+                    ],
+                )],
+                ..Default::default()
+            };
+            let table = table(&snapshot);
+            for _ in 0..8 {
+                let peeked = table.peek_endpoint("svc", None).unwrap().endpoint;
+                assert_eq!(table.peek_endpoint("svc", None).unwrap().endpoint, peeked);
+                assert_eq!(
+                    table.get_endpoint("svc", None).unwrap().endpoint,
+                    peeked,
+                    "{lb}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn explain_route_reports_skipped_rules_and_other_hosts() {
+        let snapshot = Snapshot {
+            clusters: vec![cluster("svc", "RoundRobin", vec![endpoint("10.0.0.1", 1)])],
+            routes: vec![
+                route("a.example", "/api/v2", "svc"),
+                route("a.example", "/", "svc"),
+                route("b.example", "/api", "svc"),
+                route("", "/api", "svc"),
+            ],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+
+        let explanation = table.explain_route("a.example", "/api/v1");
+        assert_eq!(explanation.matched.unwrap().path(), "/");
+        let skipped: Vec<_> = explanation
+            .skipped
+            .iter()
+            .map(|(rule, _)| rule.path())
+            .collect();
+        assert_eq!(skipped, ["/api/v2"]);
+        let other_hosts: Vec<_> = explanation.other_hosts.iter().map(|r| r.host()).collect();
+        assert_eq!(other_hosts, ["b.example"]);
+    }
+
+    #[test]
+    fn weighted_round_robin_is_smooth_and_skips_drained_endpoints() {
+        let snapshot = Snapshot {
+            clusters: vec![cluster(
+                "svc",
+                "WeightedRoundRobin",
+                vec![
+                    endpoint("10.0.0.1", 5),
+                    endpoint("10.0.0.2", 1),
+                    endpoint("10.0.0.3", 1),
+                    endpoint("10.0.0.4", 0),
+                ],
+            )],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let picks: Vec<String> = (0..7)
+            .map(|_| table.get_endpoint("svc", None).unwrap().endpoint.address)
+            .collect();
+        assert_eq!(
+            picks,
+            [
+                "10.0.0.1", "10.0.0.1", "10.0.0.2", "10.0.0.1", "10.0.0.3", "10.0.0.1", "10.0.0.1"
+            ]
+        );
+
+        let mut counts = HashMap::new();
+        for _ in 0..700 {
+            let address = table.get_endpoint("svc", None).unwrap().endpoint.address;
+            *counts.entry(address).or_insert(0) += 1;
+        }
+        assert_eq!(counts["10.0.0.1"], 500);
+        assert_eq!(counts["10.0.0.2"], 100);
+        assert_eq!(counts["10.0.0.3"], 100);
+        assert!(!counts.contains_key("10.0.0.4"));
+    }
+
+    #[test]
+    fn latency_ewma_starts_at_the_first_sample_and_moves_by_alpha() {
+        let stats = EndpointStats::default();
+        assert_eq!(stats.latency_ewma_us(), 0.0);
+        stats.record_latency(Duration::from_micros(1000));
+        assert_eq!(stats.latency_ewma_us(), 1000.0);
+        stats.record_latency(Duration::from_micros(2000));
+        assert!((stats.latency_ewma_us() - 1300.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn p2c_never_picks_the_slowest_endpoint() {
+        let snapshot = Snapshot {
+            clusters: vec![cluster(
+                "svc",
+                "P2C",
+                vec![
+                    endpoint("10.0.0.1", 1),
+                    endpoint("10.0.0.2", 1),
+                    endpoint("10.0.0.3", 1),
+                ],
+            )],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let cluster = table.get_cluster_rules("svc").unwrap();
+        for (idx, latency_ms) in [(0, 10), (1, 12), (2, 500)] {
+            cluster.stats[idx].record_latency(Duration::from_millis(latency_ms));
+        }
+
+        let mut counts = HashMap::new();
+        for _ in 0..300 {
+            let address = table.get_endpoint("svc", None).unwrap().endpoint.address;
+            *counts.entry(address).or_insert(0) += 1;
+        }
+        assert!(counts["10.0.0.1"] > 0);
+        assert!(counts["10.0.0.2"] > 0);
+        assert!(!counts.contains_key("10.0.0.3"));
+    }
+
+    #[test]
+    fn p2c_weighs_latency_by_requests_in_flight() {
+        let snapshot = Snapshot {
+            clusters: vec![cluster(
+                "svc",
+                "P2C",
+                vec![endpoint("10.0.0.1", 1), endpoint("10.0.0.2", 1)],
+            )],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let cluster = table.get_cluster_rules("svc").unwrap();
+        cluster.stats[0].record_latency(Duration::from_millis(10));
+        cluster.stats[1].record_latency(Duration::from_millis(30));
+        assert_eq!(
+            table.get_endpoint("svc", None).unwrap().endpoint.address,
+            "10.0.0.1"
+        );
+
+        // 10ms x 4 in flight is worse than 30ms x 1
+        cluster.active[0].store(3, Ordering::Relaxed);
+        for _ in 0..10 {
+            assert_eq!(
+                table.get_endpoint("svc", None).unwrap().endpoint.address,
+                "10.0.0.2"
+            );
+        }
+    }
+
+    #[test]
+    fn outlier_ejection_stops_at_max_ejection_percent() {
+        let endpoints = (1..=10)
+            .map(|i| endpoint(&format!("10.0.0.{i}"), 1))
+            .collect();
+        let snapshot = Snapshot {
+            clusters: vec![Cluster {
+                outlier_detection: Some(OutlierDetection {
+                    consecutive_errors: 2,
+                    max_ejection_percent: 30,
+                    ..Default::default()
+                }),
+                ..cluster("svc", "RoundRobin", endpoints)
+            }],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let cluster = table.get_cluster_rules("svc").unwrap();
+
+        for (idx, endpoint) in cluster.endpoints.iter().enumerate() {
+            for _ in 0..2 {
+                cluster.record_outcome(&cluster.health[idx], endpoint, true);
+            }
+        }
+        let ejected = cluster.health.iter().filter(|h| h.is_ejected()).count();
+        assert_eq!(ejected, 3);
+        assert_eq!(cluster.healthy_candidates(0).len(), 7);
+    }
+
+    #[test]
+    fn outlier_ejection_always_allows_one_and_keeps_one() {
+        for (count, expected) in [(2, 1), (4, 1), (1, 0)] {
+            let endpoints = (1..=count)
+                .map(|i| endpoint(&format!("10.0.0.{i}"), 1))
+                .collect();
+            let snapshot = Snapshot {
+                clusters: vec![Cluster {
+                    outlier_detection: Some(OutlierDetection {
+                        consecutive_errors: 1,
+                        ..Default::default()
+                    }),
+                    ..cluster("svc", "RoundRobin", endpoints)
+                }],
+                ..Default::default()
+            };
+            let table = table(&snapshot);
+            let cluster = table.get_cluster_rules("svc").unwrap();
+            for (idx, endpoint) in cluster.endpoints.iter().enumerate() {
+                cluster.record_outcome(&cluster.health[idx], endpoint, true);
+            }
+            let ejected = cluster.health.iter().filter(|h| h.is_ejected()).count();
+            assert_eq!(ejected, expected, "{count} endpoints");
+        }
+    }
+
+    #[test]
+    fn retry_backoff_stays_under_the_doubling_ceiling_and_the_max() {
+        let snapshot = Snapshot {
+            clusters: vec![Cluster {
+                retry_policy: Some(RetryPolicy {
+                    base_backoff_ms: 10,
+                    max_backoff_ms: 50,
+                    ..Default::default()
+                }),
+                ..cluster("svc", "RoundRobin", vec![endpoint("10.0.0.1", 1)])
+            }],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let cluster = table.get_cluster_rules("svc").unwrap();
+
+        for (retry, ceiling_ms) in [(1, 10), (2, 20), (3, 40), (4, 50), (40, 50)] {
+            let delays: Vec<_> = (0..200).map(|_| cluster.retry_backoff(retry)).collect();
+            let ceiling = Duration::from_millis(ceiling_ms);
+            assert!(delays.iter().all(|d| *d <= ceiling), "retry {retry}");
+            // full jitter: delays spread over the whole range
+            assert!(delays.iter().any(|d| *d < ceiling / 4), "retry {retry}");
+            assert!(delays.iter().any(|d| *d > ceiling * 3 / 4), "retry {retry}");
+        }
+    }
+
+    #[test]
+    fn retry_backoff_defaults_and_a_max_below_the_base() {
+        let rule = RetryPolicyRule::from_pb("svc", &RetryPolicy::default());
+        assert_eq!(rule.base_backoff, Duration::from_millis(25));
+        assert_eq!(rule.max_backoff, Duration::from_millis(250));
+
+        let rule = RetryPolicyRule::from_pb(
+            "svc",
+            &RetryPolicy {
+                base_backoff_ms: 100,
+                max_backoff_ms: 10,
+                ..Default::default()
+            },
+        );
+        assert_eq!(rule.max_backoff, rule.base_backoff);
+    }
+
+    #[test]
+    fn only_connect_failures_are_retried_by_default() {
+        let snapshot = Snapshot {
+            clusters: vec![cluster("svc", "RoundRobin", vec![endpoint("10.0.0.1", 1)])],
+            ..Default::default()
+        };
+        let table = table(&snapshot);
+        let rule = &table.get_cluster_rules("svc").unwrap().retry_policy;
+        assert!(rule.retry_on_connect_failure);
+        assert!(!rule.retry_on_reset);
+        assert!(!rule.retry_on_timeout);
+        assert!(rule.retry_on_statuses.is_empty());
+        assert_eq!(
+            **rule,
+            RetryPolicyRule::from_pb("svc", &RetryPolicy::default())
+        );
+
+        let rule = RetryPolicyRule::from_pb(
+            "svc",
+            &RetryPolicy {
+                retry_on: vec!["reset".into(), "timeout".into(), "503".into()],
+                ..Default::default()
+            },
+        );
+        assert!(!rule.retry_on_connect_failure);
+        assert!(rule.retry_on_reset);
+        assert!(rule.retry_on_timeout);
+        assert_eq!(rule.retry_on_statuses, [503]);
+    }
+}
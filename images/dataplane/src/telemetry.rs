@@ -0,0 +1,254 @@
+use http::{HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Uri};
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer, TracerProvider as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use std::sync::Arc;
+
+/// Distributed tracing: W3C trace context in and out, spans exported over OTLP/gRPC.
+pub struct Telemetry {
+    provider: TracerProvider,
+    tracer: opentelemetry_sdk::trace::Tracer,
+    propagator: TraceContextPropagator,
+}
+
+impl Telemetry {
+    // OTEL_EXPORTER_OTLP_ENDPOINT: collector, e.g. http://otel-collector:4317 (unset disables tracing)
+    // OTEL_TRACES_SAMPLER_ARG: ratio of new traces sampled, default 1.0; the parent decides otherwise
+    // OTEL_SERVICE_NAME: default "argon-dataplane"
+    pub fn from_env() -> anyhow::Result<Option<Arc<Telemetry>>> {
+        let endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_default();
+        let endpoint = endpoint.trim();
+        if endpoint.is_empty() {
+            return Ok(None);
+        }
+        let ratio = std::env::var("OTEL_TRACES_SAMPLER_ARG")
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0);
+        let service = std::env::var("OTEL_SERVICE_NAME")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "argon-dataplane".to_string());
+        Self::new(endpoint, ratio, service).map(|telemetry| Some(Arc::new(telemetry)))
+    }
+
+    fn new(endpoint: &str, ratio: f64, service: String) -> anyhow::Result<Telemetry> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                ratio,
+            ))))
+            .with_resource(Resource::new([KeyValue::new("service.name", service)]))
+            .build();
+        let tracer = provider.tracer("argon-dataplane");
+        tracing::info!(%endpoint, ratio, "tracing enabled");
+
+        Ok(Telemetry {
+            provider,
+            tracer,
+            propagator: TraceContextPropagator::new(),
+        })
+    }
+
+    // server span of a request, child of the client's traceparent when it sent one
+    pub fn start_server<B>(&self, req: &Request<B>) -> Context {
+        let parent = self.propagator.extract(&HeaderExtractor(req.headers()));
+        let span = self
+            .tracer
+            .span_builder(req.method().to_string())
+            .with_kind(SpanKind::Server)
+            .with_attributes([
+                KeyValue::new("http.request.method", req.method().to_string()),
+                KeyValue::new("url.path", req.uri().path().to_string()),
+                KeyValue::new("network.protocol.version", format!("{:?}", req.version())),
+            ])
+            .start_with_context(&self.tracer, &parent);
+        parent.with_span(span)
+    }
+
+    // client span of a subrequest (auth, upstream attempt) under `parent`
+    pub fn start_client(
+        &self,
+        parent: &Context,
+        name: String,
+        method: &Method,
+        uri: &Uri,
+    ) -> Context {
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", method.to_string()),
+            KeyValue::new("url.full", uri.to_string()),
+        ];
+        if let Some(host) = uri.host() {
+            attributes.push(KeyValue::new("server.address", host.to_string()));
+        }
+        if let Some(port) = uri.port_u16() {
+            attributes.push(KeyValue::new("server.port", i64::from(port)));
+        }
+        let span = self
+            .tracer
+            .span_builder(name)
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes)
+            .start_with_context(&self.tracer, parent);
+        parent.with_span(span)
+    }
+
+    // write traceparent/tracestate of `cx` into outgoing headers
+    pub fn inject(&self, cx: &Context, headers: &mut HeaderMap) {
+        self.propagator
+            .inject_context(cx, &mut HeaderInjector(headers));
+    }
+
+    // flush spans still queued for export
+    pub fn shutdown(&self) {
+        if let Err(err) = self.provider.shutdown() {
+            tracing::warn!(%err, "failed to flush traces");
+        }
+    }
+}
+
+// end the span of `cx` with the response status or the error that replaced it
+pub fn end_span(cx: &Context, status: Option<StatusCode>, error: Option<&str>) {
+    let span = cx.span();
+    if let Some(status) = status {
+        span.set_attribute(KeyValue::new(
+            "http.response.status_code",
+            i64::from(status.as_u16()),
+        ));
+        if status.is_server_error() {
+            span.set_status(Status::error(status.to_string()));
+        }
+    }
+    if let Some(error) = error {
+        span.set_status(Status::error(error.to_string()));
+    }
+    span.end();
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{
+        TraceService, TraceServiceServer,
+    };
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use opentelemetry_proto::tonic::trace::v1::Span;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+
+    // OTLP collector that hands every exported span to the test
+    struct StubCollector(mpsc::UnboundedSender<Span>);
+
+    #[tonic::async_trait]
+    impl TraceService for StubCollector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            let spans = request
+                .into_inner()
+                .resource_spans
+                .into_iter()
+                .flat_map(|r| r.scope_spans)
+                .flat_map(|s| s.spans);
+            for span in spans {
+                let _ = self.0.send(span);
+            }
+            Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exports_server_and_client_spans_under_the_incoming_trace() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(
+            Server::builder()
+                .add_service(TraceServiceServer::new(StubCollector(tx)))
+                .serve_with_incoming(incoming),
+        );
+
+        let telemetry = Telemetry::new(&format!("http://{addr}"), 1.0, "test".into()).unwrap();
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/api")
+            .header("traceparent", format!("00-{trace_id}-00f067aa0ba902b7-01"))
+            .body(())
+            .unwrap();
+        let server = telemetry.start_server(&req);
+        let upstream: Uri = "http://10.0.0.1:8080/api".parse().unwrap();
+        let client = telemetry.start_client(&server, "upstream".into(), &Method::GET, &upstream);
+        let mut headers = HeaderMap::new();
+        telemetry.inject(&client, &mut headers);
+        end_span(&client, Some(StatusCode::BAD_GATEWAY), None);
+        end_span(&server, Some(StatusCode::OK), None);
+        tokio::task::spawn_blocking(move || telemetry.shutdown())
+            .await
+            .unwrap();
+
+        let mut spans = Vec::new();
+        while spans.len() < 2 {
+            let span = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("collector got no spans")
+                .unwrap();
+            spans.push(span);
+        }
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let server = spans.iter().find(|s| s.name == "GET").unwrap();
+        let client = spans.iter().find(|s| s.name == "upstream").unwrap();
+        assert_eq!(hex(&server.trace_id), trace_id);
+        assert_eq!(hex(&server.parent_span_id), "00f067aa0ba902b7");
+        assert_eq!(client.trace_id, server.trace_id);
+        assert_eq!(client.parent_span_id, server.span_id);
+        assert_eq!(client.status.as_ref().unwrap().code, 2);
+
+        let traceparent = headers["traceparent"].to_str().unwrap();
+        assert_eq!(
+            traceparent,
+            format!("00-{trace_id}-{}-01", hex(&client.span_id))
+        );
+    }
+}